@@ -1,14 +1,32 @@
 use derive_where::derive_where;
-use std::{collections::BTreeMap, mem};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive_where(Debug, Clone, PartialEq, Eq)]
 pub enum Constraint<Db: crate::Db> {
     Ty(Db::Node, Ty<Db>),
     Instantiation(Instantiation<Db>),
     Bound(Bound<Db>),
+
+    /// The type of the node (a variable) may be generalized once it's solved,
+    /// so each use of the variable gets its own copy of the type. The other
+    /// nodes are the variables in scope whose types are shared with the
+    /// variable's uses, like the inputs of enclosing functions; variables in
+    /// their types aren't generalized.
+    Generalize(Db::Node, Vec<Db::Node>),
 }
 
 impl<Db: crate::Db> Constraint<Db> {
+    /// The node the constraint is reported on.
+    pub fn owner(&self) -> Db::Node {
+        match self {
+            Constraint::Ty(node, _) => *node,
+            Constraint::Instantiation(instantiation) => instantiation.source,
+            Constraint::Bound(bound) => bound.0.source,
+            Constraint::Generalize(node, _) => *node,
+        }
+    }
+
     pub fn traverse_nodes_mut(&mut self, f: &mut impl FnMut(&mut Db::Node)) {
         fn traverse_node_ty<Db: crate::Db>(
             node: &mut Db::Node,
@@ -16,7 +34,10 @@ impl<Db: crate::Db> Constraint<Db> {
             f: &mut impl FnMut(&mut Db::Node),
         ) {
             f(node);
+            traverse_ty(ty, f);
+        }
 
+        fn traverse_ty<Db: crate::Db>(ty: &mut Ty<Db>, f: &mut impl FnMut(&mut Db::Node)) {
             ty.traverse_mut(&mut |ty| {
                 if let Ty::Of(node) = ty {
                     f(node);
@@ -28,13 +49,10 @@ impl<Db: crate::Db> Constraint<Db> {
             substitutions: &mut Substitutions<Db>,
             f: &mut impl FnMut(&mut Db::Node),
         ) {
-            substitutions.0 = mem::take(&mut substitutions.0)
-                .into_iter()
-                .map(|(mut node, mut ty)| {
-                    traverse_node_ty(&mut node, &mut ty, f);
-                    (node, ty)
-                })
-                .collect();
+            // The keys are the definition's parameters, which aren't copied
+            for ty in substitutions.0.values_mut() {
+                traverse_ty(ty, f);
+            }
         }
 
         match self {
@@ -42,12 +60,17 @@ impl<Db: crate::Db> Constraint<Db> {
                 traverse_node_ty(node, ty, f);
             }
             Constraint::Instantiation(instantiation) => {
+                f(&mut instantiation.node);
                 traverse_substitutions(&mut instantiation.substitutions, f);
             }
             Constraint::Bound(bound) => {
                 f(&mut bound.0.node);
                 traverse_substitutions(&mut bound.0.substitutions, f);
             }
+            Constraint::Generalize(node, environment) => {
+                f(node);
+                environment.iter_mut().for_each(f);
+            }
         }
     }
 
@@ -64,11 +87,20 @@ impl<Db: crate::Db> Constraint<Db> {
                     f(ty);
                 }
             }
+            Constraint::Generalize(..) => {}
         }
     }
 }
 
 #[derive_where(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(
+    rename_all = "camelCase",
+    bound(
+        serialize = "Db::Node: Serialize",
+        deserialize = "Db::Node: Deserialize<'de>"
+    )
+)]
 pub enum Ty<Db: crate::Db> {
     Unknown(Db::Node),
     Of(Db::Node),
@@ -180,6 +212,11 @@ impl<Db: crate::Db> Ty<Db> {
 }
 
 #[derive_where(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "Db::Node: Serialize",
+    deserialize = "Db::Node: Deserialize<'de>"
+))]
 pub struct Substitutions<Db: crate::Db>(pub BTreeMap<Db::Node, Ty<Db>>);
 
 impl<Db: crate::Db> Substitutions<Db> {
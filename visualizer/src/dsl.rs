@@ -0,0 +1,148 @@
+//! A small textual language for writing constraints directly, along with an
+//! in-memory [`Db`](crate::Db) to solve them with. This makes it possible to
+//! test the solver without going through Wipple source code.
+//!
+//! ```
+//! use visualizer::dsl::Program;
+//!
+//! let source = "
+//! ## Types are declared along with their parameters
+//! type Number
+//! type Maybe value
+//!
+//! ## Constraints in a definition are solved on their own and copied each time
+//! ## it's instantiated. Constraints marked `where` are only copied.
+//! def Show {
+//!     Show : (value) -> Number
+//!     value : 'value
+//! }
+//!
+//! def showNumber {
+//!     instantiate Show for showNumber [value: Number]
+//! }
+//!
+//! def showMaybe {
+//!     instantiate Show for showMaybe [value: Maybe element]
+//!     element : 'element
+//!     where bound Show for showElement [value: element] in showMaybe
+//! }
+//!
+//! instance Show showNumber
+//! instance Show showMaybe
+//!
+//! ## Default instances are used when no other instance matches
+//! def showAny {
+//!     instantiate Show for showAny [value: anything]
+//!     anything : 'anything
+//! }
+//!
+//! default instance Show showAny
+//!
+//! ## Output parameters are determined by the selected instance
+//! def Element {
+//!     Element : (collection) -> element
+//!     collection : 'collection
+//!     element : 'element
+//! }
+//!
+//! output Element element
+//!
+//! ## Everything else is solved right away
+//! x : Number
+//! y : Maybe x
+//! bound Show for show [value: y]
+//!
+//! ## Each instantiation of a generalized variable gets its own copy of the
+//! ## variable's type. `generalize f in a, b` keeps the parts of the type
+//! ## shared with `a` and `b`.
+//! f : (z) -> z
+//! generalize f
+//! instantiate f for g
+//! g : (Number) -> n
+//! ";
+//!
+//! let mut program = Program::parse(source).unwrap();
+//! program.solve();
+//!
+//! let report = program.db.report();
+//! assert!(report.contains("resolved show with showMaybe"));
+//! assert!(report.contains("\nn : Number\n"));
+//! ```
+//!
+//! Types are written as `name` (the type of another node), `'name` (a type
+//! parameter), `?name` (unknown), `Name arg...` (a declared type),
+//! `(a, b) -> c` (a function) and `(a, b)` (a tuple).
+
+mod db;
+mod parse;
+mod print;
+
+pub use db::*;
+pub use parse::*;
+
+use crate::{Constraint, Solver, TyGroups};
+use std::collections::BTreeSet;
+
+/// A parsed constraint program.
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub db: MemoryDb,
+    pub constraints: Vec<Constraint<MemoryDb>>,
+}
+
+impl Program {
+    /// All the constraints to solve, including the definitions' own
+    /// constraints.
+    pub fn all_constraints(&self) -> Vec<Constraint<MemoryDb>> {
+        self.db
+            .definitions()
+            .flat_map(|(_, definition)| definition.constraints.iter().cloned())
+            .chain(self.constraints.iter().cloned())
+            .collect()
+    }
+
+    /// Solve the program's constraints and check its instances for missing
+    /// super instances and overlaps, recording the results in
+    /// [`Program::db`].
+    pub fn solve(&mut self) -> TyGroups<MemoryDb> {
+        let constraints = self.all_constraints();
+
+        let (traits, instances) = self.db.instances().unzip::<_, _, BTreeSet<_>, Vec<_>>();
+
+        let mut solver = Solver::new(&mut self.db);
+        solver.insert(constraints);
+        solver.check_super_instances(instances);
+        solver.check_overlapping_instances(traits);
+        solver.finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    /// A small deterministic random number generator for property tests.
+    pub(crate) struct Rng(u64);
+
+    impl Rng {
+        pub fn new(seed: u64) -> Self {
+            Rng(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1)
+        }
+
+        pub fn below(&mut self, n: usize) -> usize {
+            // xorshift64
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        pub fn chance(&mut self, percent: usize) -> bool {
+            self.below(100) < percent
+        }
+
+        pub fn shuffle<T>(&mut self, items: &mut [T]) {
+            for i in (1..items.len()).rev() {
+                items.swap(i, self.below(i + 1));
+            }
+        }
+    }
+}
@@ -0,0 +1,476 @@
+use crate::{Constraint, Instantiation, Substitutions, Ty};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MemoryNode(u32);
+
+/// A result reported by the solver, other than a node's type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Flag {
+    Resolved {
+        node: MemoryNode,
+        instance: MemoryNode,
+        ty: MemoryNode,
+    },
+    UsedDefault {
+        node: MemoryNode,
+        instance: MemoryNode,
+    },
+    CustomError {
+        node: MemoryNode,
+        instance: MemoryNode,
+        substitutions: Substitutions<MemoryDb>,
+    },
+    Unresolved {
+        node: MemoryNode,
+        ty: MemoryNode,
+    },
+    Ambiguous {
+        node: MemoryNode,
+        candidates: Vec<MemoryNode>,
+        ty: MemoryNode,
+    },
+    TraitOutput {
+        node: MemoryNode,
+        parameter: MemoryNode,
+        ty: Ty<MemoryDb>,
+    },
+    Overlapping {
+        instance: MemoryNode,
+        other: MemoryNode,
+    },
+    MissingSuperInstance {
+        instance: MemoryNode,
+        super_trait: MemoryNode,
+    },
+    Generalized {
+        node: MemoryNode,
+    },
+    RecursionLimit {
+        node: MemoryNode,
+        ty: MemoryNode,
+    },
+    StepLimit {
+        node: MemoryNode,
+    },
+    Unsolved {
+        node: MemoryNode,
+        constraint: Constraint<MemoryDb>,
+    },
+}
+
+/// The constraints copied whenever a node is instantiated.
+#[derive(Debug, Clone, Default)]
+pub struct Definition {
+    /// Also solved on their own, giving the definition its type.
+    pub constraints: Vec<Constraint<MemoryDb>>,
+
+    /// Only solved on copies, like the bounds in a `where` clause.
+    pub lazy: Vec<Constraint<MemoryDb>>,
+}
+
+/// An in-memory [`Db`](crate::Db) whose nodes are identified by name.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryDb {
+    names: Vec<String>,
+    nodes: HashMap<String, MemoryNode>,
+    copies: BTreeSet<MemoryNode>,
+    hidden: BTreeSet<MemoryNode>,
+    types: Vec<(MemoryNode, Vec<MemoryNode>)>,
+    definitions: BTreeMap<MemoryNode, Definition>,
+    instances: Vec<(MemoryNode, MemoryNode)>,
+    defaults: BTreeSet<MemoryNode>,
+    errors: BTreeSet<MemoryNode>,
+    outputs: Vec<(MemoryNode, MemoryNode)>,
+    tys: BTreeMap<MemoryNode, Vec<Ty<MemoryDb>>>,
+    incomplete: BTreeSet<MemoryNode>,
+    flags: Vec<Flag>,
+}
+
+impl MemoryDb {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The node with this name, creating it if it doesn't exist yet.
+    pub fn node(&mut self, name: &str) -> MemoryNode {
+        if let Some(&node) = self.nodes.get(name) {
+            return node;
+        }
+
+        let node = MemoryNode(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.nodes.insert(name.to_string(), node);
+        node
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<MemoryNode> {
+        self.nodes.get(name).copied()
+    }
+
+    pub fn name(&self, node: MemoryNode) -> &str {
+        &self.names[node.0 as usize]
+    }
+
+    /// The nodes created while parsing, in order.
+    pub fn named_nodes(&self) -> impl Iterator<Item = MemoryNode> {
+        (0..self.names.len() as u32)
+            .map(MemoryNode)
+            .filter(|node| !self.copies.contains(node))
+    }
+
+    /// The nodes created while solving, eg. when instantiating a definition.
+    pub fn copies(&self) -> impl Iterator<Item = MemoryNode> {
+        self.copies.iter().copied()
+    }
+
+    pub fn declare_type(&mut self, name: MemoryNode, parameters: Vec<MemoryNode>) {
+        self.types.push((name, parameters));
+    }
+
+    pub fn types(&self) -> impl Iterator<Item = (MemoryNode, &[MemoryNode])> {
+        self.types
+            .iter()
+            .map(|(name, parameters)| (*name, parameters.as_slice()))
+    }
+
+    /// The parameters of the type declared with this name.
+    pub fn type_parameters(&self, name: MemoryNode) -> Option<&[MemoryNode]> {
+        self.types
+            .iter()
+            .find(|(other, _)| *other == name)
+            .map(|(_, parameters)| parameters.as_slice())
+    }
+
+    pub fn define(&mut self, node: MemoryNode, definition: Definition) {
+        let existing = self.definitions.entry(node).or_default();
+        existing.constraints.extend(definition.constraints);
+        existing.lazy.extend(definition.lazy);
+    }
+
+    pub fn definitions(&self) -> impl Iterator<Item = (MemoryNode, &Definition)> {
+        self.definitions
+            .iter()
+            .map(|(&node, definition)| (node, definition))
+    }
+
+    pub fn add_instance(&mut self, trait_id: MemoryNode, instance: MemoryNode) {
+        self.instances.push((trait_id, instance));
+    }
+
+    pub fn instances(&self) -> impl Iterator<Item = (MemoryNode, MemoryNode)> {
+        self.instances.iter().copied()
+    }
+
+    /// Only use the instance when no other instance matches.
+    pub fn set_default(&mut self, instance: MemoryNode) {
+        self.defaults.insert(instance);
+    }
+
+    pub fn is_default(&self, instance: MemoryNode) -> bool {
+        self.defaults.contains(&instance)
+    }
+
+    /// Report an error when the instance is selected.
+    pub fn set_error(&mut self, instance: MemoryNode) {
+        self.errors.insert(instance);
+    }
+
+    pub fn is_error(&self, instance: MemoryNode) -> bool {
+        self.errors.contains(&instance)
+    }
+
+    /// Mark a parameter of the trait as determined by the selected instance.
+    pub fn add_output(&mut self, trait_id: MemoryNode, parameter: MemoryNode) {
+        self.outputs.push((trait_id, parameter));
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = (MemoryNode, MemoryNode)> {
+        self.outputs.iter().copied()
+    }
+
+    /// The types the solver reported for `node`.
+    pub fn tys(&self, node: MemoryNode) -> &[Ty<MemoryDb>] {
+        self.tys.get(&node).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn is_incomplete(&self, node: MemoryNode) -> bool {
+        self.incomplete.contains(&node)
+    }
+
+    pub fn flags(&self) -> &[Flag] {
+        &self.flags
+    }
+
+    fn copy_node(&mut self, node: MemoryNode, hide: bool) -> MemoryNode {
+        let name = self.name(node).to_string();
+        let copy = self.copy_named(&name);
+
+        if hide {
+            self.hidden.insert(copy);
+        }
+
+        copy
+    }
+
+    fn copy_named(&mut self, base: &str) -> MemoryNode {
+        let mut index = self.copies.len() + 1;
+        let name = loop {
+            let name = format!("{base}.{index}");
+            if !self.nodes.contains_key(&name) {
+                break name;
+            }
+
+            index += 1;
+        };
+
+        let copy = self.node(&name);
+        self.copies.insert(copy);
+        copy
+    }
+
+    fn is_untyped(&self, node: MemoryNode) -> bool {
+        self.types
+            .iter()
+            .any(|(name, parameters)| *name == node || parameters.contains(&node))
+    }
+}
+
+impl crate::Db for MemoryDb {
+    type Node = MemoryNode;
+    type Checkpoint = usize;
+
+    fn typed_nodes(&self) -> impl Iterator<Item = Self::Node> {
+        (0..self.names.len() as u32)
+            .map(MemoryNode)
+            .filter(|&node| !self.hidden.contains(&node) && !self.is_untyped(node))
+    }
+
+    fn clone_node_tree(
+        &mut self,
+        node: Self::Node,
+        substitutions: &mut Substitutions<Self>,
+        hide: bool,
+    ) -> (Self::Node, Vec<Constraint<Self>>) {
+        let copy = self.copy_node(node, hide);
+
+        let definition = self.definitions.get(&node).cloned().unwrap_or_default();
+
+        let mut copies = BTreeMap::from([(node, copy)]);
+        let mut constraints = Vec::new();
+        for mut constraint in definition.constraints.into_iter().chain(definition.lazy) {
+            // Copy nodes before substituting parameters, since substitutions
+            // refer to nodes outside the definition
+            constraint.traverse_nodes_mut(&mut |node| {
+                *node = *copies
+                    .entry(*node)
+                    .or_insert_with(|| self.copy_node(*node, hide));
+            });
+
+            constraint.traverse_tys_mut(&mut |ty| {
+                ty.traverse_mut(&mut |ty| {
+                    let Ty::Parameter(parameter) = *ty else {
+                        return;
+                    };
+
+                    if let Some(substitution) = substitutions.0.get(&parameter) {
+                        *ty = substitution.clone();
+                    } else {
+                        let copy = *copies
+                            .entry(parameter)
+                            .or_insert_with(|| self.copy_node(parameter, hide));
+
+                        substitutions.0.insert(parameter, Ty::Of(copy));
+                        *ty = Ty::Of(copy);
+                    }
+                });
+            });
+
+            constraints.push(constraint);
+        }
+
+        (copy, constraints)
+    }
+
+    fn fresh_node(&mut self) -> Self::Node {
+        let node = self.copy_named("fresh");
+        self.hidden.insert(node);
+        node
+    }
+
+    fn checkpoint(&mut self) -> Self::Checkpoint {
+        self.names.len()
+    }
+
+    fn remove_nodes_since(&mut self, checkpoint: Self::Checkpoint) {
+        for node in self.nodes_since(&checkpoint) {
+            self.remove_node(node);
+        }
+    }
+
+    fn nodes_since(&self, checkpoint: &Self::Checkpoint) -> Vec<Self::Node> {
+        (*checkpoint..self.names.len())
+            .map(|index| MemoryNode(index as u32))
+            .collect()
+    }
+
+    fn remove_node(&mut self, node: Self::Node) {
+        // Nodes keep their names so later copies are named the same way, but
+        // are hidden so they aren't typed
+        self.hidden.insert(node);
+        self.definitions.remove(&node);
+        self.tys.remove(&node);
+        self.incomplete.remove(&node);
+    }
+
+    fn remove_results(&mut self) {
+        self.tys.clear();
+        self.incomplete.clear();
+
+        // Overlapping and missing instances are checked separately
+        self.flags.retain(|flag| {
+            matches!(
+                flag,
+                Flag::Overlapping { .. } | Flag::MissingSuperInstance { .. }
+            )
+        });
+    }
+
+    fn get_trait_instances(
+        &mut self,
+        source: Self::Node,
+        node: Self::Node,
+        trait_id: Self::Node,
+    ) -> Vec<(Self::Node, Instantiation<Self>)> {
+        self.instances
+            .iter()
+            .filter(|&&(other, _)| other == trait_id)
+            .map(|&(_, instance)| {
+                let instantiation = Instantiation {
+                    source,
+                    node,
+                    definition: instance,
+                    substitutions: Substitutions::replace_all(),
+                };
+
+                (instance, instantiation)
+            })
+            .collect()
+    }
+
+    fn get_instance_trait(&mut self, instance: Self::Node) -> Option<Instantiation<Self>> {
+        let &(trait_id, _) = self
+            .instances
+            .iter()
+            .find(|&&(_, other)| other == instance)?;
+
+        // The instance's definition instantiates the trait for the instance
+        self.definitions
+            .get(&instance)?
+            .constraints
+            .iter()
+            .find_map(|constraint| match constraint {
+                Constraint::Instantiation(instantiation)
+                    if instantiation.definition == trait_id =>
+                {
+                    Some(instantiation.clone())
+                }
+                _ => None,
+            })
+    }
+
+    fn get_trait_outputs(&mut self, trait_id: Self::Node) -> Vec<Self::Node> {
+        self.outputs
+            .iter()
+            .filter(|&&(other, _)| other == trait_id)
+            .map(|&(_, parameter)| parameter)
+            .collect()
+    }
+
+    fn is_default_instance(&mut self, instance: Self::Node) -> bool {
+        self.is_default(instance)
+    }
+
+    fn is_error_instance(&mut self, instance: Self::Node) -> bool {
+        self.is_error(instance)
+    }
+
+    fn flag_resolved(&mut self, node: Self::Node, instance: Self::Node, ty: Self::Node) {
+        self.flags.push(Flag::Resolved { node, instance, ty });
+    }
+
+    fn flag_default_instance(&mut self, node: Self::Node, instance: Self::Node) {
+        self.flags.push(Flag::UsedDefault { node, instance });
+    }
+
+    fn flag_custom_error(
+        &mut self,
+        node: Self::Node,
+        instance: Self::Node,
+        substitutions: Substitutions<Self>,
+    ) {
+        self.flags.push(Flag::CustomError {
+            node,
+            instance,
+            substitutions,
+        });
+    }
+
+    fn flag_unresolved(&mut self, node: Self::Node, ty: Self::Node) {
+        self.flags.push(Flag::Unresolved { node, ty });
+    }
+
+    fn flag_ambiguous(&mut self, node: Self::Node, candidates: Vec<Self::Node>, ty: Self::Node) {
+        self.flags.push(Flag::Ambiguous {
+            node,
+            candidates,
+            ty,
+        });
+    }
+
+    fn flag_trait_output(&mut self, node: Self::Node, parameter: Self::Node, ty: Ty<Self>) {
+        self.flags.push(Flag::TraitOutput {
+            node,
+            parameter,
+            ty,
+        });
+    }
+
+    fn flag_overlapping_instance(&mut self, instance: Self::Node, other: Self::Node) {
+        self.flags.push(Flag::Overlapping { instance, other });
+    }
+
+    fn flag_missing_super_instance(&mut self, instance: Self::Node, super_trait: Self::Node) {
+        self.flags.push(Flag::MissingSuperInstance {
+            instance,
+            super_trait,
+        });
+    }
+
+    fn flag_generalized(&mut self, node: Self::Node) {
+        self.flags.push(Flag::Generalized { node });
+    }
+
+    fn flag_recursion_limit(&mut self, node: Self::Node, ty: Self::Node) {
+        self.flags.push(Flag::RecursionLimit { node, ty });
+    }
+
+    fn flag_step_limit(&mut self, node: Self::Node) {
+        self.flags.push(Flag::StepLimit { node });
+    }
+
+    fn flag_unsolved(&mut self, node: Self::Node, constraint: Constraint<Self>) {
+        self.flags.push(Flag::Unsolved { node, constraint });
+    }
+
+    fn flag_type(&mut self, node: Self::Node, ty: Ty<Self>) {
+        let tys = self.tys.entry(node).or_default();
+        if !tys.contains(&ty) {
+            tys.push(ty);
+        }
+    }
+
+    fn flag_incomplete_type(&mut self, node: Self::Node) {
+        self.incomplete.insert(node);
+    }
+}
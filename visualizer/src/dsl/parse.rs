@@ -0,0 +1,813 @@
+use super::{Definition, MemoryDb, MemoryNode, Program};
+use crate::{Bound, Constraint, Instantiation, Substitutions, Ty};
+use std::{collections::BTreeMap, fmt, iter::Peekable, str::CharIndices};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Program {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let tokens = tokenize(source)?;
+
+        let mut parser = Parser {
+            tokens: &tokens,
+            index: 0,
+            db: MemoryDb::new(),
+        };
+
+        // Types may be used before they're declared, so declare them first
+        parser.declare_types()?;
+
+        let constraints = parser.parse_program()?;
+
+        Ok(Program {
+            db: parser.db,
+            constraints,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Name(String),
+    Parameter(String),
+    Unknown(String),
+    Colon,
+    Comma,
+    Arrow,
+    OpenParen,
+    CloseParen,
+    OpenBracket,
+    CloseBracket,
+    OpenBrace,
+    CloseBrace,
+    Newline,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Name(name) => write!(f, "`{name}`"),
+            Token::Parameter(name) => write!(f, "`'{name}`"),
+            Token::Unknown(name) => write!(f, "`?{name}`"),
+            Token::Colon => write!(f, "`:`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::Arrow => write!(f, "`->`"),
+            Token::OpenParen => write!(f, "`(`"),
+            Token::CloseParen => write!(f, "`)`"),
+            Token::OpenBracket => write!(f, "`[`"),
+            Token::CloseBracket => write!(f, "`]`"),
+            Token::OpenBrace => write!(f, "`{{`"),
+            Token::CloseBrace => write!(f, "`}}`"),
+            Token::Newline => write!(f, "end of line"),
+        }
+    }
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_name_continue(c: char) -> bool {
+    // Copies of nodes are named `node.1`, `node.2`, etc.
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    fn name(
+        chars: &mut Peekable<CharIndices<'_>>,
+        source: &str,
+        line: usize,
+    ) -> Result<String, ParseError> {
+        let Some(&(start, c)) = chars.peek() else {
+            return Err(ParseError {
+                line,
+                message: String::from("expected name"),
+            });
+        };
+
+        if !is_name_start(c) {
+            return Err(ParseError {
+                line,
+                message: format!("expected name, found `{c}`"),
+            });
+        }
+
+        let mut end = start;
+        while let Some(&(index, c)) = chars.peek()
+            && is_name_continue(c)
+        {
+            end = index + c.len_utf8();
+            chars.next();
+        }
+
+        Ok(source[start..end].to_string())
+    }
+
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(_, c)) = chars.peek() {
+        let token = match c {
+            '\n' => {
+                chars.next();
+                tokens.push((line, Token::Newline));
+                line += 1;
+                continue;
+            }
+            '#' => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+                continue;
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '\'' => {
+                chars.next();
+                Token::Parameter(name(&mut chars, source, line)?)
+            }
+            '?' => {
+                chars.next();
+                Token::Unknown(name(&mut chars, source, line)?)
+            }
+            '-' => {
+                chars.next();
+                if chars.next_if(|&(_, c)| c == '>').is_none() {
+                    return Err(ParseError {
+                        line,
+                        message: String::from("expected `->`"),
+                    });
+                }
+
+                Token::Arrow
+            }
+            c if is_name_start(c) => Token::Name(name(&mut chars, source, line)?),
+            c => {
+                chars.next();
+
+                match c {
+                    ':' => Token::Colon,
+                    ',' => Token::Comma,
+                    '(' => Token::OpenParen,
+                    ')' => Token::CloseParen,
+                    '[' => Token::OpenBracket,
+                    ']' => Token::CloseBracket,
+                    '{' => Token::OpenBrace,
+                    '}' => Token::CloseBrace,
+                    c => {
+                        return Err(ParseError {
+                            line,
+                            message: format!("unexpected character `{c}`"),
+                        });
+                    }
+                }
+            }
+        };
+
+        tokens.push((line, token));
+    }
+
+    tokens.push((line, Token::Newline));
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [(usize, Token)],
+    index: usize,
+    db: MemoryDb,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, token)| token)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(self.index + n).map(|(_, token)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.index)
+            .or(self.tokens.last())
+            .map_or(1, |&(line, _)| line)
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError {
+            line: self.line(),
+            message: message.into(),
+        })
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, ParseError> {
+        match self.peek() {
+            Some(token) => self.error(format!("expected {expected}, found {token}")),
+            None => self.error(format!("expected {expected}, found end of input")),
+        }
+    }
+
+    fn next_if(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ParseError> {
+        if self.next_if(&token) {
+            Ok(())
+        } else {
+            self.unexpected(&token.to_string())
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        // A keyword followed by `:` is a node with the same name
+        if matches!(self.peek(), Some(Token::Name(name)) if name == keyword)
+            && self.peek_nth(1) != Some(&Token::Colon)
+        {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if self.keyword(keyword) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{keyword}`"))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Token::Name(name)) => {
+                let name = name.clone();
+                self.index += 1;
+                Ok(name)
+            }
+            _ => self.unexpected("name"),
+        }
+    }
+
+    fn node(&mut self) -> Result<MemoryNode, ParseError> {
+        let name = self.name()?;
+        Ok(self.db.node(&name))
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.next_if(&Token::Newline) {}
+    }
+
+    fn end_of_line(&mut self) -> Result<(), ParseError> {
+        // A definition's closing brace may follow its last constraint
+        if self.peek() == Some(&Token::CloseBrace) {
+            return Ok(());
+        }
+
+        self.expect(Token::Newline)
+    }
+
+    fn declare_types(&mut self) -> Result<(), ParseError> {
+        while self.index < self.tokens.len() {
+            let at_line_start = self.index == 0 || self.tokens[self.index - 1].1 == Token::Newline;
+
+            if at_line_start && self.keyword("type") {
+                let name = self.node()?;
+
+                if self.db.type_parameters(name).is_some() {
+                    return self
+                        .error(format!("type `{}` is already declared", self.db.name(name)));
+                }
+
+                let mut parameters = Vec::new();
+                while self.peek() != Some(&Token::Newline) {
+                    parameters.push(self.node()?);
+                }
+
+                self.db.declare_type(name, parameters);
+            } else {
+                self.index += 1;
+            }
+        }
+
+        self.index = 0;
+
+        Ok(())
+    }
+
+    fn instance(&mut self, default: bool, error: bool) -> Result<(), ParseError> {
+        let trait_id = self.node()?;
+        let instance = self.node()?;
+        self.expect(Token::Newline)?;
+
+        self.db.add_instance(trait_id, instance);
+
+        if default {
+            self.db.set_default(instance);
+        }
+
+        if error {
+            self.db.set_error(instance);
+        }
+
+        Ok(())
+    }
+
+    fn parse_program(&mut self) -> Result<Vec<Constraint<MemoryDb>>, ParseError> {
+        let mut constraints = Vec::new();
+
+        loop {
+            self.skip_newlines();
+
+            if self.peek().is_none() {
+                break;
+            }
+
+            if self.keyword("type") {
+                // Already declared
+                while !self.next_if(&Token::Newline) {
+                    self.index += 1;
+                }
+            } else if self.keyword("instance") {
+                self.instance(false, false)?;
+            } else if self.keyword("default") {
+                let error = self.keyword("error");
+                self.expect_keyword("instance")?;
+                self.instance(true, error)?;
+            } else if self.keyword("error") {
+                self.expect_keyword("instance")?;
+                self.instance(false, true)?;
+            } else if self.keyword("output") {
+                let trait_id = self.node()?;
+                let parameter = self.node()?;
+                self.expect(Token::Newline)?;
+
+                self.db.add_output(trait_id, parameter);
+            } else if self.keyword("def") {
+                let definition = self.node()?;
+                self.expect(Token::OpenBrace)?;
+
+                let mut body = Definition::default();
+                loop {
+                    self.skip_newlines();
+
+                    if self.next_if(&Token::CloseBrace) {
+                        break;
+                    }
+
+                    if self.peek().is_none() {
+                        return self.unexpected("`}`");
+                    }
+
+                    if self.keyword("where") {
+                        body.lazy.push(self.parse_constraint()?);
+                    } else {
+                        body.constraints.push(self.parse_constraint()?);
+                    }
+
+                    self.end_of_line()?;
+                }
+
+                self.expect(Token::Newline)?;
+
+                self.db.define(definition, body);
+            } else {
+                constraints.push(self.parse_constraint()?);
+                self.expect(Token::Newline)?;
+            }
+        }
+
+        Ok(constraints)
+    }
+
+    fn parse_constraint(&mut self) -> Result<Constraint<MemoryDb>, ParseError> {
+        if self.keyword("instantiate") {
+            Ok(Constraint::Instantiation(self.parse_instantiation()?))
+        } else if self.keyword("bound") {
+            Ok(Constraint::Bound(Bound(self.parse_instantiation()?)))
+        } else if self.keyword("generalize") {
+            let node = self.node()?;
+
+            let mut environment = Vec::new();
+            if self.keyword("in") {
+                environment.push(self.node()?);
+                while self.next_if(&Token::Comma) {
+                    environment.push(self.node()?);
+                }
+            }
+
+            Ok(Constraint::Generalize(node, environment))
+        } else {
+            let node = self.node()?;
+            self.expect(Token::Colon)?;
+            let ty = self.parse_ty()?;
+
+            Ok(Constraint::Ty(node, ty))
+        }
+    }
+
+    /// `<definition> for <node> [<parameter>: <ty>, ...] in <source>`, where
+    /// the substitutions and source are optional.
+    fn parse_instantiation(&mut self) -> Result<Instantiation<MemoryDb>, ParseError> {
+        let definition = self.node()?;
+        self.expect_keyword("for")?;
+        let node = self.node()?;
+
+        let mut substitutions = BTreeMap::new();
+        if self.next_if(&Token::OpenBracket) {
+            while !self.next_if(&Token::CloseBracket) {
+                let parameter = self.node()?;
+                self.expect(Token::Colon)?;
+                let ty = self.parse_ty()?;
+
+                if substitutions.insert(parameter, ty).is_some() {
+                    return self.error(format!(
+                        "`{}` is substituted more than once",
+                        self.db.name(parameter)
+                    ));
+                }
+
+                if !self.next_if(&Token::Comma) {
+                    self.expect(Token::CloseBracket)?;
+                    break;
+                }
+            }
+        }
+
+        let source = if self.keyword("in") {
+            self.node()?
+        } else {
+            node
+        };
+
+        Ok(Instantiation {
+            source,
+            node,
+            definition,
+            substitutions: Substitutions(substitutions),
+        })
+    }
+
+    fn parse_ty(&mut self) -> Result<Ty<MemoryDb>, ParseError> {
+        let (ty, list) = self.parse_application()?;
+
+        if !self.next_if(&Token::Arrow) {
+            return Ok(ty);
+        }
+
+        // `(a, b) -> c` takes two inputs, whereas `(a, b)` is a tuple
+        let inputs = match list {
+            Some(inputs) => inputs,
+            None => vec![ty],
+        };
+
+        let output = self.parse_ty()?;
+
+        Ok(Ty::Function {
+            inputs,
+            output: Box::new(output),
+        })
+    }
+
+    /// Also returns the elements if the type was written as a parenthesized
+    /// list, so they can be used as a function's inputs.
+    fn parse_application(
+        &mut self,
+    ) -> Result<(Ty<MemoryDb>, Option<Vec<Ty<MemoryDb>>>), ParseError> {
+        if let Some(Token::Name(name)) = self.peek()
+            && let Some(node) = self.db.lookup(name)
+            && let Some(parameters) = self.db.type_parameters(node)
+        {
+            let parameters = parameters.to_vec();
+            self.index += 1;
+
+            let mut arguments = Vec::new();
+            while self.at_atom() {
+                arguments.push(self.parse_atom()?.0);
+            }
+
+            if arguments.len() != parameters.len() {
+                return self.error(format!(
+                    "`{}` expects {} parameters, but {} were provided",
+                    self.db.name(node),
+                    parameters.len(),
+                    arguments.len(),
+                ));
+            }
+
+            let ty = Ty::Named {
+                name: node,
+                parameters: parameters.into_iter().zip(arguments).collect(),
+            };
+
+            return Ok((ty, None));
+        }
+
+        self.parse_atom()
+    }
+
+    fn at_atom(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Token::Name(_) | Token::Parameter(_) | Token::Unknown(_) | Token::OpenParen)
+        )
+    }
+
+    fn parse_atom(&mut self) -> Result<(Ty<MemoryDb>, Option<Vec<Ty<MemoryDb>>>), ParseError> {
+        let ty = match self.peek().cloned() {
+            Some(Token::Name(name)) => {
+                self.index += 1;
+
+                let node = self.db.node(&name);
+                match self.db.type_parameters(node) {
+                    Some([]) => Ty::Named {
+                        name: node,
+                        parameters: BTreeMap::new(),
+                    },
+                    Some(_) => {
+                        return self.error(format!(
+                            "`{name}` expects parameters, so it must be wrapped in parentheses"
+                        ));
+                    }
+                    None => Ty::Of(node),
+                }
+            }
+            Some(Token::Parameter(name)) => {
+                self.index += 1;
+                Ty::Parameter(self.db.node(&name))
+            }
+            Some(Token::Unknown(name)) => {
+                self.index += 1;
+                Ty::Unknown(self.db.node(&name))
+            }
+            Some(Token::OpenParen) => {
+                self.index += 1;
+
+                let mut elements = Vec::new();
+                let mut trailing_comma = false;
+                while !self.next_if(&Token::CloseParen) {
+                    elements.push(self.parse_ty()?);
+
+                    trailing_comma = self.next_if(&Token::Comma);
+                    if !trailing_comma {
+                        self.expect(Token::CloseParen)?;
+                        break;
+                    }
+                }
+
+                let ty = if elements.len() == 1 && !trailing_comma {
+                    elements[0].clone()
+                } else {
+                    Ty::Tuple {
+                        elements: elements.clone(),
+                    }
+                };
+
+                return Ok((ty, Some(elements)));
+            }
+            _ => return self.unexpected("type"),
+        };
+
+        Ok((ty, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::tests::Rng;
+
+    #[test]
+    fn test_parse_ty() {
+        let program = Program::parse("type Maybe value\na : (Maybe b, 'c) -> ?d\n").unwrap();
+
+        let node = |name| program.db.lookup(name).unwrap();
+
+        assert_eq!(
+            program.constraints,
+            vec![Constraint::Ty(
+                node("a"),
+                Ty::Function {
+                    inputs: vec![
+                        Ty::Named {
+                            name: node("Maybe"),
+                            parameters: BTreeMap::from([(node("value"), Ty::Of(node("b")))]),
+                        },
+                        Ty::Parameter(node("c")),
+                    ],
+                    output: Box::new(Ty::Unknown(node("d"))),
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_parentheses() {
+        let program = Program::parse(
+            "a : (b)\na : (b,)\na : ()\na : () -> b\na : b -> c -> d\na : ((b, c)) -> d\n",
+        )
+        .unwrap();
+
+        let printed = program
+            .constraints
+            .iter()
+            .map(|constraint| program.db.display_constraint(constraint))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            printed,
+            [
+                "a : b",
+                "a : (b,)",
+                "a : ()",
+                "a : () -> b",
+                "a : (b) -> (c) -> d",
+                "a : ((b, c)) -> d",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_instantiation() {
+        let source = "\
+type Number
+def id {
+    id : (x) -> x
+    x : 'x
+    where bound Show for s [x: Number] in id
+}
+instance Show id
+default instance Show s
+error instance Show e
+default error instance Show d
+output Show x
+instantiate id for a [x: Number]
+bound Show for b in c
+generalize d
+";
+
+        assert_eq!(Program::parse(source).unwrap().to_string(), source);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |source| Program::parse(source).unwrap_err().to_string();
+
+        assert_eq!(error("a : b\na : %"), "line 2: unexpected character `%`");
+        assert_eq!(
+            error("type Maybe value\n\na : Maybe"),
+            "line 3: `Maybe` expects 1 parameters, but 0 were provided"
+        );
+        assert_eq!(
+            error("type Maybe value\na : (Maybe Maybe)"),
+            "line 2: `Maybe` expects parameters, so it must be wrapped in parentheses"
+        );
+        assert_eq!(
+            error("instantiate a b"),
+            "line 1: expected `for`, found `b`"
+        );
+        assert_eq!(
+            error("def a {\n  a : b\n"),
+            "line 3: expected `}`, found end of input"
+        );
+        assert_eq!(
+            error("type A\ntype A"),
+            "line 2: type `A` is already declared"
+        );
+    }
+
+    fn random_ty(rng: &mut Rng, db: &mut MemoryDb, depth: usize) -> Ty<MemoryDb> {
+        const NAMES: &[&str] = &["a", "b", "c", "d", "e"];
+
+        let leaf = depth == 0 || rng.chance(40);
+        match rng.below(if leaf { 3 } else { 6 }) {
+            0 => Ty::Of(db.node(NAMES[rng.below(NAMES.len())])),
+            1 => Ty::Parameter(db.node(NAMES[rng.below(NAMES.len())])),
+            2 => Ty::Unknown(db.node(NAMES[rng.below(NAMES.len())])),
+            3 => {
+                let name = db.lookup("Pair").unwrap();
+                let parameters = db.type_parameters(name).unwrap().to_vec();
+
+                Ty::Named {
+                    name,
+                    parameters: parameters
+                        .into_iter()
+                        .map(|parameter| (parameter, random_ty(rng, db, depth - 1)))
+                        .collect(),
+                }
+            }
+            4 => Ty::Function {
+                inputs: (0..rng.below(3))
+                    .map(|_| random_ty(rng, db, depth - 1))
+                    .collect(),
+                output: Box::new(random_ty(rng, db, depth - 1)),
+            },
+            _ => Ty::Tuple {
+                elements: (0..rng.below(3))
+                    .map(|_| random_ty(rng, db, depth - 1))
+                    .collect(),
+            },
+        }
+    }
+
+    fn random_constraint(rng: &mut Rng, db: &mut MemoryDb) -> Constraint<MemoryDb> {
+        let node = db.node(["a", "b", "c", "d", "e"][rng.below(5)]);
+        let definition = db.node(["f", "g"][rng.below(2)]);
+
+        let mut instantiation = Instantiation {
+            source: node,
+            node,
+            definition,
+            substitutions: Substitutions::replace_all(),
+        };
+
+        for _ in 0..rng.below(3) {
+            let parameter = db.node(["x", "y"][rng.below(2)]);
+            let ty = random_ty(rng, db, 2);
+            instantiation.substitutions.0.insert(parameter, ty);
+        }
+
+        if rng.chance(30) {
+            instantiation.source = db.node("s");
+        }
+
+        match rng.below(4) {
+            0 => Constraint::Instantiation(instantiation),
+            1 => Constraint::Bound(Bound(instantiation)),
+            2 => Constraint::Generalize(
+                node,
+                (0..rng.below(3))
+                    .map(|_| db.node(["x", "y"][rng.below(2)]))
+                    .collect(),
+            ),
+            _ => Constraint::Ty(node, random_ty(rng, db, 3)),
+        }
+    }
+
+    #[test]
+    fn test_print_then_parse_round_trips() {
+        for seed in 0..200 {
+            let mut rng = Rng::new(seed);
+
+            let mut program = Program::default();
+            let pair = program.db.node("Pair");
+            let parameters = vec![program.db.node("left"), program.db.node("right")];
+            program.db.declare_type(pair, parameters);
+
+            for name in ["f", "g"] {
+                let mut definition = Definition::default();
+                for _ in 0..rng.below(3) {
+                    definition
+                        .constraints
+                        .push(random_constraint(&mut rng, &mut program.db));
+                }
+
+                for _ in 0..rng.below(2) {
+                    definition
+                        .lazy
+                        .push(random_constraint(&mut rng, &mut program.db));
+                }
+
+                let node = program.db.node(name);
+                program.db.define(node, definition);
+            }
+
+            let trait_id = program.db.node("g");
+            let instance = program.db.node("f");
+            program.db.add_instance(trait_id, instance);
+
+            let parameter = program.db.node("y");
+            program.db.add_output(trait_id, parameter);
+
+            for _ in 0..rng.below(6) {
+                let constraint = random_constraint(&mut rng, &mut program.db);
+                program.constraints.push(constraint);
+            }
+
+            let printed = program.to_string();
+            let reparsed = Program::parse(&printed)
+                .unwrap_or_else(|error| panic!("seed {seed}: {error}\n{printed}"));
+
+            assert_eq!(reparsed.to_string(), printed, "seed {seed}");
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use super::{Flag, MemoryDb, Program};
+use crate::{Bound, Constraint, Instantiation, Substitutions, Ty};
+use std::fmt::{self, Write};
+
+impl MemoryDb {
+    pub fn display_ty(&self, ty: &Ty<MemoryDb>) -> String {
+        let mut s = String::new();
+        self.write_ty(&mut s, ty, false).unwrap();
+        s
+    }
+
+    pub fn display_constraint(&self, constraint: &Constraint<MemoryDb>) -> String {
+        let mut s = String::new();
+        self.write_constraint(&mut s, constraint).unwrap();
+        s
+    }
+
+    pub fn display_flag(&self, flag: &Flag) -> String {
+        match flag {
+            Flag::Resolved { node, instance, ty } => format!(
+                "resolved {} with {} ({})",
+                self.name(*node),
+                self.name(*instance),
+                self.display_node_tys(*ty),
+            ),
+            Flag::UsedDefault { node, instance } => {
+                format!("default {} with {}", self.name(*node), self.name(*instance))
+            }
+            Flag::CustomError {
+                node,
+                instance,
+                substitutions,
+            } => {
+                let mut s = format!("error {} with {}", self.name(*node), self.name(*instance));
+                self.write_substitutions(&mut s, substitutions).unwrap();
+                s
+            }
+            Flag::Unresolved { node, ty } => format!(
+                "unresolved {} ({})",
+                self.name(*node),
+                self.display_node_tys(*ty),
+            ),
+            Flag::Ambiguous {
+                node,
+                candidates,
+                ty,
+            } => format!(
+                "ambiguous {} between {} ({})",
+                self.name(*node),
+                candidates
+                    .iter()
+                    .map(|&candidate| self.name(candidate))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                self.display_node_tys(*ty),
+            ),
+            Flag::TraitOutput {
+                node,
+                parameter,
+                ty,
+            } => format!(
+                "output {} {} = {}",
+                self.name(*node),
+                self.name(*parameter),
+                self.display_ty(ty),
+            ),
+            Flag::Overlapping { instance, other } => format!(
+                "overlapping {} with {}",
+                self.name(*instance),
+                self.name(*other),
+            ),
+            Flag::MissingSuperInstance {
+                instance,
+                super_trait,
+            } => format!(
+                "missing super instance {} of {}",
+                self.name(*instance),
+                self.name(*super_trait),
+            ),
+            Flag::Generalized { node } => format!("generalized {}", self.name(*node)),
+            Flag::RecursionLimit { node, ty } => format!(
+                "recursion limit {} ({})",
+                self.name(*node),
+                self.display_node_tys(*ty),
+            ),
+            Flag::StepLimit { node } => format!("step limit {}", self.name(*node)),
+            Flag::Unsolved { node, constraint } => format!(
+                "unsolved {} ({})",
+                self.name(*node),
+                self.display_constraint(constraint),
+            ),
+        }
+    }
+
+    /// The solved type of each node written in the program, followed by the
+    /// other results reported by the solver, one per line.
+    pub fn report(&self) -> String {
+        let mut s = String::new();
+
+        for node in self.named_nodes() {
+            for ty in self.tys(node) {
+                writeln!(s, "{} : {}", self.name(node), self.display_ty(ty)).unwrap();
+            }
+        }
+
+        for flag in self.flags() {
+            writeln!(s, "{}", self.display_flag(flag)).unwrap();
+        }
+
+        s
+    }
+
+    fn display_node_tys(&self, node: super::MemoryNode) -> String {
+        match self.tys(node) {
+            [] => self.name(node).to_string(),
+            tys => tys
+                .iter()
+                .map(|ty| self.display_ty(ty))
+                .collect::<Vec<_>>()
+                .join(" or "),
+        }
+    }
+
+    fn write_ty(&self, w: &mut impl Write, ty: &Ty<MemoryDb>, atom: bool) -> fmt::Result {
+        match ty {
+            Ty::Unknown(node) => write!(w, "?{}", self.name(*node)),
+            Ty::Of(node) => write!(w, "{}", self.name(*node)),
+            Ty::Parameter(node) => write!(w, "'{}", self.name(*node)),
+            Ty::Named { name, parameters } => {
+                let parenthesize = atom && !parameters.is_empty();
+                if parenthesize {
+                    write!(w, "(")?;
+                }
+
+                write!(w, "{}", self.name(*name))?;
+
+                // Write the parameters in the order they were declared
+                let order = self.type_parameters(*name).unwrap_or_default();
+                let mut parameters = parameters.iter().collect::<Vec<_>>();
+                parameters.sort_by_key(|(parameter, _)| {
+                    order.iter().position(|other| other == *parameter)
+                });
+
+                for (_, parameter) in parameters {
+                    write!(w, " ")?;
+                    self.write_ty(w, parameter, true)?;
+                }
+
+                if parenthesize {
+                    write!(w, ")")?;
+                }
+
+                Ok(())
+            }
+            Ty::Function { inputs, output } => {
+                if atom {
+                    write!(w, "(")?;
+                }
+
+                write!(w, "(")?;
+                for (index, input) in inputs.iter().enumerate() {
+                    if index > 0 {
+                        write!(w, ", ")?;
+                    }
+
+                    self.write_ty(w, input, false)?;
+                }
+
+                write!(w, ") -> ")?;
+                self.write_ty(w, output, false)?;
+
+                if atom {
+                    write!(w, ")")?;
+                }
+
+                Ok(())
+            }
+            Ty::Tuple { elements } => {
+                write!(w, "(")?;
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        write!(w, ", ")?;
+                    }
+
+                    self.write_ty(w, element, false)?;
+                }
+
+                if elements.len() == 1 {
+                    write!(w, ",")?;
+                }
+
+                write!(w, ")")
+            }
+        }
+    }
+
+    fn write_constraint(
+        &self,
+        w: &mut impl Write,
+        constraint: &Constraint<MemoryDb>,
+    ) -> fmt::Result {
+        match constraint {
+            Constraint::Ty(node, ty) => {
+                write!(w, "{} : ", self.name(*node))?;
+                self.write_ty(w, ty, false)
+            }
+            Constraint::Instantiation(instantiation) => {
+                write!(w, "instantiate ")?;
+                self.write_instantiation(w, instantiation)
+            }
+            Constraint::Bound(Bound(instantiation)) => {
+                write!(w, "bound ")?;
+                self.write_instantiation(w, instantiation)
+            }
+            Constraint::Generalize(node, environment) => {
+                write!(w, "generalize {}", self.name(*node))?;
+
+                for (index, node) in environment.iter().enumerate() {
+                    let separator = if index == 0 { " in" } else { "," };
+                    write!(w, "{separator} {}", self.name(*node))?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn write_instantiation(
+        &self,
+        w: &mut impl Write,
+        instantiation: &Instantiation<MemoryDb>,
+    ) -> fmt::Result {
+        write!(
+            w,
+            "{} for {}",
+            self.name(instantiation.definition),
+            self.name(instantiation.node),
+        )?;
+
+        self.write_substitutions(w, &instantiation.substitutions)?;
+
+        if instantiation.source != instantiation.node {
+            write!(w, " in {}", self.name(instantiation.source))?;
+        }
+
+        Ok(())
+    }
+
+    fn write_substitutions(
+        &self,
+        w: &mut impl Write,
+        substitutions: &Substitutions<MemoryDb>,
+    ) -> fmt::Result {
+        if substitutions.0.is_empty() {
+            return Ok(());
+        }
+
+        let mut substitutions = substitutions.0.iter().collect::<Vec<_>>();
+        substitutions.sort_by_key(|(parameter, _)| self.name(**parameter));
+
+        write!(w, " [")?;
+        for (index, (parameter, ty)) in substitutions.into_iter().enumerate() {
+            if index > 0 {
+                write!(w, ", ")?;
+            }
+
+            write!(w, "{}: ", self.name(*parameter))?;
+            self.write_ty(w, ty, false)?;
+        }
+
+        write!(w, "]")
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, parameters) in self.db.types() {
+            write!(f, "type {}", self.db.name(name))?;
+            for &parameter in parameters {
+                write!(f, " {}", self.db.name(parameter))?;
+            }
+
+            writeln!(f)?;
+        }
+
+        for (node, definition) in self.db.definitions() {
+            writeln!(f, "def {} {{", self.db.name(node))?;
+            for constraint in &definition.constraints {
+                write!(f, "    ")?;
+                self.db.write_constraint(f, constraint)?;
+                writeln!(f)?;
+            }
+
+            for constraint in &definition.lazy {
+                write!(f, "    where ")?;
+                self.db.write_constraint(f, constraint)?;
+                writeln!(f)?;
+            }
+
+            writeln!(f, "}}")?;
+        }
+
+        for (trait_id, instance) in self.db.instances() {
+            if self.db.is_default(instance) {
+                write!(f, "default ")?;
+            }
+
+            if self.db.is_error(instance) {
+                write!(f, "error ")?;
+            }
+
+            writeln!(
+                f,
+                "instance {} {}",
+                self.db.name(trait_id),
+                self.db.name(instance)
+            )?;
+        }
+
+        for (trait_id, parameter) in self.db.outputs() {
+            writeln!(
+                f,
+                "output {} {}",
+                self.db.name(trait_id),
+                self.db.name(parameter)
+            )?;
+        }
+
+        for constraint in &self.constraints {
+            self.db.write_constraint(f, constraint)?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
@@ -111,9 +111,38 @@ impl<Db: crate::Db> GroupKeys<Db> {
         self.keys.get(&node).copied()
     }
 
-    pub fn update_representative(&mut self, node: Db::Node, group: GroupKey<Db>) {
-        self.keys.insert(node, group);
-        self.nodes.insert(group, node);
+    /// Returns the previous key for `node` and the previous node for `group`,
+    /// which can be passed to [`GroupKeys::restore_representative`].
+    pub fn update_representative(
+        &mut self,
+        node: Db::Node,
+        group: GroupKey<Db>,
+    ) -> (Option<GroupKey<Db>>, Option<Db::Node>) {
+        let prev_group = self.keys.insert(node, group);
+        let prev_node = self.nodes.insert(group, node);
+        (prev_group, prev_node)
+    }
+
+    pub fn restore_representative(
+        &mut self,
+        node: Db::Node,
+        group: GroupKey<Db>,
+        (prev_group, prev_node): (Option<GroupKey<Db>>, Option<Db::Node>),
+    ) {
+        match prev_group {
+            Some(prev_group) => self.keys.insert(node, prev_group),
+            None => self.keys.remove(&node),
+        };
+
+        match prev_node {
+            Some(prev_node) => self.nodes.insert(group, prev_node),
+            None => self.nodes.remove(&group),
+        };
+    }
+
+    pub fn remove(&mut self, node: Db::Node, key: GroupKey<Db>) {
+        self.keys.remove(&node);
+        self.nodes.remove(&key);
     }
 
     pub fn node_for_key(&self, key: GroupKey<Db>) -> Db::Node {
@@ -1,4 +1,6 @@
 mod constraints;
+#[cfg(feature = "dsl")]
+pub mod dsl;
 mod graph;
 mod groups;
 mod solve;
@@ -13,6 +15,10 @@ use std::{fmt::Debug, hash::Hash};
 pub trait Db: Sized + 'static {
     type Node: Debug + Copy + Eq + Ord + Hash;
 
+    /// Marks which nodes existed at some point, so the nodes created after it
+    /// can be removed.
+    type Checkpoint;
+
     fn typed_nodes(&self) -> impl Iterator<Item = Self::Node>;
 
     fn clone_node_tree(
@@ -22,6 +28,25 @@ pub trait Db: Sized + 'static {
         hide: bool,
     ) -> (Self::Node, Vec<Constraint<Self>>);
 
+    fn fresh_node(&mut self) -> Self::Node;
+
+    fn checkpoint(&mut self) -> Self::Checkpoint;
+
+    /// Remove the nodes created since the checkpoint, along with their facts.
+    /// The solver calls this when rolling back the work that created them.
+    fn remove_nodes_since(&mut self, checkpoint: Self::Checkpoint);
+
+    /// The nodes created since the checkpoint.
+    fn nodes_since(&self, checkpoint: &Self::Checkpoint) -> Vec<Self::Node>;
+
+    /// Remove a node created while solving, along with its facts. The solver
+    /// calls this when the constraint that created it is solved again.
+    fn remove_node(&mut self, node: Self::Node);
+
+    /// Remove everything written by [`Solver::finish`], so solving again
+    /// after retracting constraints doesn't leave the old results behind.
+    fn remove_results(&mut self);
+
     fn get_trait_instances(
         &mut self,
         source: Self::Node,
@@ -29,8 +54,38 @@ pub trait Db: Sized + 'static {
         trait_id: Self::Node,
     ) -> Vec<(Self::Node, Instantiation<Self>)>;
 
+    /// The instance's instantiation of its trait, with the instance's
+    /// parameters.
+    fn get_instance_trait(&mut self, instance: Self::Node) -> Option<Instantiation<Self>>;
+
+    /// The parameters of the trait that are determined by the selected
+    /// instance, rather than used to select one.
+    fn get_trait_outputs(&mut self, trait_id: Self::Node) -> Vec<Self::Node>;
+
+    /// Whether the instance is only used when no other instance matches.
+    fn is_default_instance(&mut self, instance: Self::Node) -> bool;
+
+    /// Whether selecting the instance is an error that should be reported
+    /// instead of resolving the bound.
+    fn is_error_instance(&mut self, instance: Self::Node) -> bool;
+
     fn flag_resolved(&mut self, node: Self::Node, instance: Self::Node, ty: Self::Node);
+    fn flag_default_instance(&mut self, node: Self::Node, instance: Self::Node);
+    fn flag_custom_error(
+        &mut self,
+        node: Self::Node,
+        instance: Self::Node,
+        substitutions: Substitutions<Self>,
+    );
     fn flag_unresolved(&mut self, node: Self::Node, ty: Self::Node);
+    fn flag_ambiguous(&mut self, node: Self::Node, candidates: Vec<Self::Node>, ty: Self::Node);
+    fn flag_trait_output(&mut self, node: Self::Node, parameter: Self::Node, ty: Ty<Self>);
+    fn flag_overlapping_instance(&mut self, instance: Self::Node, other: Self::Node);
+    fn flag_missing_super_instance(&mut self, instance: Self::Node, super_trait: Self::Node);
+    fn flag_generalized(&mut self, node: Self::Node);
+    fn flag_recursion_limit(&mut self, node: Self::Node, ty: Self::Node);
+    fn flag_step_limit(&mut self, node: Self::Node);
+    fn flag_unsolved(&mut self, node: Self::Node, constraint: Constraint<Self>);
 
     fn flag_type(&mut self, node: Self::Node, ty: Ty<Self>);
     fn flag_incomplete_type(&mut self, node: Self::Node);
@@ -1,7 +1,17 @@
-use crate::{Bound, Constraint, Group, GroupKey, GroupKeys, Instantiation, Ty, TyGroups};
+mod retract;
+
+use crate::{
+    Bound, Constraint, Group, GroupKey, GroupKeys, Instantiation, Substitutions, Ty, TyGroups,
+};
 use derive_where::derive_where;
-use ena::unify::InPlaceUnificationTable;
-use std::{cell::RefCell, collections::BTreeMap, fmt::Debug, mem, rc::Rc};
+use ena::unify::{InPlace, InPlaceUnificationTable};
+use std::{
+    cell::{Ref, RefCell},
+    collections::{BTreeMap, BTreeSet, btree_map},
+    fmt::Debug,
+    mem,
+    rc::Rc,
+};
 
 #[derive_where(Debug, Clone)]
 pub struct Instance<Db: crate::Db> {
@@ -10,6 +20,27 @@ pub struct Instance<Db: crate::Db> {
     pub constraints: Vec<Constraint<Db>>,
 }
 
+/// Bounds on how much work the solver does before giving up, so programs with
+/// recursive instances or constraints that never settle still finish.
+#[derive(Debug, Clone, Copy)]
+pub struct SolverLimits {
+    /// How many instances may be nested while resolving a bound.
+    pub max_depth: u32,
+
+    /// How many times the solver may go through its constraints for each
+    /// constraint inserted, so larger programs get a larger budget.
+    pub max_steps: u32,
+}
+
+impl Default for SolverLimits {
+    fn default() -> Self {
+        SolverLimits {
+            max_depth: 32,
+            max_steps: 1_000,
+        }
+    }
+}
+
 #[derive_where(Clone)]
 pub struct Solver<'a, Db: crate::Db> {
     db: Rc<RefCell<&'a mut Db>>,
@@ -18,8 +49,109 @@ pub struct Solver<'a, Db: crate::Db> {
     groups: BTreeMap<GroupKey<Db>, Ty<Db>>,
     others: BTreeMap<Db::Node, Vec<Ty<Db>>>, // failed to unify
     queue: Vec<Constraint<Db>>,
+    consumed: bool, // whether the current step took constraints from the queue
     progress: Progress,
     source: Option<Db::Node>,
+    bound_nodes: Vec<Db::Node>,
+    bound_outputs: BTreeMap<Db::Node, Vec<(Db::Node, Db::Node, Ty<Db>)>>, // bound -> (parameter, output, use)
+    trait_outputs: Vec<(Db::Node, Db::Node, Db::Node)>, // (source, parameter, output)
+    custom_errors: Vec<(Db::Node, Db::Node, Substitutions<Db>)>, // (source, instance, substitutions)
+    reports: Vec<Report<Db>>,
+    deferred_bounds: Vec<DeferredBound<Db>>,
+    generalizations: BTreeMap<Db::Node, Generalization<Db>>,
+    deferred_instantiations: Vec<Instantiation<Db>>,
+    report_ambiguous: bool,
+    types_only: bool,
+    checking: Option<bool>, // whether the bound being checked failed
+    depth: u32,
+    limits: SolverLimits,
+    bound_depth: u32,
+    steps: u32,
+    step_budget: u32,
+    halted: bool,
+    step_limits: Vec<Db::Node>, // kept when rolling back, unlike `reports`
+    recursion_limited: bool,    // whether the candidate failed by recursing too deeply
+    error: bool,
+    undo_log: Vec<Undo<Db>>,
+    open_snapshots: usize,
+    owned: BTreeMap<Db::Node, Vec<Constraint<Db>>>,
+    instantiated_by: BTreeMap<Db::Node, BTreeSet<Db::Node>>, // definition -> sources
+    copies: Vec<(Db::Node, Db::Node)>,                       // (source, copy)
+}
+
+#[derive_where(Clone)]
+enum Generalization<Db: crate::Db> {
+    /// Waiting for the solver to reach a fixpoint. Contains the variables in
+    /// scope where the variable is assigned.
+    Pending(Vec<Db::Node>),
+
+    /// Each instantiation replaces the variables with new nodes.
+    Generalized(BTreeSet<Db::Node>, Ty<Db>),
+
+    /// The type couldn't be solved, so uses share the variable's type.
+    Monomorphic,
+}
+
+/// Reverts a change to the solver's groups, keys, failed types or
+/// generalizations when rolling back to a snapshot. `unify` has its own undo
+/// log.
+#[derive_where(Clone)]
+enum Undo<Db: crate::Db> {
+    NewKey(Db::Node, GroupKey<Db>),
+    Representative(
+        Db::Node,
+        GroupKey<Db>,
+        (Option<GroupKey<Db>>, Option<Db::Node>),
+    ),
+    Group(GroupKey<Db>, Option<Ty<Db>>),
+    Other(Db::Node),
+    Generalization(Db::Node, Option<Generalization<Db>>),
+}
+
+/// A bound that matched more than one instance, kept with the instances it
+/// matched so they're only instantiated once.
+#[derive_where(Clone)]
+struct DeferredBound<Db: crate::Db> {
+    bound: Instantiation<Db>,
+    candidates: Vec<Candidate<Db>>,
+}
+
+/// An instance of a bound's trait, instantiated for the bound.
+#[derive_where(Clone)]
+struct Candidate<Db: crate::Db> {
+    instance: Db::Node,
+    default: bool,
+    ty_constraints: Vec<Constraint<Db>>,
+    queued_constraints: Vec<Constraint<Db>>,
+}
+
+/// A result of solving that's written to the database in
+/// [`Solver::finish`], so results for retracted constraints can be dropped
+/// first.
+#[derive_where(Clone)]
+enum Report<Db: crate::Db> {
+    Resolved(Db::Node, Db::Node, Db::Node), // (source, instance, bound)
+    DefaultInstance(Db::Node, Db::Node),    // (source, instance)
+    Unresolved(Db::Node, Db::Node),         // (source, bound)
+    Ambiguous(Db::Node, Vec<Db::Node>, Db::Node), // (source, candidates, bound)
+    Generalized(Db::Node),
+    RecursionLimit(Db::Node, Db::Node), // (source, bound)
+    Unsolved(Constraint<Db>),
+}
+
+struct Snapshot<Db: crate::Db> {
+    unify: ena::unify::Snapshot<InPlace<GroupKey<Db>>>,
+    undo_log_len: usize,
+    queue: Vec<Constraint<Db>>,
+    deferred_bounds: Vec<DeferredBound<Db>>,
+    deferred_instantiations: Vec<Instantiation<Db>>,
+    bound_nodes_len: usize,
+    trait_outputs_len: usize,
+    custom_errors_len: usize,
+    reports_len: usize,
+    copies_len: usize,
+    checkpoint: Db::Checkpoint,
+    progress: Progress,
     error: bool,
 }
 
@@ -32,25 +164,244 @@ impl<'a, Db: crate::Db> Solver<'a, Db> {
             groups: Default::default(),
             others: Default::default(),
             queue: Default::default(),
+            consumed: false,
             progress: Default::default(),
             source: None,
+            bound_nodes: Default::default(),
+            bound_outputs: Default::default(),
+            trait_outputs: Default::default(),
+            custom_errors: Default::default(),
+            reports: Default::default(),
+            deferred_bounds: Default::default(),
+            generalizations: Default::default(),
+            deferred_instantiations: Default::default(),
+            report_ambiguous: false,
+            types_only: false,
+            checking: None,
+            depth: 0,
+            limits: Default::default(),
+            bound_depth: 0,
+            steps: 0,
+            step_budget: 0,
+            halted: false,
+            step_limits: Default::default(),
+            recursion_limited: false,
             error: false,
+            undo_log: Default::default(),
+            open_snapshots: 0,
+            owned: Default::default(),
+            instantiated_by: Default::default(),
+            copies: Default::default(),
         }
     }
 
+    pub fn with_limits(mut self, limits: SolverLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     pub fn insert(&mut self, constraints: impl IntoIterator<Item = Constraint<Db>>) {
         self.queue.extend(constraints);
         self.run();
     }
 
+    /// Like [`Solver::insert`], but remembers which node each constraint
+    /// belongs to so the constraints can be removed with [`Solver::retract`].
+    /// Any previously inserted constraints touching the same groups are solved
+    /// again alongside the new ones.
+    pub fn insert_owned(
+        &mut self,
+        constraints: impl IntoIterator<Item = (Db::Node, Vec<Constraint<Db>>)>,
+    ) {
+        let mut changed = Vec::new();
+        for (owner, constraints) in constraints {
+            changed.extend(constraints.iter().cloned());
+            self.owned.entry(owner).or_default().extend(constraints);
+        }
+
+        self.resolve_affected(&changed);
+    }
+
+    /// Report each pair of instances of the same trait whose types unify, since
+    /// a bound on that type would match both. Only the instances' types are
+    /// compared, not their bounds. Default instances are expected to overlap
+    /// with the other instances, so only overlaps between two default
+    /// instances are reported. Each pair is copied and tried in a snapshot, so
+    /// apart from the reports, the solver's types and the database are
+    /// unchanged afterward.
+    pub fn check_overlapping_instances(&mut self, traits: impl IntoIterator<Item = Db::Node>) {
+        for trait_id in traits {
+            if self.halted {
+                return;
+            }
+
+            // Only the instances are needed here, so the node they're
+            // instantiated on is removed again
+            let snapshot = self.snapshot();
+            let instances = self
+                .trait_instances(trait_id)
+                .into_iter()
+                .map(|(instance, _)| {
+                    let default = self.db.borrow_mut().is_default_instance(instance);
+                    (instance, default)
+                })
+                .collect::<Vec<_>>();
+            self.rollback_to(snapshot);
+
+            for (index, &(instance, default)) in instances.iter().enumerate() {
+                for &(other, other_default) in &instances[index + 1..] {
+                    if default != other_default {
+                        continue;
+                    }
+
+                    if self.instances_overlap(trait_id, instance, other) {
+                        let mut db = self.db.borrow_mut();
+                        db.flag_overlapping_instance(instance, other);
+                        db.flag_overlapping_instance(other, instance);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The instances of `trait_id`, instantiated on a new node.
+    fn trait_instances(&mut self, trait_id: Db::Node) -> Vec<(Db::Node, Instantiation<Db>)> {
+        let node = self.db.borrow_mut().fresh_node();
+
+        self.db
+            .borrow_mut()
+            .get_trait_instances(node, node, trait_id)
+    }
+
+    /// Whether the types of two instances of `trait_id` unify. The instances
+    /// are copied in a snapshot, so the copies are removed afterward.
+    fn instances_overlap(
+        &mut self,
+        trait_id: Db::Node,
+        instance: Db::Node,
+        other: Db::Node,
+    ) -> bool {
+        let snapshot = self.snapshot();
+
+        // Instantiate both instances on the same node, so trying them together
+        // unifies their types
+        let mut ty_constraints = Vec::new();
+        for (candidate, instantiation) in self.trait_instances(trait_id) {
+            if candidate == instance || candidate == other {
+                self.instantiate(instantiation, &mut ty_constraints, &mut Vec::new());
+            }
+        }
+
+        let overlap = self.try_constraints(ty_constraints);
+
+        self.rollback_to(snapshot);
+
+        overlap
+    }
+
+    /// Check that each instance satisfies the bounds on its trait with the
+    /// instance's parameters, reporting each bound that has no matching
+    /// instance, is ambiguous or exceeds the recursion limit. Each bound is
+    /// tried in a snapshot, so the solver's types are unchanged afterward.
+    pub fn check_super_instances(&mut self, instances: impl IntoIterator<Item = Db::Node>) {
+        for instance in instances {
+            if self.halted {
+                return;
+            }
+
+            let Some(instantiation) = self.db.borrow_mut().get_instance_trait(instance) else {
+                continue;
+            };
+
+            let mut ty_constraints = Vec::new();
+            let mut queued_constraints = Vec::new();
+            self.instantiate(instantiation, &mut ty_constraints, &mut queued_constraints);
+
+            let (bounds, other_constraints) = queued_constraints
+                .into_iter()
+                .partition::<Vec<_>, _>(|constraint| matches!(constraint, Constraint::Bound(_)));
+
+            for bound in bounds {
+                let Constraint::Bound(Bound(Instantiation {
+                    definition: super_trait,
+                    ..
+                })) = bound
+                else {
+                    unreachable!();
+                };
+
+                let constraints = ty_constraints
+                    .iter()
+                    .chain(&other_constraints)
+                    .cloned()
+                    .chain([bound]);
+
+                // Like `try_constraints`, but the bound is resolved right away
+                // and its failures are recorded instead of reported
+                let snapshot = self.snapshot();
+                let depth = mem::replace(&mut self.depth, 1);
+                let steps = mem::take(&mut self.steps);
+                let report_ambiguous = mem::replace(&mut self.report_ambiguous, true);
+                let checking = self.checking.replace(false);
+
+                self.insert(constraints);
+                let failed = self.checking == Some(true);
+
+                self.depth = depth;
+                self.steps = steps;
+                self.report_ambiguous = report_ambiguous;
+                self.checking = checking;
+                self.rollback_to(snapshot);
+
+                if failed {
+                    self.db
+                        .borrow_mut()
+                        .flag_missing_super_instance(instance, super_trait);
+                }
+            }
+        }
+    }
+
+    /// Write the solution to the database, replacing the results of any
+    /// earlier call.
     pub fn finish(&self) -> TyGroups<Db> {
         let mut ty_groups = TyGroups::default();
 
         let mut unify = self.unify.clone();
 
+        // The variables in generalized types become type parameters
+        let generalized = self
+            .generalizations
+            .values()
+            .filter_map(|generalization| match generalization {
+                Generalization::Generalized(variables, _) => Some(variables),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|&node| Some(unify.find(self.try_key_for_node(node)?)))
+            .collect::<BTreeSet<_>>();
+
+        for &key in &generalized {
+            let index = ty_groups.insert_group(Ty::Parameter(self.node_for_key(key)));
+
+            let nodes = unify.probe_value(key).0;
+            for node in nodes {
+                ty_groups.assign_node_to_index(node, index);
+            }
+        }
+
         for (representative_key, mut ty) in self.groups.clone().into_iter() {
             self.try_apply_ty(&mut ty, &mut unify);
 
+            ty.traverse_mut(&mut |ty| {
+                if let Ty::Of(node) = *ty
+                    && let Some(key) = self.try_key_for_node(node)
+                    && generalized.contains(&unify.find(key))
+                {
+                    *ty = Ty::Parameter(self.node_for_key(unify.find(key)));
+                }
+            });
+
             let index = ty_groups.insert_group(ty);
 
             let nodes = unify.probe_value(representative_key).0;
@@ -75,6 +426,30 @@ impl<'a, Db: crate::Db> Solver<'a, Db> {
 
         let mut db = self.db.borrow_mut();
 
+        db.remove_results();
+
+        for report in &self.reports {
+            match report.clone() {
+                Report::Resolved(source, instance, bound) => {
+                    db.flag_resolved(source, instance, bound)
+                }
+                Report::DefaultInstance(source, instance) => {
+                    db.flag_default_instance(source, instance)
+                }
+                Report::Unresolved(source, bound) => db.flag_unresolved(source, bound),
+                Report::Ambiguous(source, candidates, bound) => {
+                    db.flag_ambiguous(source, candidates, bound)
+                }
+                Report::Generalized(node) => db.flag_generalized(node),
+                Report::RecursionLimit(source, bound) => db.flag_recursion_limit(source, bound),
+                Report::Unsolved(constraint) => db.flag_unsolved(constraint.owner(), constraint),
+            }
+        }
+
+        for &node in &self.step_limits {
+            db.flag_step_limit(node);
+        }
+
         let typed_nodes = db.typed_nodes().collect::<Vec<_>>();
 
         for node in typed_nodes {
@@ -100,30 +475,174 @@ impl<'a, Db: crate::Db> Solver<'a, Db> {
             }
         }
 
+        for &(source, parameter, output) in &self.trait_outputs {
+            let Some(index) = ty_groups.index_of(output) else {
+                continue;
+            };
+
+            for ty in ty_groups.tys_at(index) {
+                db.flag_trait_output(source, parameter, ty.clone());
+            }
+        }
+
+        for (source, instance, substitutions) in &self.custom_errors {
+            let mut substitutions = substitutions.clone();
+            for ty in substitutions.0.values_mut() {
+                ty.traverse_mut(&mut |ty| {
+                    if let Ty::Of(node) = *ty
+                        && let Some(index) = ty_groups.index_of(node)
+                        && let Some(group_ty) = ty_groups.tys_at(index).first()
+                    {
+                        *ty = group_ty.clone();
+                    }
+                });
+            }
+
+            db.flag_custom_error(*source, *instance, substitutions);
+        }
+
+        // Bounds are resolved on temporary nodes, which are typed too so
+        // diagnostics can refer to the bound's type
+        for &node in &self.bound_nodes {
+            let Some(index) = ty_groups.index_of(node) else {
+                continue;
+            };
+
+            for ty in ty_groups.tys_at(index) {
+                db.flag_type(node, ty.clone());
+            }
+        }
+
         ty_groups
     }
+
+    /// The database, eg. to read the results of [`Solver::finish`] before
+    /// retracting more constraints.
+    pub fn db(&self) -> Ref<'_, Db> {
+        Ref::map(self.db.borrow(), |db| &**db)
+    }
 }
 
 impl<Db: crate::Db> Solver<'_, Db> {
     fn run(&mut self) {
+        // Each call to `insert` gets its own budget, which grows with the
+        // number of constraints inserted
+        if self.depth == 0 {
+            self.steps = 0;
+            self.step_budget = (self.limits.max_steps)
+                .saturating_mul(u32::try_from(self.queue.len()).unwrap_or(u32::MAX).max(1));
+            self.halted = false;
+        }
+
+        self.depth += 1;
+
+        // Nested runs happen in the middle of the enclosing run's steps
+        let consumed = mem::take(&mut self.consumed);
+
         loop {
+            // Whatever is left in the queue is reported as unsolved
+            if self.halted {
+                break;
+            }
+
+            self.steps += 1;
+            if self.steps > self.step_budget {
+                self.halt();
+                continue;
+            }
+
+            let queue_len = self.queue.len();
+            self.consumed = false;
+
             let progress = self
                 .run_instantiations()
                 .or_else(|| self.run_tys())
-                .or_else(|| self.run_bounds()); // TODO: run_defaults(), etc.
+                .or_else(|| {
+                    if self.types_only {
+                        Progress::NoProgress
+                    } else {
+                        self.run_bounds()
+                    }
+                }); // TODO: run_defaults(), etc.
 
+            // Stop once there's nothing left to do, or none of the above
+            // touched the remaining constraints
             if let Progress::NoProgress = progress
-                && self.queue.is_empty()
+                && (self.queue.is_empty() || (!self.consumed && self.queue.len() == queue_len))
             {
+                // Generalize variables and report ambiguous bounds once the
+                // outermost run reaches a fixpoint; nested runs may be missing
+                // constraints that the enclosing run has yet to queue
+                if self.depth == 1 {
+                    if let Progress::Progressed = self.generalize() {
+                        continue;
+                    }
+
+                    if !self.report_ambiguous && !self.deferred_bounds.is_empty() {
+                        self.report_ambiguous = true;
+                        continue;
+                    }
+                }
+
                 break;
             }
         }
 
-        assert!(
-            self.queue.is_empty(),
-            "constraints remaining in the queue: {:#?}",
-            self.queue
-        );
+        // Nested runs (eg. while trying candidates) happen during reporting,
+        // and leave remaining constraints to the outermost run
+        if self.depth == 1 {
+            self.report_ambiguous = false;
+
+            // Bounds are only left deferred if the solver stopped early
+            let deferred_bounds = mem::take(&mut self.deferred_bounds)
+                .into_iter()
+                .map(|deferred| Constraint::Bound(Bound(deferred.bound)));
+
+            for constraint in mem::take(&mut self.queue)
+                .into_iter()
+                .chain(deferred_bounds)
+            {
+                self.reports.push(Report::Unsolved(constraint));
+            }
+        }
+
+        self.consumed = consumed;
+        self.depth -= 1;
+    }
+
+    /// Solve `constraints` in a snapshot and roll back, returning whether their
+    /// types unified. The constraints are solved like a nested run without
+    /// resolving bounds, so nothing is reported to the database.
+    fn try_constraints(&mut self, constraints: impl IntoIterator<Item = Constraint<Db>>) -> bool {
+        let snapshot = self.snapshot();
+        let depth = mem::replace(&mut self.depth, 1);
+        let steps = mem::take(&mut self.steps);
+        let types_only = mem::replace(&mut self.types_only, true);
+
+        self.insert(constraints);
+        let error = self.error;
+
+        self.depth = depth;
+        self.steps = steps;
+        self.types_only = types_only;
+        self.rollback_to(snapshot);
+
+        !error
+    }
+
+    /// Stop solving. The remaining constraints are kept, so they can be
+    /// reported as unsolved once the outermost run returns. This may happen
+    /// while trying a candidate, so the step limit isn't rolled back.
+    fn halt(&mut self) {
+        self.halted = true;
+
+        let node = self
+            .source
+            .or_else(|| self.queue.first().map(Constraint::owner));
+
+        if let Some(node) = node {
+            self.step_limits.push(node);
+        }
     }
 
     fn run_tys(&mut self) -> Progress {
@@ -139,6 +658,8 @@ impl<Db: crate::Db> Solver<'_, Db> {
             })
             .collect();
 
+        self.consumed |= !tys.is_empty();
+
         // Form better groups by first processing constraints that reference
         // other nodes directly, followed by other incomplete types
         tys.sort_by_key(|(_, ty)| match ty {
@@ -163,14 +684,43 @@ impl<Db: crate::Db> Solver<'_, Db> {
                     instantiations.push(instantiation);
                     None
                 }
+                Constraint::Generalize(node, environment) => {
+                    self.consumed = true;
+
+                    if !self.generalizations.contains_key(&node) {
+                        self.set_generalization(node, Generalization::Pending(environment));
+                    }
+
+                    None
+                }
                 _ => Some(constraint),
             })
             .collect();
 
+        self.consumed |= !instantiations.is_empty();
+
         let mut ty_constraints = Vec::new();
         let mut queued_constraints = Vec::new();
         for instantiation in instantiations {
-            self.instantiate(instantiation, &mut ty_constraints, &mut queued_constraints);
+            match self.generalizations.get(&instantiation.definition).cloned() {
+                Some(Generalization::Pending(_)) => {
+                    // Wait until the variable's type is known
+                    self.deferred_instantiations.push(instantiation);
+                }
+                Some(Generalization::Generalized(variables, ty)) => {
+                    let ty = self.instantiate_generalized(instantiation.source, &variables, ty);
+                    ty_constraints.push(Constraint::Ty(instantiation.node, ty));
+                }
+                Some(Generalization::Monomorphic) => {
+                    ty_constraints.push(Constraint::Ty(
+                        instantiation.node,
+                        Ty::Of(instantiation.definition),
+                    ));
+                }
+                None => {
+                    self.instantiate(instantiation, &mut ty_constraints, &mut queued_constraints)
+                }
+            }
         }
 
         if !ty_constraints.is_empty() {
@@ -182,6 +732,174 @@ impl<Db: crate::Db> Solver<'_, Db> {
         self.progress.take()
     }
 
+    /// Generalize the variables whose types are solved, and queue their
+    /// deferred instantiations. Variables that are still unsolved once no more
+    /// variables can be generalized are used monomorphically.
+    fn generalize(&mut self) -> Progress {
+        let pending = self
+            .generalizations
+            .iter()
+            .filter_map(|(&node, generalization)| match generalization {
+                Generalization::Pending(environment) => Some((node, environment.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if pending.is_empty() {
+            return Progress::NoProgress;
+        }
+
+        // Types still waiting on a bound can't be generalized without losing
+        // the bound, including bounds that haven't been tried yet
+        let mut bound_nodes = self
+            .deferred_bounds
+            .iter()
+            .map(|deferred| deferred.bound.node)
+            .collect::<Vec<_>>();
+
+        for constraint in &self.queue {
+            if let Constraint::Bound(Bound(bound)) = constraint {
+                bound_nodes.push(bound.node);
+
+                for ty in bound.substitutions.0.values() {
+                    ty.traverse(&mut |ty| {
+                        if let Ty::Of(node) = *ty {
+                            bound_nodes.push(node);
+                        }
+                    });
+                }
+            }
+        }
+
+        let bound_variables = self.free_variables(bound_nodes);
+
+        // The types of variables that aren't generalized are shared with every
+        // use, so the parts they refer to stay shared too
+        let monomorphic = self
+            .generalizations
+            .iter()
+            .filter_map(|(&node, generalization)| match generalization {
+                Generalization::Pending(_) | Generalization::Monomorphic => Some(node),
+                Generalization::Generalized(..) => None,
+            })
+            .collect::<Vec<_>>();
+
+        let monomorphic_variables = monomorphic
+            .into_iter()
+            .map(|node| (node, self.free_variables([node])))
+            .collect::<BTreeMap<_, _>>();
+
+        // How many of those types each variable appears in, so finding the
+        // variables shared with the other types doesn't go through every type
+        let mut uses = BTreeMap::<_, usize>::new();
+        for &variable in monomorphic_variables.values().flatten() {
+            *uses.entry(variable).or_default() += 1;
+        }
+
+        let mut generalized = false;
+        for (node, environment) in &pending {
+            let mut ty = Ty::Of(*node);
+            self.apply_ty(&mut ty);
+
+            if matches!(ty, Ty::Of(_) | Ty::Unknown(_)) || self.others.contains_key(node) {
+                continue;
+            }
+
+            let own = &monomorphic_variables[node];
+            let environment =
+                self.free_variables(environment.iter().copied().filter(|other| other != node));
+
+            let is_shared = |variable: &Db::Node| {
+                environment.contains(variable)
+                    || uses.get(variable).copied().unwrap_or_default()
+                        > usize::from(own.contains(variable))
+            };
+
+            let mut variables = BTreeSet::new();
+            let mut solved = true;
+            ty.traverse(&mut |ty| match *ty {
+                Ty::Of(node) => {
+                    solved &= !bound_variables.contains(&node);
+
+                    if !is_shared(&node) {
+                        variables.insert(node);
+                    }
+                }
+                Ty::Unknown(_) => solved = false,
+                _ => {}
+            });
+
+            if !solved {
+                continue;
+            }
+
+            if !variables.is_empty() {
+                self.reports.push(Report::Generalized(*node));
+            }
+
+            self.set_generalization(*node, Generalization::Generalized(variables, ty));
+
+            generalized = true;
+        }
+
+        if !generalized {
+            for (node, _) in pending {
+                self.set_generalization(node, Generalization::Monomorphic);
+            }
+        }
+
+        let (ready, deferred) = mem::take(&mut self.deferred_instantiations)
+            .into_iter()
+            .partition::<Vec<_>, _>(|instantiation| {
+                !matches!(
+                    self.generalizations.get(&instantiation.definition),
+                    Some(Generalization::Pending(_))
+                )
+            });
+
+        self.deferred_instantiations = deferred;
+        self.queue
+            .extend(ready.into_iter().map(Constraint::Instantiation));
+
+        Progress::Progressed
+    }
+
+    /// The nodes standing for unknown parts of the nodes' types.
+    fn free_variables(&mut self, nodes: impl IntoIterator<Item = Db::Node>) -> BTreeSet<Db::Node> {
+        let mut variables = BTreeSet::new();
+        for node in nodes {
+            let mut ty = Ty::Of(node);
+            self.apply_ty(&mut ty);
+            ty.traverse(&mut |ty| {
+                if let Ty::Of(node) = *ty {
+                    variables.insert(node);
+                }
+            });
+        }
+
+        variables
+    }
+
+    fn instantiate_generalized(
+        &mut self,
+        source: Db::Node,
+        variables: &BTreeSet<Db::Node>,
+        mut ty: Ty<Db>,
+    ) -> Ty<Db> {
+        let mut nodes = BTreeMap::new();
+        ty.traverse_mut(&mut |ty| {
+            if let Ty::Of(node) = ty
+                && variables.contains(node)
+            {
+                *node = *nodes
+                    .entry(*node)
+                    .or_insert_with(|| self.fresh_node(source));
+            }
+        });
+
+        ty
+    }
+
     fn run_bounds(&mut self) -> Progress {
         let mut bounds = Vec::new();
         self.queue = mem::take(&mut self.queue)
@@ -195,23 +913,53 @@ impl<Db: crate::Db> Solver<'_, Db> {
             })
             .collect();
 
+        self.consumed |= !bounds.is_empty();
+
+        // Bounds that were ambiguous last time are retried after the new
+        // bounds, which may determine their types
+        let deferred_bounds = mem::take(&mut self.deferred_bounds);
+
         for Bound(mut bound) in bounds {
+            if self.halted {
+                self.queue.push(Constraint::Bound(Bound(bound)));
+                continue;
+            }
+
             // Use a temporary node for the bound while resolving, unless the
             // bound comes directly from a trait expression.
             let temp_node = if bound.source == bound.node {
                 bound.source
             } else {
-                let (node, constraints) = self.db.borrow_mut().clone_node_tree(
-                    bound.node,
-                    &mut bound.substitutions,
-                    false,
-                );
+                let (node, constraints) =
+                    self.clone_node_tree(bound.source, bound.node, &mut bound.substitutions, false);
 
                 self.queue.extend(constraints);
 
                 node
             };
 
+            bound.node = temp_node;
+            self.bound_nodes.push(temp_node);
+
+            // Output parameters are left out when selecting an instance, and
+            // unified with the types at the use site once one is selected
+            let outputs = self.db.borrow_mut().get_trait_outputs(bound.definition);
+            for parameter in outputs {
+                let Some(ty) = bound.substitutions.0.get_mut(&parameter) else {
+                    continue;
+                };
+
+                let output = self.fresh_node(bound.source);
+                self.bound_nodes.push(output);
+
+                let ty = mem::replace(ty, Ty::Of(output));
+
+                self.bound_outputs
+                    .entry(temp_node)
+                    .or_default()
+                    .push((parameter, output, ty));
+            }
+
             let prev_source = if let Some(source) = self.source {
                 bound.source = source;
                 Some(source)
@@ -222,76 +970,363 @@ impl<Db: crate::Db> Solver<'_, Db> {
             // Instantiate the bound with the trait's type.
             self.insert([Constraint::Instantiation(bound.clone())]);
 
-            let instances =
-                self.db
-                    .borrow_mut()
-                    .get_trait_instances(bound.source, temp_node, bound.definition);
-
-            let mut candidates = Vec::new();
-            for (instance, instantiation) in instances {
-                // Apply the instance's constraints to a copy of the
-                // typechecker, so if the instance fails to match, we can reset.
-                let mut copy = self.clone();
-                copy.error = false;
-                copy.queue.clear();
-
-                let mut ty_constraints = Vec::new();
-                let mut queued_constraints = Vec::new();
-                copy.instantiate(instantiation, &mut ty_constraints, &mut queued_constraints);
-
-                copy.insert(ty_constraints);
-                if copy.error {
-                    continue;
-                }
-
-                candidates.push((instance, queued_constraints, copy));
-            }
-
-            if candidates.len() != 1 {
-                self.db
-                    .borrow_mut()
-                    .flag_unresolved(bound.source, temp_node);
+            self.resolve_bound(bound);
 
-                self.source = prev_source;
-                continue;
-            }
-
-            let (instance, constraints, mut copy) = candidates.into_iter().next().unwrap();
+            self.source = prev_source;
+        }
 
-            // Resolve bounds and other constraints on the candidate.
-            copy.insert(constraints);
-            if copy.error {
-                self.db
-                    .borrow_mut()
-                    .flag_unresolved(bound.source, temp_node);
+        for deferred in deferred_bounds {
+            let prev_source = self.source.replace(deferred.bound.source);
+            self.select_instance(deferred);
+            self.source = prev_source;
+        }
 
-                self.source = prev_source;
-                continue;
-            }
+        self.progress.take()
+    }
 
-            // Incorporate the resolved types from the selected instance
-            *self = copy;
+    fn resolve_bound(&mut self, bound: Instantiation<Db>) {
+        if self.halted {
+            self.queue.push(Constraint::Bound(Bound(bound)));
+            return;
+        }
 
-            self.progress.set();
+        let instances =
             self.db
                 .borrow_mut()
-                .flag_resolved(bound.source, instance, temp_node);
+                .get_trait_instances(bound.source, bound.node, bound.definition);
 
-            self.source = prev_source;
+        let mut candidates = Vec::new();
+        for (instance, instantiation) in instances {
+            let mut ty_constraints = Vec::new();
+            let mut queued_constraints = Vec::new();
+            self.instantiate(instantiation, &mut ty_constraints, &mut queued_constraints);
+
+            candidates.push(Candidate {
+                instance,
+                default: self.db.borrow_mut().is_default_instance(instance),
+                ty_constraints,
+                queued_constraints,
+            });
         }
 
-        self.progress.take()
+        self.select_instance(DeferredBound { bound, candidates });
     }
-}
 
-impl<Db: crate::Db> Solver<'_, Db> {
-    fn key_for_node(&mut self, node: Db::Node) -> GroupKey<Db> {
-        self.keys
-            .key_for_node(node, || self.unify.new_key(Group::new(node)))
-    }
+    /// Resolve the bound with the candidate that matches its type, or defer
+    /// it if more than one does.
+    fn select_instance(&mut self, deferred: DeferredBound<Db>) {
+        // Apply each instance's constraints in a snapshot, so if the instance
+        // fails to match, we can reset.
+        let mut matching = Vec::new();
+        for candidate in &deferred.candidates {
+            if self.halted {
+                break;
+            }
 
-    fn try_key_for_node(&self, node: Db::Node) -> Option<GroupKey<Db>> {
-        self.keys.try_key_for_node(node)
+            let snapshot = self.snapshot();
+            self.insert(candidate.ty_constraints.clone());
+            let error = self.error;
+            self.rollback_to(snapshot);
+
+            if !error {
+                matching.push(candidate.clone());
+            }
+        }
+
+        // Keep the bound so it's reported as unsolved
+        if self.halted {
+            self.deferred_bounds.push(deferred);
+            return;
+        }
+
+        let bound = deferred.bound;
+
+        let (mut candidates, default_candidates) = matching
+            .iter()
+            .cloned()
+            .partition::<Vec<_>, _>(|candidate| !candidate.default);
+
+        // Fall back to a default instance if no other instance matches, or if
+        // the bound is still ambiguous once the solver reaches a fixpoint
+        let use_default = candidates.is_empty()
+            || (candidates.len() > 1 && self.report_ambiguous && default_candidates.len() == 1);
+
+        if use_default && !default_candidates.is_empty() {
+            candidates = default_candidates;
+        }
+
+        if candidates.len() > 1 {
+            // Other constraints may still determine the bound's type, so wait
+            // until the solver reaches a fixpoint before reporting
+            if !self.report_ambiguous {
+                self.deferred_bounds.push(DeferredBound {
+                    bound,
+                    candidates: matching,
+                });
+
+                return;
+            }
+
+            if self.check_failed() {
+                return;
+            }
+
+            let candidates = candidates
+                .into_iter()
+                .map(|candidate| candidate.instance)
+                .collect();
+
+            self.reports
+                .push(Report::Ambiguous(bound.source, candidates, bound.node));
+
+            return;
+        }
+
+        let Some(Candidate {
+            instance,
+            default,
+            ty_constraints,
+            queued_constraints,
+        }) = candidates.pop()
+        else {
+            if !self.check_failed() {
+                self.reports
+                    .push(Report::Unresolved(bound.source, bound.node));
+            }
+
+            return;
+        };
+
+        // The candidate's bounds are resolved while inserting its constraints,
+        // which may require the same trait again on a larger type. Every
+        // enclosing candidate fails too, and the limit is reported on the
+        // outermost bound.
+        if self.bound_depth >= self.limits.max_depth {
+            self.recursion_limited = true;
+            self.error = true;
+            return;
+        }
+
+        // Resolve bounds and other constraints on the candidate.
+        let snapshot = self.snapshot();
+        self.bound_depth += 1;
+        self.insert(ty_constraints);
+        self.insert(queued_constraints);
+        self.bound_depth -= 1;
+
+        // Don't keep a partly solved candidate
+        if self.halted {
+            self.rollback_to(snapshot);
+            self.queue.push(Constraint::Bound(Bound(bound)));
+            return;
+        }
+
+        if self.error {
+            self.rollback_to(snapshot);
+
+            if self.recursion_limited && self.bound_depth > 0 {
+                self.error = true;
+                return;
+            }
+
+            let report = if mem::take(&mut self.recursion_limited) {
+                Report::RecursionLimit(bound.source, bound.node)
+            } else {
+                Report::Unresolved(bound.source, bound.node)
+            };
+
+            if !self.check_failed() {
+                self.reports.push(report);
+            }
+
+            return;
+        }
+
+        // Keep the resolved types from the selected instance
+        self.commit(snapshot);
+
+        self.progress.set();
+        if self.db.borrow_mut().is_error_instance(instance) {
+            // The instance's types are kept like any other instance, but using
+            // it is reported once the types are known
+            self.custom_errors
+                .push((bound.source, instance, bound.substitutions.clone()));
+        } else if self.checking.is_none() {
+            self.reports
+                .push(Report::Resolved(bound.source, instance, bound.node));
+        }
+
+        if default && self.checking.is_none() {
+            self.reports
+                .push(Report::DefaultInstance(bound.source, instance));
+        }
+
+        let outputs = self.bound_outputs.get(&bound.node).cloned();
+        for (parameter, output, ty) in outputs.into_iter().flatten() {
+            self.trait_outputs.push((bound.source, parameter, output));
+            self.insert([Constraint::Ty(output, ty)]);
+        }
+    }
+
+    /// While checking a bound with [`Solver::check_super_instances`], record
+    /// that it failed rather than reporting it. Returns whether the failure
+    /// was recorded.
+    fn check_failed(&mut self) -> bool {
+        match &mut self.checking {
+            Some(failed) => {
+                *failed = true;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<Db: crate::Db> Solver<'_, Db> {
+    /// Start recording changes so they can be undone with
+    /// [`Solver::rollback_to`]. Constraints inserted until then are solved
+    /// separately from the constraints already in the queue.
+    fn snapshot(&mut self) -> Snapshot<Db> {
+        self.open_snapshots += 1;
+
+        Snapshot {
+            unify: self.unify.snapshot(),
+            undo_log_len: self.undo_log.len(),
+            queue: mem::take(&mut self.queue),
+            deferred_bounds: mem::take(&mut self.deferred_bounds),
+            deferred_instantiations: mem::take(&mut self.deferred_instantiations),
+            bound_nodes_len: self.bound_nodes.len(),
+            trait_outputs_len: self.trait_outputs.len(),
+            custom_errors_len: self.custom_errors.len(),
+            reports_len: self.reports.len(),
+            copies_len: self.copies.len(),
+            checkpoint: self.db.borrow_mut().checkpoint(),
+            progress: self.progress,
+            error: mem::take(&mut self.error),
+        }
+    }
+
+    fn rollback_to(&mut self, snapshot: Snapshot<Db>) {
+        self.open_snapshots -= 1;
+
+        self.unify.rollback_to(snapshot.unify);
+
+        while self.undo_log.len() > snapshot.undo_log_len {
+            match self.undo_log.pop().unwrap() {
+                Undo::NewKey(node, key) => self.keys.remove(node, key),
+                Undo::Representative(node, group, prev) => {
+                    self.keys.restore_representative(node, group, prev)
+                }
+                Undo::Group(key, Some(ty)) => {
+                    self.groups.insert(key, ty);
+                }
+                Undo::Group(key, None) => {
+                    self.groups.remove(&key);
+                }
+                Undo::Other(node) => {
+                    if let btree_map::Entry::Occupied(mut entry) = self.others.entry(node) {
+                        entry.get_mut().pop();
+                        if entry.get().is_empty() {
+                            entry.remove();
+                        }
+                    }
+                }
+                Undo::Generalization(node, Some(generalization)) => {
+                    self.generalizations.insert(node, generalization);
+                }
+                Undo::Generalization(node, None) => {
+                    self.generalizations.remove(&node);
+                }
+            }
+        }
+
+        self.queue = snapshot.queue;
+        self.deferred_bounds = snapshot.deferred_bounds;
+        self.deferred_instantiations = snapshot.deferred_instantiations;
+        self.bound_nodes.truncate(snapshot.bound_nodes_len);
+        self.trait_outputs.truncate(snapshot.trait_outputs_len);
+        self.custom_errors.truncate(snapshot.custom_errors_len);
+        self.reports.truncate(snapshot.reports_len);
+        self.copies.truncate(snapshot.copies_len);
+        self.progress = snapshot.progress;
+
+        // Nothing refers to the nodes created since the snapshot anymore, like
+        // the copies of an instance that didn't match
+        self.db.borrow_mut().remove_nodes_since(snapshot.checkpoint);
+        self.error = snapshot.error;
+    }
+
+    fn commit(&mut self, snapshot: Snapshot<Db>) {
+        self.open_snapshots -= 1;
+
+        self.unify.commit(snapshot.unify);
+
+        // Changes only need to be recorded while a snapshot is open
+        if self.open_snapshots == 0 {
+            self.undo_log.clear();
+        }
+
+        self.queue.splice(0..0, snapshot.queue);
+        self.deferred_bounds.extend(snapshot.deferred_bounds);
+        self.deferred_instantiations
+            .extend(snapshot.deferred_instantiations);
+        self.error = snapshot.error;
+    }
+
+    fn record(&mut self, undo: Undo<Db>) {
+        if self.open_snapshots > 0 {
+            self.undo_log.push(undo);
+        }
+    }
+
+    fn insert_group(&mut self, key: GroupKey<Db>, ty: Ty<Db>) -> Option<Ty<Db>> {
+        let prev = self.groups.insert(key, ty);
+        self.record(Undo::Group(key, prev.clone()));
+        prev
+    }
+
+    fn remove_group(&mut self, key: GroupKey<Db>) -> Option<Ty<Db>> {
+        let prev = self.groups.remove(&key);
+        if prev.is_some() {
+            self.record(Undo::Group(key, prev.clone()));
+        }
+
+        prev
+    }
+
+    fn update_representative(&mut self, node: Db::Node, group: GroupKey<Db>) {
+        let prev = self.keys.update_representative(node, group);
+        self.record(Undo::Representative(node, group, prev));
+    }
+
+    fn push_other(&mut self, node: Db::Node, ty: Ty<Db>) {
+        self.others.entry(node).or_default().push(ty);
+        self.record(Undo::Other(node));
+    }
+
+    fn set_generalization(&mut self, node: Db::Node, generalization: Generalization<Db>) {
+        let prev = self.generalizations.insert(node, generalization);
+        self.record(Undo::Generalization(node, prev));
+    }
+}
+
+impl<Db: crate::Db> Solver<'_, Db> {
+    fn key_for_node(&mut self, node: Db::Node) -> GroupKey<Db> {
+        if let Some(key) = self.keys.try_key_for_node(node) {
+            return key;
+        }
+
+        let key = self
+            .keys
+            .key_for_node(node, || self.unify.new_key(Group::new(node)));
+
+        self.record(Undo::NewKey(node, key));
+
+        key
+    }
+
+    fn try_key_for_node(&self, node: Db::Node) -> Option<GroupKey<Db>> {
+        self.keys.try_key_for_node(node)
     }
 
     fn node_for_key(&self, key: GroupKey<Db>) -> Db::Node {
@@ -323,11 +1358,10 @@ impl<Db: crate::Db> Solver<'_, Db> {
                 let key = self.key_for_node(node);
                 let representative_key = self.unify.find(key);
                 let representative = self.node_for_key(representative_key);
-                if let Some(mut representative_ty) = self.groups.remove(&representative_key) {
+                if let Some(mut representative_ty) = self.remove_group(representative_key) {
                     self.apply_ty(&mut representative_ty);
 
-                    self.groups
-                        .insert(representative_key, representative_ty.clone());
+                    self.insert_group(representative_key, representative_ty.clone());
 
                     *ty = representative_ty;
                 } else {
@@ -343,29 +1377,83 @@ impl<Db: crate::Db> Solver<'_, Db> {
         ty_constraints: &mut Vec<Constraint<Db>>,
         queued_constraints: &mut Vec<Constraint<Db>>,
     ) {
-        let (copy, copy_constraints) = self.db.borrow_mut().clone_node_tree(
+        let (copy, copy_constraints) = self.clone_node_tree(
+            instantiation.source,
             instantiation.definition,
             &mut instantiation.substitutions,
             true,
         );
 
         // Ensure the types unify before trying bounds and other constraints.
-        for constraint in copy_constraints {
-            if let Constraint::Ty(..) = constraint {
-                ty_constraints.push(constraint);
-            } else {
-                queued_constraints.push(constraint);
+        for mut constraint in copy_constraints {
+            match &mut constraint {
+                Constraint::Ty(..) | Constraint::Instantiation(_) | Constraint::Generalize(..) => {
+                    ty_constraints.push(constraint)
+                }
+                Constraint::Bound(bound) => {
+                    // Bounds are reported on the code that instantiated the
+                    // definition
+                    bound.0.source = instantiation.source;
+                    queued_constraints.push(constraint);
+                }
             }
         }
 
         // Unify the node with the untyped copy first to form better groups.
         ty_constraints.push(Constraint::Ty(instantiation.node, Ty::Of(copy)));
 
-        // Now that we've formed groups, unify back with the original definition type.
-        ty_constraints.push(Constraint::Ty(copy, Ty::Of(instantiation.definition)));
+        // Now that we've formed groups, unify back with the original definition
+        // type. This is queued so the copy's own types are known first;
+        // otherwise the copy would join the definition's group.
+        queued_constraints.push(Constraint::Ty(copy, Ty::Of(instantiation.definition)));
+    }
+
+    /// Copy `node` for `source`. The copies are removed from the database
+    /// when `source` is solved again, and `source` is solved again whenever
+    /// `node` is.
+    fn clone_node_tree(
+        &mut self,
+        source: Db::Node,
+        node: Db::Node,
+        substitutions: &mut Substitutions<Db>,
+        hide: bool,
+    ) -> (Db::Node, Vec<Constraint<Db>>) {
+        let mut db = self.db.borrow_mut();
+
+        let checkpoint = db.checkpoint();
+        let result = db.clone_node_tree(node, substitutions, hide);
+
+        self.instantiated_by.entry(node).or_default().insert(source);
+
+        self.copies.extend(
+            db.nodes_since(&checkpoint)
+                .into_iter()
+                .map(|copy| (source, copy)),
+        );
+
+        result
+    }
+
+    /// Like [`Solver::clone_node_tree`], but for a new node.
+    fn fresh_node(&mut self, source: Db::Node) -> Db::Node {
+        let node = self.db.borrow_mut().fresh_node();
+        self.copies.push((source, node));
+        node
     }
 
     fn unify_node_ty(&mut self, node: Db::Node, mut ty: Ty<Db>) {
+        // A type parameter is the type of its own node. `unify_tys` treats
+        // parameters as wildcards, so assign the type to the group directly.
+        if ty == Ty::Parameter(node) {
+            let key = self.key_for_node(node);
+            let representative_key = self.unify.find(key);
+            if !self.groups.contains_key(&representative_key) {
+                self.insert_group(representative_key, ty);
+                self.progress.set();
+                return;
+            }
+        }
+
         // `Ty::Of(node)` will resolve to the representative for `node`, so this
         // effectively unifies the node's type with the other types in the
         // node's group
@@ -373,7 +1461,7 @@ impl<Db: crate::Db> Solver<'_, Db> {
 
         if result.is_err() {
             self.error = true;
-            self.others.entry(node).or_default().push(ty);
+            self.push_other(node, ty);
         }
     }
 
@@ -394,7 +1482,7 @@ impl<Db: crate::Db> Solver<'_, Db> {
             }
             (other, ty @ &mut Ty::Of(node)) | (ty @ &mut Ty::Of(node), other) => {
                 let key = self.key_for_node(node);
-                let existing = self.groups.insert(key, other.clone());
+                let existing = self.insert_group(key, other.clone());
                 assert!(existing.is_none());
 
                 *ty = other.clone();
@@ -471,12 +1559,12 @@ impl<Db: crate::Db> Solver<'_, Db> {
                 continue;
             }
 
-            if let Some(ty) = self.groups.remove(&key) {
-                self.groups.insert(representative_key, ty);
+            if let Some(ty) = self.remove_group(key) {
+                self.insert_group(representative_key, ty);
             }
 
             let node = self.node_for_key(key);
-            self.keys.update_representative(node, representative_key);
+            self.update_representative(node, representative_key);
         }
 
         *left_node = representative;
@@ -510,3 +1598,1058 @@ impl Progress {
         }
     }
 }
+
+#[cfg(all(test, feature = "dsl"))]
+mod tests {
+    use super::*;
+    use crate::dsl::{MemoryDb, MemoryNode, Program, tests::Rng};
+
+    fn solve(source: &str) -> String {
+        let mut program = Program::parse(source).unwrap();
+        program.solve();
+        program.db.report()
+    }
+
+    const SHOW: &str = "
+type Number
+type Text
+type Unit
+type Maybe value
+def Show {
+    Show : (value) -> Unit
+    value : 'value
+}
+def showNumber {
+    instantiate Show for showNumber [value: Number]
+}
+def showMaybe {
+    instantiate Show for showMaybe [value: Maybe element]
+    element : 'element
+    where bound Show for showElement [value: element] in showMaybe
+}
+instance Show showNumber
+instance Show showMaybe
+";
+
+    #[test]
+    fn test_unify_nodes() {
+        assert_eq!(
+            solve("type Number\na : b\nb : c\nc : Number\n"),
+            "a : Number\nb : Number\nc : Number\n"
+        );
+    }
+
+    #[test]
+    fn test_conflicting_types() {
+        assert_eq!(
+            solve("type Number\ntype Text\na : Number\na : Text\nb : a\n"),
+            "a : Number\na : Text\nb : Number\nb : Text\n"
+        );
+    }
+
+    #[test]
+    fn test_instantiate_definition() {
+        let source = "
+type Number
+type Text
+def id {
+    id : (x) -> x
+    x : 'x
+}
+n : Number
+t : Text
+instantiate id for a
+a : (n) -> r
+instantiate id for b [x: t]
+";
+
+        assert_eq!(
+            solve(source),
+            "\
+id : ('x) -> 'x
+x : 'x
+n : Number
+t : Text
+a : (Number) -> Number
+r : Number
+b : (Text) -> Text
+"
+        );
+    }
+
+    #[test]
+    fn test_resolve_bounds() {
+        let source = format!(
+            "{SHOW}
+x : Maybe (Maybe Number)
+bound Show for show [value: x]
+y : Text
+bound Show for unresolved [value: y]
+bound Show for ambiguous [value: z]
+"
+        );
+
+        let report = solve(&source);
+        let flags = report
+            .lines()
+            .skip_while(|line| !line.contains(" with "))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            flags,
+            [
+                "resolved show with showNumber ((Number) -> Unit)",
+                "resolved show with showMaybe ((Maybe Number) -> Unit)",
+                "resolved show with showMaybe ((Maybe (Maybe Number)) -> Unit)",
+                "unresolved unresolved ((Text) -> Unit)",
+                "ambiguous ambiguous between showNumber, showMaybe ((value.54) -> Unit)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_default_instances() {
+        let source = format!(
+            "{SHOW}
+def showAny {{
+    instantiate Show for showAny [value: any]
+    any : 'any
+}}
+default instance Show showAny
+x : Maybe Number
+bound Show for maybe [value: x]
+y : Text
+bound Show for text [value: y]
+bound Show for unknown [value: z]
+"
+        );
+
+        let report = solve(&source);
+        let flags = report
+            .lines()
+            .filter(|line| line.starts_with("resolved ") || line.starts_with("default "))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            flags,
+            [
+                "resolved maybe with showNumber ((Number) -> Unit)",
+                "resolved maybe with showMaybe ((Maybe Number) -> Unit)",
+                "resolved text with showAny ((Text) -> Unit)",
+                "default text with showAny",
+                "resolved unknown with showAny ((value.57) -> Unit)",
+                "default unknown with showAny",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_instances() {
+        let source = format!(
+            "{SHOW}
+def showFunction {{
+    instantiate Show for showFunction [value: (input) -> output]
+    input : 'input
+    output : 'output
+}}
+error instance Show showFunction
+f : (Number) -> Text
+bound Show for show [value: f]
+"
+        );
+
+        let report = solve(&source);
+        let flags = report
+            .lines()
+            .filter(|line| line.starts_with("resolved ") || line.starts_with("error "))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            flags,
+            ["error show with showFunction [value: (Number) -> Text]"]
+        );
+    }
+
+    #[test]
+    fn test_overlapping_instances() {
+        let source = format!(
+            "{SHOW}
+def showMaybeNumber {{
+    instantiate Show for showMaybeNumber [value: Maybe Number]
+}}
+instance Show showMaybeNumber
+"
+        );
+
+        let report = solve(&source);
+        let flags = report
+            .lines()
+            .filter(|line| line.starts_with("overlapping "))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            flags,
+            [
+                "overlapping showMaybe with showMaybeNumber",
+                "overlapping showMaybeNumber with showMaybe",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overlap_check_does_not_resolve_bounds() {
+        let source = "
+type Number
+type Text
+type Boolean
+def Equal {
+    Equal : (value) -> Boolean
+    value : 'value
+}
+def Order {
+    Order : (value) -> Number
+    value : 'value
+    where bound Equal for equal [value: value] in Order
+}
+def equalNumber {
+    instantiate Equal for equalNumber [value: Number]
+}
+def orderNumber {
+    instantiate Order for orderNumber [value: Number]
+}
+def orderText {
+    instantiate Order for orderText [value: Text]
+}
+instance Equal equalNumber
+instance Order orderNumber
+instance Order orderText
+";
+
+        let report = solve(source);
+        let flags = report
+            .lines()
+            .filter(|line| line.starts_with("resolved ") || line.starts_with("unresolved "))
+            .collect::<Vec<_>>();
+
+        // Only the instances' own definitions resolve `Equal`
+        assert_eq!(
+            flags,
+            [
+                "resolved orderNumber with equalNumber ((Number) -> Boolean)",
+                "unresolved orderText ((Text) -> Boolean)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_super_instances() {
+        let source = "
+type Number
+type Text
+type Boolean
+type Maybe value
+def Equal {
+    Equal : (value) -> Boolean
+    value : 'value
+}
+def Order {
+    Order : (value) -> Number
+    value : 'value
+    where bound Equal for equal [value: value] in Order
+}
+def equalNumber {
+    instantiate Equal for equalNumber [value: Number]
+}
+def equalText {
+    instantiate Equal for equalText [value: Text]
+    where bound Equal for equalAgain [value: Text] in equalText
+}
+def equalMaybeNumber {
+    instantiate Equal for equalMaybeNumber [value: Maybe Number]
+}
+def equalMaybeText {
+    instantiate Equal for equalMaybeText [value: Maybe Text]
+}
+def orderNumber {
+    instantiate Order for orderNumber [value: Number]
+}
+def orderBoolean {
+    instantiate Order for orderBoolean [value: Boolean]
+}
+def orderText {
+    instantiate Order for orderText [value: Text]
+}
+def orderMaybe {
+    instantiate Order for orderMaybe [value: Maybe element]
+}
+instance Equal equalNumber
+instance Equal equalText
+instance Equal equalMaybeNumber
+instance Equal equalMaybeText
+instance Order orderNumber
+instance Order orderBoolean
+instance Order orderText
+instance Order orderMaybe
+";
+
+        let report = solve(source);
+        let flags = report
+            .lines()
+            .filter(|line| line.starts_with("missing "))
+            .collect::<Vec<_>>();
+
+        // `Equal Text` requires itself, and `Equal (Maybe element)` matches
+        // both `Maybe` instances
+        assert_eq!(
+            flags,
+            [
+                "missing super instance orderBoolean of Equal",
+                "missing super instance orderText of Equal",
+                "missing super instance orderMaybe of Equal",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trait_outputs() {
+        let source = "
+type Number
+type Text
+type List element
+def Element {
+    Element : (collection) -> element
+    collection : 'collection
+    element : 'element
+}
+def listElement {
+    instantiate Element for listElement [collection: List e, element: e]
+    e : 'e
+}
+def textElement {
+    instantiate Element for textElement [collection: Text, element: Text]
+}
+instance Element listElement
+instance Element textElement
+output Element element
+xs : List Number
+bound Element for first [collection: xs, element: x]
+ys : List y
+bound Element for second [collection: ys, element: z]
+z : Text
+bound Element for ambiguous [collection: c, element: Number]
+";
+
+        let report = solve(source);
+
+        assert!(report.contains("\nx : Number\n"), "{report}");
+        assert!(
+            report.contains("resolved first with listElement"),
+            "{report}"
+        );
+        assert!(report.contains("output first element = Number"), "{report}");
+
+        // The collection's element type is only known through the output
+        assert!(report.contains("\ny : Text\n"), "{report}");
+        assert!(
+            report.contains("resolved second with listElement"),
+            "{report}"
+        );
+
+        // The output isn't used to select an instance, even though only
+        // `listElement` could produce a `Number`
+        assert!(
+            report.contains("ambiguous ambiguous between listElement, textElement"),
+            "{report}"
+        );
+    }
+
+    #[test]
+    fn test_bound_order_with_trait_outputs() {
+        // Like `show (1 + 2)`: the type `Show` needs is only known once `Add`
+        // is resolved and unifies its output
+        let definitions = "
+type Number
+type Text
+type Unit
+def Show {
+    Show : (value) -> Unit
+    value : 'value
+}
+def showNumber {
+    instantiate Show for showNumber [value: Number]
+}
+def showText {
+    instantiate Show for showText [value: Text]
+}
+instance Show showNumber
+instance Show showText
+def Add {
+    Add : (left, right) -> output
+    left : 'left
+    right : 'right
+    output : 'output
+}
+def addNumber {
+    instantiate Add for addNumber [left: Number, right: Number, output: Number]
+}
+def addText {
+    instantiate Add for addText [left: Text, right: Text, output: Text]
+}
+instance Add addNumber
+instance Add addText
+output Add output
+one : Number
+two : Number
+";
+
+        let show = "bound Show for show [value: sum]\n";
+        let add = "bound Add for add [left: one, right: two, output: sum]\n";
+
+        let bounds = |report: &str| {
+            report
+                .lines()
+                .filter(|line| {
+                    ["resolved ", "unresolved ", "ambiguous "]
+                        .iter()
+                        .any(|prefix| line.starts_with(prefix))
+                })
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        let show_first = solve(&format!("{definitions}{show}{add}"));
+        let add_first = solve(&format!("{definitions}{add}{show}"));
+
+        assert_eq!(
+            bounds(&show_first),
+            [
+                "resolved add with addNumber ((Number, Number) -> Number)",
+                "resolved show with showNumber ((Number) -> Unit)",
+            ],
+            "{show_first}"
+        );
+        assert_eq!(bounds(&show_first), bounds(&add_first), "{add_first}");
+        assert!(show_first.contains("\nsum : Number\n"), "{show_first}");
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let source = "
+type Unit
+type Maybe value
+def Show {
+    Show : (value) -> Unit
+    value : 'value
+}
+def showMaybe {
+    instantiate Show for showMaybe [value: Maybe element]
+    element : 'element
+    where bound Show for showElement [value: Maybe element] in showMaybe
+}
+instance Show showMaybe
+bound Show for show [value: Maybe x]
+";
+
+        let mut program = Program::parse(source).unwrap();
+        let constraints = program.all_constraints();
+
+        let mut solver = Solver::new(&mut program.db).with_limits(SolverLimits {
+            max_depth: 3,
+            ..Default::default()
+        });
+
+        solver.insert(constraints);
+        solver.finish();
+
+        let report = program.db.report();
+        let flags = report
+            .lines()
+            .skip_while(|line| !line.contains(" limit "))
+            .collect::<Vec<_>>();
+
+        // None of the nested bounds are resolved, since the instance can't be
+        // used for them either
+        assert_eq!(flags, ["recursion limit show ((Maybe x) -> Unit)"]);
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let source =
+            format!("{SHOW}bound Show for show [value: Maybe (Maybe Number)]\nn : Number\n");
+
+        let mut program = Program::parse(&source).unwrap();
+        let n = program.db.lookup("n").unwrap();
+        let (later, constraints) = program
+            .all_constraints()
+            .into_iter()
+            .partition::<Vec<_>, _>(|constraint| constraint.owner() == n);
+
+        let mut solver = Solver::new(&mut program.db).with_limits(SolverLimits {
+            max_steps: 2,
+            ..Default::default()
+        });
+
+        solver.insert(constraints);
+
+        // A new call to `insert` gets a new budget, even after the previous
+        // one stopped
+        solver.insert(later);
+        solver.finish();
+
+        let report = program.db.report();
+        assert!(report.contains("step limit show\n"), "{report}");
+        assert!(report.contains("n : Number\n"), "{report}");
+    }
+
+    #[test]
+    fn test_default_limits_fit_large_programs() {
+        let mut source = String::from(SHOW);
+        for index in 0..300 {
+            source.push_str(&format!(
+                "x{index} : Maybe (Maybe Number)\nbound Show for show{index} [value: x{index}]\n"
+            ));
+        }
+
+        let report = solve(&source);
+
+        assert_eq!(report.matches("resolved show").count(), 900);
+        assert!(!report.contains("step limit"), "{report}");
+        assert!(!report.contains("unsolved"), "{report}");
+    }
+
+    #[test]
+    fn test_step_limit_while_trying_candidates() {
+        let source = format!("{SHOW}bound Show for show [value: Maybe (Maybe Number)]\n");
+
+        // Stop after every possible number of steps, including while a
+        // candidate is being tried in a snapshot
+        let mut completed = false;
+        for max_steps in 1.. {
+            let mut program = Program::parse(&source).unwrap();
+            let show = program.db.lookup("show").unwrap();
+            let (bound, constraints) = program
+                .all_constraints()
+                .into_iter()
+                .partition::<Vec<_>, _>(|constraint| constraint.owner() == show);
+
+            let mut solver = Solver::new(&mut program.db);
+            solver.insert(constraints);
+            solver.limits.max_steps = max_steps;
+            solver.insert(bound);
+            solver.finish();
+
+            let report = program.db.report();
+            if !report.contains("step limit show\n") {
+                assert_eq!(report.matches("resolved show").count(), 3, "{report}");
+                assert!(!report.contains("unsolved"), "{report}");
+                completed = true;
+                break;
+            }
+
+            // The bounds that weren't resolved are reported
+            assert!(report.contains("unsolved show ("), "{max_steps}: {report}");
+        }
+
+        assert!(completed);
+    }
+
+    #[test]
+    fn test_unsolved_constraints() {
+        let source = format!("{SHOW}bound Show for show [value: Number]\nn : Number\n");
+
+        let mut program = Program::parse(&source).unwrap();
+        let constraints = program.all_constraints();
+
+        // Bounds are never resolved while only solving types, so the bound is
+        // left over
+        let mut solver = Solver::new(&mut program.db);
+        solver.types_only = true;
+        solver.insert(constraints);
+        solver.finish();
+
+        let report = program.db.report();
+        assert!(report.contains("n : Number\n"), "{report}");
+        assert!(report.contains("unsolved show ("), "{report}");
+    }
+
+    #[test]
+    fn test_deferred_bounds_are_instantiated_once() {
+        // The bound matches both instances until the solver reaches a
+        // fixpoint, and is retried with the same instantiations then
+        let source = format!("{SHOW}bound Show for show [value: x]\n");
+
+        let mut program = Program::parse(&source).unwrap();
+        let constraints = program.all_constraints();
+
+        let mut solver = Solver::new(&mut program.db);
+        solver.insert(constraints);
+        solver.finish();
+
+        let report = program.db.report();
+        assert!(report.contains("ambiguous show"), "{report}");
+
+        let copies = program
+            .db
+            .copies()
+            .filter(|&node| program.db.name(node).starts_with("element."))
+            .count();
+
+        assert_eq!(copies, 1, "{report}");
+    }
+
+    #[test]
+    fn test_rollback_discards_nested_results() {
+        let source = "
+type Number
+type Text
+type List element
+def Element {
+    Element : (collection) -> element
+    collection : 'collection
+    element : 'element
+}
+def textElement {
+    instantiate Element for textElement [collection: Text, element: Text]
+}
+def nestedElement {
+    instantiate Element for nestedElement [collection: List c, element: Number]
+    c : 'c
+    where bound Element for inner [collection: c, element: Number] in nestedElement
+}
+instance Element textElement
+instance Element nestedElement
+output Element element
+bound Element for outer [collection: List Text, element: x]
+";
+
+        let mut program = Program::parse(source).unwrap();
+        let constraints = program.all_constraints();
+
+        let mut solver = Solver::new(&mut program.db);
+        solver.insert(constraints);
+        solver.finish();
+
+        // `inner` is resolved with `textElement` while trying `nestedElement`,
+        // but its output conflicts with `Number`, so the whole attempt is
+        // rolled back
+        let report = program.db.report();
+        let flags = report
+            .lines()
+            .skip_while(|line| !line.starts_with("unresolved ") && !line.starts_with("resolved "))
+            .collect::<Vec<_>>();
+
+        assert_eq!(flags, ["unresolved outer ((List Text) -> element.10)"]);
+
+        // The copies made during the attempt are removed too
+        let typed_copies = crate::Db::typed_nodes(&program.db)
+            .filter(|&node| program.db.copies().any(|copy| copy == node))
+            .map(|node| program.db.name(node).to_string())
+            .collect::<Vec<_>>();
+
+        assert!(typed_copies.is_empty(), "{typed_copies:?}");
+    }
+
+    #[test]
+    fn test_generalize() {
+        let source = "
+type Number
+type Text
+f : (x) -> x
+generalize f
+instantiate f for a
+a : (Number) -> r
+instantiate f for b
+b : (Text) -> s
+";
+
+        assert_eq!(
+            solve(source),
+            "\
+f : ('x) -> 'x
+x : 'x
+a : (Number) -> Number
+r : Number
+b : (Text) -> Text
+s : Text
+generalized f
+"
+        );
+    }
+
+    #[test]
+    fn test_generalize_keeps_environment_shared() {
+        // Like `f : x -> { g : z -> x ; (g n :: Number) ; (g n :: Text) }`
+        let source = "
+type Number
+type Text
+g : (z) -> x
+generalize g in x
+instantiate g for a
+a : (n) -> Number
+instantiate g for b
+b : (n) -> Text
+";
+
+        let report = solve(source);
+
+        // Only `z` is generalized, so both uses return the same `x` and the
+        // second use conflicts with the first
+        assert!(report.contains("g : ('z) -> Number\n"), "{report}");
+        assert!(
+            report.contains("b : (n) -> Text\nb : (n) -> Number\n"),
+            "{report}"
+        );
+    }
+
+    #[test]
+    fn test_generalize_waits_for_bounds() {
+        // Like `f : g -> show (g 1)` followed by `f (x -> 2)`: the bound's type
+        // is only known once `f` is used, after the solver reaches a fixpoint
+        let source = format!(
+            "{SHOW}
+f : (g) -> Unit
+g : (Number) -> v
+bound Show for show [value: v]
+generalize f
+instantiate f for a
+a : (h) -> Unit
+h : (Number) -> Number
+"
+        );
+
+        let report = solve(&source);
+
+        // Generalizing `f` would lose the bound, since `a`'s copy of `v` isn't
+        // the one the bound refers to
+        assert!(!report.contains("generalized f"), "{report}");
+        assert!(
+            report.contains("f : ((Number) -> Number) -> Unit\n"),
+            "{report}"
+        );
+        assert!(
+            report.contains("resolved show with showNumber ((Number) -> Unit)"),
+            "{report}"
+        );
+        assert!(!report.contains("ambiguous "), "{report}");
+        assert!(!report.contains("unresolved "), "{report}");
+
+        // Stopping early must not generalize `f` either; the bound is reported
+        // as unsolved instead
+        for max_steps in 1.. {
+            let mut program = Program::parse(&source).unwrap();
+            let constraints = program.all_constraints();
+
+            let mut solver = Solver::new(&mut program.db).with_limits(SolverLimits {
+                max_steps,
+                ..Default::default()
+            });
+
+            solver.insert(constraints);
+            solver.finish();
+
+            let report = program.db.report();
+            assert!(!report.contains("generalized f"), "{max_steps}: {report}");
+            assert!(
+                report.contains("resolved show with showNumber")
+                    || report.contains("unsolved show ("),
+                "{max_steps}: {report}"
+            );
+
+            if !report.contains("step limit ") {
+                break;
+            }
+        }
+    }
+
+    /// Returns a program whose constraints all agree with a randomly chosen
+    /// type for each node, along with that type.
+    fn random_consistent_program(rng: &mut Rng) -> (Program, BTreeMap<MemoryNode, String>) {
+        fn random_ty(rng: &mut Rng, db: &MemoryDb, depth: usize) -> Ty<MemoryDb> {
+            let name = |name| db.lookup(name).unwrap();
+
+            match rng.below(if depth == 0 { 2 } else { 3 }) {
+                0 => Ty::Named {
+                    name: name("Number"),
+                    parameters: BTreeMap::new(),
+                },
+                1 => Ty::Named {
+                    name: name("Text"),
+                    parameters: BTreeMap::new(),
+                },
+                _ => Ty::Named {
+                    name: name("Pair"),
+                    parameters: BTreeMap::from([
+                        (name("left"), random_ty(rng, db, depth - 1)),
+                        (name("right"), random_ty(rng, db, depth - 1)),
+                    ]),
+                },
+            }
+        }
+
+        // Replace parts of the type with nodes known to have that type
+        fn abstract_ty(
+            rng: &mut Rng,
+            ty: &mut Ty<MemoryDb>,
+            truth: &BTreeMap<MemoryNode, Ty<MemoryDb>>,
+        ) {
+            let candidates = truth
+                .iter()
+                .filter(|&(_, other)| other == ty)
+                .map(|(&node, _)| node)
+                .collect::<Vec<_>>();
+
+            if !candidates.is_empty() && rng.chance(30) {
+                *ty = Ty::Of(candidates[rng.below(candidates.len())]);
+                return;
+            }
+
+            if let Ty::Named { parameters, .. } = ty {
+                for parameter in parameters.values_mut() {
+                    abstract_ty(rng, parameter, truth);
+                }
+            }
+        }
+
+        let mut program = Program::parse("type Number\ntype Text\ntype Pair left right\n").unwrap();
+
+        let mut truth = BTreeMap::new();
+        for index in 0..8 {
+            let node = program.db.node(&format!("n{index}"));
+            truth.insert(node, random_ty(rng, &program.db, 2));
+        }
+
+        let nodes = truth.keys().copied().collect::<Vec<_>>();
+        for _ in 0..12 {
+            let node = nodes[rng.below(nodes.len())];
+
+            let mut ty = truth[&node].clone();
+            abstract_ty(rng, &mut ty, &truth);
+
+            // Don't constrain a node to its own type
+            if ty != Ty::Of(node) {
+                program.constraints.push(Constraint::Ty(node, ty));
+            }
+        }
+
+        let truth = truth
+            .into_iter()
+            .map(|(node, ty)| (node, program.db.display_ty(&ty)))
+            .collect();
+
+        (program, truth)
+    }
+
+    fn tys(db: &MemoryDb, ty_groups: &TyGroups<MemoryDb>) -> BTreeMap<MemoryNode, Vec<String>> {
+        db.named_nodes()
+            .filter_map(|node| {
+                let index = ty_groups.index_of(node)?;
+
+                let tys = ty_groups
+                    .tys_at(index)
+                    .iter()
+                    .map(|ty| db.display_ty(ty))
+                    .collect();
+
+                Some((node, tys))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_solution_does_not_depend_on_constraint_order() {
+        for seed in 0..100 {
+            let mut rng = Rng::new(seed);
+            let (program, truth) = random_consistent_program(&mut rng);
+
+            let mut expected = None;
+            for _ in 0..5 {
+                let mut program = program.clone();
+                rng.shuffle(&mut program.constraints);
+
+                let ty_groups = program.solve();
+                let tys = tys(&program.db, &ty_groups);
+
+                for (node, tys) in &tys {
+                    let Some(truth) = truth.get(node) else {
+                        continue;
+                    };
+
+                    assert_eq!(
+                        tys.len(),
+                        1,
+                        "seed {seed}: {}\n{program}",
+                        program.db.name(*node)
+                    );
+
+                    // Unconstrained nodes are unknown, and partially
+                    // constrained nodes may be incomplete
+                    let unknown = tys[0].starts_with('?');
+                    if !unknown && !program.db.is_incomplete(*node) {
+                        assert_eq!(&tys[0], truth, "seed {seed}\n{program}");
+                    }
+                }
+
+                match &expected {
+                    Some(expected) => assert_eq!(&tys, expected, "seed {seed}\n{program}"),
+                    None => expected = Some(tys),
+                }
+            }
+        }
+    }
+
+    fn by_owner(
+        constraints: &[Constraint<MemoryDb>],
+    ) -> BTreeMap<MemoryNode, Vec<Constraint<MemoryDb>>> {
+        let mut owned = BTreeMap::<_, Vec<_>>::new();
+        for constraint in constraints {
+            owned
+                .entry(constraint.owner())
+                .or_default()
+                .push(constraint.clone());
+        }
+
+        owned
+    }
+
+    /// Everything the solver wrote to the database, sorted. Copies are
+    /// numbered in the order they're made, so the numbers are left out.
+    fn facts(db: &MemoryDb) -> Vec<String> {
+        fn without_numbers(fact: String) -> String {
+            let mut chars = fact.chars().peekable();
+            let mut result = String::new();
+            while let Some(c) = chars.next() {
+                if c == '.' && chars.peek().is_some_and(char::is_ascii_digit) {
+                    while chars.next_if(char::is_ascii_digit).is_some() {}
+                } else {
+                    result.push(c);
+                }
+            }
+
+            result
+        }
+
+        let tys = db.named_nodes().chain(db.copies()).flat_map(|node| {
+            let incomplete = db
+                .is_incomplete(node)
+                .then(|| format!("{} incomplete", db.name(node)));
+
+            db.tys(node)
+                .iter()
+                .map(move |ty| format!("{} : {}", db.name(node), db.display_ty(ty)))
+                .chain(incomplete)
+        });
+
+        let flags = db.flags().iter().map(|flag| db.display_flag(flag));
+
+        let mut facts = tys.chain(flags).map(without_numbers).collect::<Vec<_>>();
+        facts.sort();
+        facts
+    }
+
+    #[test]
+    fn test_retract_removes_earlier_results() {
+        let source = format!(
+            "{SHOW}
+x : Maybe (Maybe Number)
+bound Show for show [value: x]
+y : Text
+bound Show for unresolved [value: y]
+f : (a) -> a
+generalize f
+instantiate f for g
+g : (Number) -> r
+instantiate f for h
+h : (x) -> s
+"
+        );
+
+        let program = Program::parse(&source).unwrap();
+        let owned = by_owner(&program.all_constraints());
+
+        let solve = |owned: BTreeMap<_, _>| {
+            let mut db = program.db.clone();
+            let mut solver = Solver::new(&mut db);
+            solver.insert_owned(owned);
+            solver.finish();
+            drop(solver);
+            facts(&db)
+        };
+
+        let full = solve(owned.clone());
+
+        for &owner in owned.keys() {
+            let name = program.db.name(owner);
+
+            let mut from_scratch = owned.clone();
+            from_scratch.remove(&owner);
+            let from_scratch = solve(from_scratch);
+
+            let mut db = program.db.clone();
+            let mut solver = Solver::new(&mut db);
+            solver.insert_owned(owned.clone());
+            solver.finish();
+            solver.retract([owner]);
+            solver.finish();
+            drop(solver);
+
+            assert_eq!(facts(&db), from_scratch, "retracting {name}");
+
+            let mut db = program.db.clone();
+            let mut solver = Solver::new(&mut db);
+            solver.insert_owned(owned.clone());
+            solver.retract([owner]);
+            solver.finish();
+            solver.insert_owned([(owner, owned[&owner].clone())]);
+            solver.finish();
+            drop(solver);
+
+            assert_eq!(facts(&db), full, "reinserting {name}");
+        }
+    }
+
+    #[test]
+    fn test_retract_matches_solving_from_scratch() {
+        for seed in 0..100 {
+            let mut rng = Rng::new(seed);
+            let (program, _) = random_consistent_program(&mut rng);
+
+            let owned = by_owner(&program.constraints);
+            let owners = owned.keys().copied().collect::<Vec<_>>();
+            let owner = owners[rng.below(owners.len())];
+
+            let mut full = program.clone();
+            let full_tys = full.solve();
+            let full_tys = tys(&full.db, &full_tys);
+
+            let mut from_scratch = program.clone();
+            from_scratch
+                .constraints
+                .retain(|constraint| constraint.owner() != owner);
+            let from_scratch_tys = from_scratch.solve();
+            let from_scratch_tys = tys(&from_scratch.db, &from_scratch_tys);
+
+            let mut incremental = program.clone();
+            let mut solver = Solver::new(&mut incremental.db);
+            solver.insert_owned(owned.clone());
+            solver.retract([owner]);
+            let retracted_tys = solver.finish();
+            solver.insert_owned([(owner, owned[&owner].clone())]);
+            let reinserted_tys = solver.finish();
+            drop(solver);
+
+            let name = incremental.db.name(owner).to_string();
+
+            assert_eq!(
+                tys(&incremental.db, &retracted_tys),
+                from_scratch_tys,
+                "seed {seed}: retracting {name}\n{program}"
+            );
+
+            assert_eq!(
+                tys(&incremental.db, &reinserted_tys),
+                full_tys,
+                "seed {seed}: reinserting {name}\n{program}"
+            );
+        }
+    }
+}
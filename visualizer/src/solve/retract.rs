@@ -0,0 +1,369 @@
+use super::{Generalization, Report, Solver};
+use crate::{Constraint, Ty};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    mem,
+};
+
+impl<Db: crate::Db> Solver<'_, Db> {
+    /// Remove the constraints inserted for `owners` with
+    /// [`Solver::insert_owned`]. Only the groups the constraints contributed
+    /// to are re-solved, along with any other constraints that touch them;
+    /// the rest of the solution is kept as-is.
+    pub fn retract(&mut self, owners: impl IntoIterator<Item = Db::Node>) {
+        let retracted = owners
+            .into_iter()
+            .filter_map(|owner| self.owned.remove(&owner))
+            .flatten()
+            .collect::<Vec<_>>();
+
+        self.resolve_affected(&retracted);
+    }
+
+    /// Forget the groups `changed` contributes to and solve every owned
+    /// constraint touching them again, so the result doesn't depend on
+    /// whether the constraints were added or removed incrementally.
+    pub(super) fn resolve_affected(&mut self, changed: &[Constraint<Db>]) {
+        assert_eq!(
+            self.open_snapshots, 0,
+            "cannot re-solve while trying a candidate"
+        );
+
+        if changed.is_empty() {
+            return;
+        }
+
+        // A halted run left constraints unsolved, so there's no solution to
+        // update
+        if self.halted {
+            self.resolve_all();
+            return;
+        }
+
+        let connections = self.connections();
+
+        let mut affected = BTreeSet::new();
+        let mut replay = BTreeSet::new();
+        self.affect(
+            changed.iter().flat_map(constraint_nodes),
+            &connections,
+            &mut affected,
+            &mut replay,
+        );
+
+        let partial = replay.len() < self.owned.len();
+
+        self.forget(&affected);
+
+        let constraints = replay
+            .into_iter()
+            .flat_map(|owner| self.owned[&owner].clone())
+            .collect::<Vec<_>>();
+
+        self.insert(constraints);
+
+        // The step budget grows with the number of constraints inserted, so a
+        // partial run may stop where solving from scratch wouldn't
+        if partial && self.halted {
+            self.resolve_all();
+        }
+    }
+
+    /// Forget the whole solution and solve every owned constraint again.
+    fn resolve_all(&mut self) {
+        self.keys = Default::default();
+        self.unify = Default::default();
+        self.groups.clear();
+        self.others.clear();
+        self.queue.clear();
+        self.bound_nodes.clear();
+        self.bound_outputs.clear();
+        self.trait_outputs.clear();
+        self.custom_errors.clear();
+        self.reports.clear();
+        self.deferred_bounds.clear();
+        self.deferred_instantiations.clear();
+        self.generalizations.clear();
+        self.halted = false;
+        self.step_limits.clear();
+        self.error = false;
+        self.instantiated_by.clear();
+
+        let mut db = self.db.borrow_mut();
+        for (_, copy) in self.copies.drain(..) {
+            db.remove_node(copy);
+        }
+
+        drop(db);
+
+        let constraints = self.owned.values().flatten().cloned().collect::<Vec<_>>();
+        self.insert(constraints);
+    }
+
+    /// Index the nodes each node's solution depends on, or that depend on it,
+    /// other than through its group.
+    fn connections(&mut self) -> Connections<Db> {
+        let mut referenced_by = BTreeMap::<_, Vec<_>>::new();
+
+        let tys = self
+            .groups
+            .iter()
+            .map(|(&key, ty)| (self.node_for_key(key), ty))
+            .chain(
+                self.others
+                    .iter()
+                    .flat_map(|(&node, tys)| tys.iter().map(move |ty| (node, ty))),
+            );
+
+        for (node, ty) in tys {
+            ty.traverse(&mut |ty| {
+                if let Ty::Of(other) = *ty {
+                    referenced_by.entry(other).or_default().push(node);
+                }
+            });
+        }
+
+        let mut copies = BTreeMap::<_, Vec<_>>::new();
+        for &(source, copy) in &self.copies {
+            copies.entry(source).or_default().push(copy);
+            copies.entry(copy).or_default().push(source);
+        }
+
+        let mut dependents = BTreeMap::<_, Vec<_>>::new();
+        for (&owner, constraints) in &self.owned {
+            for node in constraints.iter().flat_map(constraint_dependencies) {
+                dependents.entry(node).or_default().push(owner);
+            }
+        }
+
+        Connections {
+            referenced_by,
+            copies,
+            dependents,
+        }
+    }
+
+    /// Mark `nodes` as affected, along with every node sharing a group with
+    /// them or connected to them through a group's type. Any owned constraint
+    /// that refers to an affected node is added to `replay`, and its nodes
+    /// are affected too.
+    fn affect(
+        &mut self,
+        nodes: impl IntoIterator<Item = Db::Node>,
+        connections: &Connections<Db>,
+        affected: &mut BTreeSet<Db::Node>,
+        replay: &mut BTreeSet<Db::Node>,
+    ) {
+        let mut stack = Vec::from_iter(nodes);
+        while let Some(node) = stack.pop() {
+            if !affected.insert(node) {
+                continue;
+            }
+
+            stack.extend(connections.referenced_by(node));
+
+            // Copies are made again along with their source
+            stack.extend(connections.copies(node));
+
+            for owner in connections.dependents(node) {
+                if !replay.insert(owner) {
+                    continue;
+                }
+
+                for constraint in &self.owned[&owner] {
+                    stack.extend(constraint_nodes(constraint));
+
+                    // Solving from scratch, instantiating a variable waits
+                    // until it's generalized, so the variable is generalized
+                    // again too
+                    if let Constraint::Instantiation(instantiation) = constraint
+                        && self.generalizations.contains_key(&instantiation.definition)
+                    {
+                        stack.push(instantiation.definition);
+                    }
+                }
+            }
+
+            // Copies of a definition are made again, and bounds resolved using
+            // an instance are resolved again, since the instance may no longer
+            // match (or may now match)
+            stack.extend(
+                self.instantiated_by
+                    .get(&node)
+                    .into_iter()
+                    .flatten()
+                    .copied(),
+            );
+
+            for ty in self.others.get(&node).into_iter().flatten() {
+                ty.traverse(&mut |ty| {
+                    if let Ty::Of(node) = *ty {
+                        stack.push(node);
+                    }
+                });
+            }
+
+            let Some(key) = self.try_key_for_node(node) else {
+                continue;
+            };
+
+            let representative_key = self.unify.find(key);
+
+            stack.extend(self.unify.probe_value(representative_key).0);
+
+            if let Some(ty) = self.groups.get(&representative_key) {
+                ty.traverse(&mut |ty| {
+                    if let Ty::Of(node) = *ty {
+                        stack.push(node);
+                    }
+                });
+            }
+        }
+    }
+
+    /// Rebuild the groups without the affected nodes, and drop any other
+    /// state that refers to them.
+    fn forget(&mut self, affected: &BTreeSet<Db::Node>) {
+        self.halted = false;
+        self.step_limits.clear();
+        self.error = false;
+
+        let mut unify = mem::take(&mut self.unify);
+        let keys = mem::take(&mut self.keys);
+        let groups = mem::take(&mut self.groups);
+
+        for node in keys.nodes() {
+            if affected.contains(&node) {
+                continue;
+            }
+
+            let representative_key = unify.find(keys.try_key_for_node(node).unwrap());
+            let representative = keys.node_for_key(representative_key);
+
+            let key = self.key_for_node(node);
+            let new_representative_key = self.key_for_node(representative);
+            self.unify
+                .unify_var_var(key, new_representative_key)
+                .unwrap_or_else(|e| match e {});
+        }
+
+        for (representative_key, ty) in groups {
+            let representative = keys.node_for_key(representative_key);
+            if affected.contains(&representative) {
+                continue;
+            }
+
+            let key = self.key_for_node(representative);
+            let representative_key = self.unify.find(key);
+            self.groups.insert(representative_key, ty);
+        }
+
+        let mut db = self.db.borrow_mut();
+        self.copies.retain(|&(_, copy)| {
+            let keep = !affected.contains(&copy);
+            if !keep {
+                db.remove_node(copy);
+            }
+
+            keep
+        });
+
+        drop(db);
+
+        for sources in self.instantiated_by.values_mut() {
+            sources.retain(|source| !affected.contains(source));
+        }
+
+        self.instantiated_by
+            .retain(|_, sources| !sources.is_empty());
+
+        self.others.retain(|node, _| !affected.contains(node));
+        self.bound_nodes.retain(|node| !affected.contains(node));
+        self.bound_outputs
+            .retain(|node, _| !affected.contains(node));
+        self.trait_outputs
+            .retain(|(source, _, output)| !affected.contains(source) && !affected.contains(output));
+        self.custom_errors
+            .retain(|(source, _, _)| !affected.contains(source));
+
+        self.reports.retain(|report| match report {
+            Report::Resolved(source, _, bound)
+            | Report::Unresolved(source, bound)
+            | Report::Ambiguous(source, _, bound)
+            | Report::RecursionLimit(source, bound) => {
+                !affected.contains(source) && !affected.contains(bound)
+            }
+            Report::DefaultInstance(source, _) => !affected.contains(source),
+            Report::Generalized(node) => !affected.contains(node),
+            Report::Unsolved(constraint) => constraint_nodes(constraint)
+                .iter()
+                .all(|node| !affected.contains(node)),
+        });
+
+        self.deferred_bounds.retain(|deferred| {
+            !affected.contains(&deferred.bound.source) && !affected.contains(&deferred.bound.node)
+        });
+
+        self.deferred_instantiations.retain(|instantiation| {
+            !affected.contains(&instantiation.source)
+                && !affected.contains(&instantiation.node)
+                && !affected.contains(&instantiation.definition)
+        });
+
+        self.generalizations.retain(|node, generalization| {
+            !affected.contains(node)
+                && match generalization {
+                    Generalization::Generalized(variables, _) => {
+                        variables.iter().all(|node| !affected.contains(node))
+                    }
+                    Generalization::Pending(_) | Generalization::Monomorphic => true,
+                }
+        });
+    }
+}
+
+/// The nodes connected to each node, built once before marking nodes as
+/// affected.
+struct Connections<Db: crate::Db> {
+    referenced_by: BTreeMap<Db::Node, Vec<Db::Node>>, // nodes whose group's type refers to the node
+    copies: BTreeMap<Db::Node, Vec<Db::Node>>,        // copies of the node, or what it copies
+    dependents: BTreeMap<Db::Node, Vec<Db::Node>>,    // owners of constraints depending on the node
+}
+
+impl<Db: crate::Db> Connections<Db> {
+    fn referenced_by(&self, node: Db::Node) -> impl Iterator<Item = Db::Node> {
+        self.referenced_by.get(&node).into_iter().flatten().copied()
+    }
+
+    fn copies(&self, node: Db::Node) -> impl Iterator<Item = Db::Node> {
+        self.copies.get(&node).into_iter().flatten().copied()
+    }
+
+    fn dependents(&self, node: Db::Node) -> impl Iterator<Item = Db::Node> {
+        self.dependents.get(&node).into_iter().flatten().copied()
+    }
+}
+
+/// The nodes whose types the constraint may change.
+fn constraint_nodes<Db: crate::Db>(constraint: &Constraint<Db>) -> Vec<Db::Node> {
+    let mut nodes = vec![constraint.owner()];
+    constraint
+        .clone()
+        .traverse_nodes_mut(&mut |node| nodes.push(*node));
+    nodes
+}
+
+/// The nodes whose types the constraint depends on. Unlike
+/// [`constraint_nodes`], this includes definitions, which are only read when
+/// instantiated.
+fn constraint_dependencies<Db: crate::Db>(
+    constraint: &Constraint<Db>,
+) -> impl Iterator<Item = Db::Node> {
+    let definition = match constraint {
+        Constraint::Instantiation(instantiation)
+        | Constraint::Bound(crate::Bound(instantiation)) => Some(instantiation.definition),
+        Constraint::Ty(..) | Constraint::Generalize(..) => None,
+    };
+
+    constraint_nodes(constraint).into_iter().chain(definition)
+}
@@ -0,0 +1,112 @@
+//! Times the fact database on a generated program with a thousand statements.
+//! Run with `cargo bench -p wipple --bench db`.
+
+use std::{fmt::Write, hint::black_box, io, time::Instant};
+use wipple::{
+    Options,
+    db::{Db, Span},
+    syntax::{self, Parse, Range},
+    visit,
+};
+
+const STATEMENTS: usize = 1000;
+const ITERATIONS: u32 = 10;
+
+fn program() -> String {
+    let mut source = String::from(
+        "Number : type\nText : type\nUnit : type\nMaybe : value => type\n\
+         Show : value => trait (value -> Text)\n\
+         instance (Show Number) : _\ninstance (Show Text) : _\n\
+         instance (Show (Maybe value)) where (Show value) : _\n\
+         show :: value -> Text where (Show value)\n",
+    );
+
+    for index in 0..STATEMENTS / 4 {
+        writeln!(source, "n{index} :: Number").unwrap();
+        writeln!(source, "n{index} : {index}").unwrap();
+        writeln!(source, "t{index} : show n{index}").unwrap();
+        writeln!(source, "f{index} : x -> show (x n{index})").unwrap();
+    }
+
+    source
+}
+
+fn db(source: &str) -> Db {
+    let source_file = syntax::SourceFile::parse(source).unwrap();
+
+    let mut db = Db::new();
+
+    let ctx = visit::Ctx {
+        db: &mut db,
+        get_span_source: Box::new(|range: Range| {
+            let Range::Some(start, end) = range else {
+                panic!("node has no range");
+            };
+
+            (Span::root("bench"), source[start..end].to_string())
+        }),
+        show_definitions: true,
+    };
+
+    let info = visit::visit(&source_file, ctx);
+
+    let mut solver = visualizer::Solver::new(&mut db);
+    solver.insert_owned(info.constraints);
+    solver.finish();
+    drop(solver);
+
+    db
+}
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    f(); // warm up
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+
+    let average = start.elapsed() / ITERATIONS;
+    println!("{name:<24} {average:>12.3?}");
+}
+
+fn main() {
+    let source = program();
+    let db = db(&source);
+
+    println!("{} statements, {} nodes\n", STATEMENTS, db.nodes().count());
+
+    bench("nodes", || {
+        black_box(db.nodes().count());
+    });
+
+    bench("iter", || {
+        for node in db.nodes() {
+            black_box(db.iter(node).count());
+        }
+    });
+
+    bench("is_hidden", || {
+        for node in db.nodes() {
+            black_box(db.is_hidden(node));
+        }
+    });
+
+    bench("write", || {
+        db.write(&[], "  ", io::sink()).unwrap();
+    });
+
+    bench("feedback", || {
+        wipple::feedback::write_feedback(&db, io::sink()).unwrap();
+    });
+
+    bench("run", || {
+        let options = Options {
+            path: "bench",
+            source: &source,
+            ..Default::default()
+        };
+
+        wipple::run(options, io::sink(), None::<fn(_)>).unwrap();
+    });
+}
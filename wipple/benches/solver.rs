@@ -0,0 +1,104 @@
+//! Times the solver on generated programs of increasing size, where every
+//! bound tries several instances. Trying an instance should cost about the
+//! same however large the program is, so the time per statement should stay
+//! roughly flat. The solver's limits are raised so every size is solved
+//! completely. Run with `cargo bench -p wipple --bench solver`.
+
+use std::{fmt::Write, hint::black_box, time::Instant};
+use visualizer::{Solver, SolverLimits};
+use wipple::{
+    db::{Db, Span},
+    syntax::{self, Parse, Range},
+    visit,
+};
+
+const SIZES: [usize; 4] = [250, 500, 1000, 2000];
+const ITERATIONS: u32 = 5;
+
+fn program(statements: usize) -> String {
+    let mut source = String::from(
+        "Number : type\nText : type\nMaybe : value => type\n\
+         Show : value => trait (value -> Text)\n\
+         instance (Show Number) : _\ninstance (Show Text) : _\n\
+         instance (Show (Maybe value)) where (Show value) : _\n\
+         show :: value -> Text where (Show value)\n\
+         m :: Maybe (Maybe Number)\nu :: Maybe Text\n",
+    );
+
+    for index in 0..statements / 2 {
+        writeln!(source, "a{index} : show m").unwrap();
+        writeln!(source, "b{index} : show u").unwrap();
+    }
+
+    source
+}
+
+/// Solves the program, returning the number of nodes with facts and the
+/// number of nodes created. The nodes created while trying instances that
+/// didn't match are removed, so only the second number includes them.
+/// Panics if the solver stopped early, since the time wouldn't include the
+/// rest of the program.
+fn solve(source: &str) -> (usize, u32) {
+    let source_file = syntax::SourceFile::parse(source).unwrap();
+
+    let mut db = Db::new();
+
+    let ctx = visit::Ctx {
+        db: &mut db,
+        get_span_source: Box::new(|range: Range| {
+            let Range::Some(start, end) = range else {
+                panic!("node has no range");
+            };
+
+            (Span::root("bench"), source[start..end].to_string())
+        }),
+        show_definitions: true,
+    };
+
+    let info = visit::visit(&source_file, ctx);
+
+    let default_limits = SolverLimits::default();
+    let mut solver = Solver::new(&mut db).with_limits(SolverLimits {
+        max_depth: default_limits.max_depth * 2,
+        max_steps: default_limits.max_steps * 10,
+    });
+
+    solver.insert_owned(info.constraints);
+    solver.finish();
+    drop(solver);
+
+    assert_eq!(db.count("solverStepLimit"), 0, "solver hit the step limit");
+    assert_eq!(
+        db.count("unsolvedConstraint"),
+        0,
+        "solver left constraints unsolved"
+    );
+
+    let created = db.node().0;
+    (db.nodes().count(), created)
+}
+
+fn main() {
+    println!(
+        "{:<12} {:>12} {:>16} {:>12} {:>12}",
+        "statements", "average", "per statement", "nodes", "created"
+    );
+
+    for statements in SIZES {
+        let source = program(statements);
+
+        let (nodes, created) = solve(&source); // warm up
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(solve(&source));
+        }
+
+        let average = start.elapsed() / ITERATIONS;
+        let per_statement = average / statements as u32;
+
+        println!(
+            "{statements:<12} {average:>12.3?} {per_statement:>16.3?} {nodes:>12} {created:>12}"
+        );
+    }
+}
@@ -1,7 +1,8 @@
-use crate::Db;
+use crate::{Db, NodeId};
 use dyn_eq::DynEq;
+use serde::{Deserialize, Serialize};
 use std::{any::Any, fmt::Debug, rc::Rc};
-use visualizer::{Substitutions, Ty};
+use visualizer::{Bound, Constraint, Instantiation, Substitutions, Ty};
 
 #[derive(Debug, Clone)]
 pub struct Fact {
@@ -56,7 +57,13 @@ impl FactValue for () {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+impl FactValue for usize {
+    fn display(&self, _db: &Db) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Source(pub String);
 
 impl FactValue for Source {
@@ -118,6 +125,48 @@ fn display_ty(ty: &Ty<Db>, db: &Db, root: bool) -> String {
     }
 }
 
+impl FactValue for Constraint<Db> {
+    fn display(&self, db: &Db) -> Option<String> {
+        Some(match self {
+            Constraint::Ty(_, ty) => display_ty(ty, db, true),
+            Constraint::Instantiation(Instantiation {
+                definition: node, ..
+            })
+            | Constraint::Bound(Bound(Instantiation {
+                definition: node, ..
+            }))
+            | Constraint::Generalize(node, _) => db
+                .get::<Source>(*node, "source")
+                .map_or_else(|| String::from("_"), |source| source.0.clone()),
+        })
+    }
+
+    fn is_code(&self) -> bool {
+        true
+    }
+}
+
+/// The type an instance gives one of its trait's output parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraitOutput {
+    pub parameter: NodeId,
+    pub ty: Ty<Db>,
+}
+
+impl FactValue for TraitOutput {
+    fn display(&self, db: &Db) -> Option<String> {
+        let parameter = db
+            .get::<Source>(self.parameter, "source")
+            .map_or_else(|| String::from("_"), |source| source.0.clone());
+
+        Some(format!("{parameter} = {}", display_ty(&self.ty, db, true)))
+    }
+
+    fn is_code(&self) -> bool {
+        true
+    }
+}
+
 impl FactValue for Substitutions<Db> {
     fn display(&self, _db: &Db) -> Option<String> {
         Some(String::from("Substitutions(..)"))
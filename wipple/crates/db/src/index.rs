@@ -0,0 +1,24 @@
+use crate::{FactValue, NodeId, Source, Span};
+
+/// A fact value that can be looked up directly, rather than by checking every
+/// fact with the same name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum ValueKey {
+    Node(NodeId),
+    Span(Span),
+    Source(Source),
+}
+
+impl ValueKey {
+    pub(crate) fn new(value: &dyn FactValue) -> Option<Self> {
+        if let Some(&node) = value.downcast_ref::<NodeId>() {
+            Some(ValueKey::Node(node))
+        } else if let Some(span) = value.downcast_ref::<Span>() {
+            Some(ValueKey::Span(span.clone()))
+        } else {
+            value
+                .downcast_ref::<Source>()
+                .map(|source| ValueKey::Source(source.clone()))
+        }
+    }
+}
@@ -1,7 +1,10 @@
 mod constraints;
 mod fact;
+mod index;
 mod node;
 mod query;
+mod serialize;
+mod souffle;
 mod span;
 mod write;
 
@@ -9,20 +12,58 @@ pub use constraints::*;
 pub use fact::*;
 pub use node::*;
 pub use query::*;
+pub use serialize::*;
+pub use souffle::*;
 pub use span::*;
 pub use write::*;
 
+use crate::index::ValueKey;
 use itertools::Itertools;
 use std::{
-    collections::{BTreeMap, BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    mem,
     rc::Rc,
 };
 use visualizer::{Constraint, Instantiation, Substitutions, Ty};
 
+/// The facts written by [`visualizer::Solver::finish`].
+const RESULTS: &[&str] = &[
+    "resolvedTrait",
+    "resolvedInstance",
+    "usedDefaultInstance",
+    "customError",
+    "customErrorInstance",
+    "unresolvedTrait",
+    "ambiguousTrait",
+    "ambiguousInstance",
+    "traitOutput",
+    "generalized",
+    "instanceRecursionLimit",
+    "solverStepLimit",
+    "unsolvedConstraint",
+    "type",
+    "incompleteType",
+];
+
 #[derive(Debug, Clone, Default)]
 pub struct Db {
     next_id: u32,
     facts: HashMap<Rc<str>, BTreeMap<NodeId, Vec<Fact>>>,
+
+    // The same facts indexed by node, so looking up everything about a node
+    // doesn't need to go through every fact name
+    node_facts: Vec<Vec<Fact>>,
+
+    // The nodes with each fact, by value, for the values in `ValueKey`
+    values: HashMap<Rc<str>, HashMap<ValueKey, BTreeSet<NodeId>>>,
+
+    // The names of facts added by `derive`
+    derived: HashSet<Rc<str>>,
+
+    // The inferred parameters of each trait. Copies of a trait's parameters
+    // keep `parameterInTraitDefinition`, so finding them again would go
+    // through every instantiation of the trait so far
+    trait_outputs: HashMap<NodeId, Vec<NodeId>>,
 }
 
 impl Db {
@@ -37,6 +78,22 @@ impl Db {
     }
 
     pub fn fact(&mut self, node: NodeId, fact: Fact) {
+        let index = node.0 as usize;
+        if index >= self.node_facts.len() {
+            self.node_facts.resize_with(index + 1, Vec::new);
+        }
+
+        self.node_facts[index].push(fact.clone());
+
+        if let Some(key) = ValueKey::new(fact.value()) {
+            self.values
+                .entry(fact.name.clone())
+                .or_default()
+                .entry(key)
+                .or_default()
+                .insert(node);
+        }
+
         self.facts
             .entry(fact.name.clone())
             .or_default()
@@ -45,13 +102,13 @@ impl Db {
             .push(fact);
     }
 
+    /// The nodes with at least one fact, in order.
     pub fn nodes(&self) -> impl Iterator<Item = NodeId> {
-        self.facts
-            .values()
-            .flat_map(|facts| facts.keys())
-            .cloned()
-            .collect::<BTreeSet<_>>() // deduplicate
-            .into_iter()
+        self.node_facts
+            .iter()
+            .enumerate()
+            .filter(|(_, facts)| !facts.is_empty())
+            .map(|(index, _)| NodeId(index as u32))
     }
 
     pub fn all(&self, name: &str) -> impl Iterator<Item = (NodeId, &Fact)> {
@@ -62,11 +119,50 @@ impl Db {
             .flat_map(|(&node, facts)| facts.iter().map(move |fact| (node, fact)))
     }
 
+    /// The facts on `node`, in the order they were added.
     pub fn iter(&self, node: NodeId) -> impl Iterator<Item = &Fact> {
-        self.facts
-            .values()
-            .filter_map(move |facts| facts.get(&node))
-            .flatten()
+        self.node_facts.get(node.0 as usize).into_iter().flatten()
+    }
+
+    /// The number of nodes with a `name` fact.
+    pub fn count(&self, name: &str) -> usize {
+        self.facts.get(name).map_or(0, BTreeMap::len)
+    }
+
+    /// The `name` facts equal to `value`, or `None` if values of this type
+    /// aren't indexed.
+    pub fn find<'a>(
+        &'a self,
+        name: &'a str,
+        value: &'a dyn FactValue,
+    ) -> Option<impl Iterator<Item = (NodeId, &'a Fact)>> {
+        let key = ValueKey::new(value)?;
+
+        let nodes = self
+            .values
+            .get(name)
+            .and_then(|values| values.get(&key))
+            .into_iter()
+            .flatten();
+
+        Some(nodes.flat_map(move |&node| {
+            self.iter_by(node, name)
+                .filter(move |fact| fact.value() == value)
+                .map(move |fact| (node, fact))
+        }))
+    }
+
+    /// Remove every `name` fact.
+    pub fn remove_all(&mut self, name: &str) {
+        let Some(facts) = self.facts.remove(name) else {
+            return;
+        };
+
+        for node in facts.into_keys() {
+            self.node_facts[node.0 as usize].retain(|fact| fact.name() != name);
+        }
+
+        self.values.remove(name);
     }
 
     pub fn iter_by(&self, node: NodeId, name: &str) -> impl Iterator<Item = &Fact> {
@@ -114,7 +210,8 @@ impl Db {
                 }
             });
 
-        for fact in node_facts {
+        // Whether the copy is hidden is determined by `hide`, not the original
+        for fact in node_facts.into_iter().filter(|fact| !fact.is_hidden()) {
             self.fact(copy, fact);
         }
 
@@ -125,10 +222,36 @@ impl Db {
             .flat_map(|constraints| constraints.resolve_for(copy))
             .collect::<Vec<_>>()
         {
+            // Copy nodes before substituting parameters, since substitutions
+            // refer to nodes outside the tree
+            constraint.traverse_nodes_mut(&mut |node| {
+                if let Some(copy) = copies.get(node) {
+                    *node = *copy;
+                } else {
+                    let copy = self.node();
+
+                    self.clone_node_tree_inner(
+                        *node,
+                        copy,
+                        substitutions,
+                        hide,
+                        copies,
+                        constraints,
+                    );
+
+                    *node = copy;
+                }
+            });
+
             constraint.traverse_tys_mut(&mut |ty| {
                 if let Ty::Parameter(parameter) = *ty {
                     if let Some(substitution) = substitutions.0.get(&parameter).cloned() {
                         *ty = substitution;
+                    } else if let Some(&copy) = copies.get(&parameter) {
+                        // The parameter was already copied while cloning the
+                        // tree, so reuse that copy rather than making another
+                        substitutions.0.insert(parameter, Ty::Of(copy));
+                        *ty = Ty::Of(copy);
                     } else {
                         let copy = self.node();
                         substitutions.0.insert(parameter, Ty::Of(copy));
@@ -138,7 +261,7 @@ impl Db {
                             parameter,
                             copy,
                             substitutions,
-                            false,
+                            hide,
                             copies,
                             constraints,
                         );
@@ -146,25 +269,6 @@ impl Db {
                 }
             });
 
-            constraint.traverse_nodes_mut(&mut |node| {
-                if let Some(copy) = copies.get(node) {
-                    *node = *copy;
-                } else {
-                    let copy = self.node();
-
-                    self.clone_node_tree_inner(
-                        *node,
-                        copy,
-                        substitutions,
-                        false,
-                        copies,
-                        constraints,
-                    );
-
-                    *node = copy;
-                }
-            });
-
             constraints.push(constraint.clone());
         }
     }
@@ -172,10 +276,19 @@ impl Db {
     pub fn is_hidden(&self, node: NodeId) -> bool {
         self.iter(node).any(Fact::is_hidden)
     }
+
+    /// The only bounds resolved on an instance definition itself are its
+    /// trait's bounds, which are reported as missing super instances by
+    /// [`visualizer::Solver::check_super_instances`] instead.
+    fn is_instance_definition(&self, node: NodeId) -> bool {
+        self.get::<NodeId>(node, "traitInInstanceDefinition")
+            .is_some()
+    }
 }
 
 impl visualizer::Db for Db {
     type Node = NodeId;
+    type Checkpoint = NodeId;
 
     fn typed_nodes(&self) -> impl Iterator<Item = Self::Node> {
         self.nodes()
@@ -203,6 +316,56 @@ impl visualizer::Db for Db {
         (copy, constraints)
     }
 
+    fn fresh_node(&mut self) -> Self::Node {
+        let node = self.node();
+        self.fact(node, Fact::hidden());
+        self.fact(node, Fact::new("instantiated", ()));
+        node
+    }
+
+    fn checkpoint(&mut self) -> Self::Checkpoint {
+        NodeId(self.next_id)
+    }
+
+    fn remove_nodes_since(&mut self, checkpoint: Self::Checkpoint) {
+        for node in self.nodes_since(&checkpoint) {
+            self.remove_node(node);
+        }
+    }
+
+    fn nodes_since(&self, checkpoint: &Self::Checkpoint) -> Vec<Self::Node> {
+        (checkpoint.0..self.next_id).map(NodeId).collect()
+    }
+
+    fn remove_node(&mut self, node: Self::Node) {
+        // IDs aren't reused, since the solver may still have removed nodes in
+        // its bookkeeping
+        let Some(node_facts) = self.node_facts.get_mut(node.0 as usize) else {
+            return;
+        };
+
+        for fact in mem::take(node_facts) {
+            if let Some(key) = ValueKey::new(fact.value())
+                && let Some(nodes) = self
+                    .values
+                    .get_mut(&fact.name)
+                    .and_then(|values| values.get_mut(&key))
+            {
+                nodes.remove(&node);
+            }
+
+            if let Some(facts) = self.facts.get_mut(&fact.name) {
+                facts.remove(&node);
+            }
+        }
+    }
+
+    fn remove_results(&mut self) {
+        for name in RESULTS {
+            self.remove_all(name);
+        }
+    }
+
     fn get_trait_instances(
         &mut self,
         source: Self::Node,
@@ -211,16 +374,13 @@ impl visualizer::Db for Db {
     ) -> Vec<(Self::Node, Instantiation<Self>)> {
         self.iter_of(trait_id, "instance")
             .map(|&instance| {
-                let substitutions = self
-                    .get::<Substitutions<Self>>(instance, "substitutions")
-                    .unwrap()
-                    .clone();
-
+                // Instantiating the instance also instantiates the trait with
+                // the instance's parameters, along with its bounds
                 let instantiation = Instantiation {
                     source,
                     node,
-                    definition: trait_id,
-                    substitutions,
+                    definition: instance,
+                    substitutions: Substitutions::replace_all(),
                 };
 
                 (instance, instantiation)
@@ -228,15 +388,143 @@ impl visualizer::Db for Db {
             .collect()
     }
 
+    fn get_instance_trait(&mut self, instance: Self::Node) -> Option<Instantiation<Self>> {
+        let trait_id = *self.get::<NodeId>(instance, "traitInInstanceDefinition")?;
+        let substitutions = self.get::<Substitutions<Self>>(instance, "substitutions")?;
+
+        Some(Instantiation {
+            source: instance,
+            node: instance,
+            definition: trait_id,
+            substitutions: substitutions.clone(),
+        })
+    }
+
+    fn get_trait_outputs(&mut self, trait_id: Self::Node) -> Vec<Self::Node> {
+        if let Some(outputs) = self.trait_outputs.get(&trait_id) {
+            return outputs.clone();
+        }
+
+        let outputs = self
+            .find("parameterInTraitDefinition", &trait_id)
+            .into_iter()
+            .flatten()
+            .map(|(parameter, _)| parameter)
+            .filter(|&parameter| {
+                self.get::<()>(parameter, "inferred").is_some()
+                    && self.get::<()>(parameter, "instantiated").is_none()
+            })
+            .collect::<Vec<_>>();
+
+        self.trait_outputs.insert(trait_id, outputs.clone());
+
+        outputs
+    }
+
+    fn is_default_instance(&mut self, instance: Self::Node) -> bool {
+        self.get::<()>(instance, "defaultInstance").is_some()
+    }
+
+    fn is_error_instance(&mut self, instance: Self::Node) -> bool {
+        self.get::<()>(instance, "errorInstance").is_some()
+    }
+
     fn flag_resolved(&mut self, node: Self::Node, instance: Self::Node, ty: NodeId) {
         self.fact(node, Fact::new("resolvedTrait", ty));
         self.fact(ty, Fact::new("resolvedInstance", instance));
     }
 
+    fn flag_default_instance(&mut self, node: Self::Node, instance: Self::Node) {
+        self.fact(node, Fact::new("usedDefaultInstance", instance));
+    }
+
+    fn flag_custom_error(
+        &mut self,
+        node: Self::Node,
+        instance: Self::Node,
+        substitutions: Substitutions<Self>,
+    ) {
+        // The instance's comments describe the error, and refer to the trait's
+        // parameters the same way feedback does, eg. [`value`]
+        let mut message = match self.get::<Source>(instance, "comments") {
+            Some(Source(comments)) => comments.clone(),
+            None => {
+                let source = self.get::<Source>(instance, "source").unwrap();
+                format!("`{}` is marked as an error.", source.0.trim())
+            }
+        };
+
+        for (parameter, ty) in &substitutions.0 {
+            let Some(Source(name)) = self.get::<Source>(*parameter, "source") else {
+                continue;
+            };
+
+            let ty = ty.display(self).unwrap();
+
+            message = message
+                .replace(&format!("[`{name}`]"), &format!("`{ty}`"))
+                .replace(&format!("[{name}]"), &ty);
+        }
+
+        self.fact(node, Fact::new("customError", Source(message)));
+        self.fact(node, Fact::new("customErrorInstance", instance));
+    }
+
     fn flag_unresolved(&mut self, node: Self::Node, ty: NodeId) {
+        if self.is_instance_definition(node) {
+            return;
+        }
+
         self.fact(node, Fact::new("unresolvedTrait", ty));
     }
 
+    fn flag_ambiguous(&mut self, node: Self::Node, candidates: Vec<Self::Node>, ty: NodeId) {
+        if self.is_instance_definition(node) {
+            return;
+        }
+
+        self.fact(node, Fact::new("ambiguousTrait", ty));
+
+        for instance in candidates {
+            self.fact(ty, Fact::new("ambiguousInstance", instance));
+        }
+    }
+
+    fn flag_trait_output(&mut self, node: Self::Node, parameter: Self::Node, ty: Ty<Self>) {
+        self.fact(
+            node,
+            Fact::new("traitOutput", TraitOutput { parameter, ty }),
+        );
+    }
+
+    fn flag_overlapping_instance(&mut self, instance: Self::Node, other: Self::Node) {
+        self.fact(instance, Fact::new("overlappingInstance", other));
+    }
+
+    fn flag_missing_super_instance(&mut self, instance: Self::Node, super_trait: Self::Node) {
+        self.fact(instance, Fact::new("missingSuperInstance", super_trait));
+    }
+
+    fn flag_generalized(&mut self, node: Self::Node) {
+        self.fact(node, Fact::new("generalized", ()));
+    }
+
+    fn flag_recursion_limit(&mut self, node: Self::Node, ty: NodeId) {
+        if self.is_instance_definition(node) {
+            return;
+        }
+
+        self.fact(node, Fact::new("instanceRecursionLimit", ty));
+    }
+
+    fn flag_step_limit(&mut self, node: Self::Node) {
+        self.fact(node, Fact::new("solverStepLimit", ()));
+    }
+
+    fn flag_unsolved(&mut self, node: Self::Node, constraint: Constraint<Self>) {
+        self.fact(node, Fact::new("unsolvedConstraint", constraint));
+    }
+
     fn flag_type(&mut self, node: Self::Node, ty: Ty<Self>) {
         self.fact(node, Fact::new("type", ty));
     }
@@ -0,0 +1,73 @@
+use crate::{Db, FactValue, NodeId, Span, query::QueryValues};
+use std::rc::Rc;
+
+/// One side of a comparison.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Variable(String),
+    Number(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+
+    /// The left span (or node's span) is inside the right one.
+    Inside,
+}
+
+impl Operand {
+    pub(crate) fn variable(&self) -> Option<&str> {
+        match self {
+            Operand::Variable(variable) => Some(variable),
+            Operand::Number(_) => None,
+        }
+    }
+
+    pub(crate) fn value(&self, values: &QueryValues) -> Option<Rc<dyn FactValue>> {
+        match self {
+            Operand::Variable(variable) => values.get(variable).cloned(),
+            Operand::Number(number) => Some(Rc::new(*number)),
+        }
+    }
+}
+
+impl Comparison {
+    pub(crate) fn test(self, db: &Db, left: &dyn FactValue, right: &dyn FactValue) -> bool {
+        let numbers = || {
+            Some((
+                *left.downcast_ref::<usize>()?,
+                *right.downcast_ref::<usize>()?,
+            ))
+        };
+
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => numbers().is_some_and(|(left, right)| left < right),
+            Comparison::LessOrEqual => numbers().is_some_and(|(left, right)| left <= right),
+            Comparison::Greater => numbers().is_some_and(|(left, right)| left > right),
+            Comparison::GreaterOrEqual => numbers().is_some_and(|(left, right)| left >= right),
+            Comparison::Inside => match (span_of(db, left), span_of(db, right)) {
+                (Some(left), Some(right)) => {
+                    left.path == right.path
+                        && right.range.start <= left.range.start
+                        && left.range.end <= right.range.end
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+fn span_of<'a>(db: &'a Db, value: &'a dyn FactValue) -> Option<&'a Span> {
+    match value.downcast_ref::<NodeId>() {
+        Some(&node) => db.get::<Span>(node, "span"),
+        None => value.downcast_ref::<Span>(),
+    }
+}
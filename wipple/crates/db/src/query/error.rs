@@ -0,0 +1,56 @@
+use std::fmt;
+
+/// A problem with a query, rule or feedback file, along with where it is.
+/// Lines and columns start at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub path: Option<String>,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        ParseError {
+            path: None,
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Make the position relative to a larger text, where the text being
+    /// parsed starts at `line` and `column`.
+    pub(crate) fn at(mut self, line: usize, column: usize) -> Self {
+        if self.line == 1 {
+            self.column += column - 1;
+        }
+
+        self.line += line - 1;
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{path}:")?;
+        }
+
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The column where `part` starts in `s`, which must contain it.
+pub(crate) fn column_of(s: &str, part: &str) -> usize {
+    let offset = part.as_ptr() as usize - s.as_ptr() as usize;
+    s[..offset].chars().count() + 1
+}
@@ -1,6 +1,6 @@
 use crate::{
     Db, FactValue, Span,
-    query::{Query, Term, query},
+    query::{ParseError, Query, Schema, Term, ValueType, error::column_of, query},
 };
 use colored::Colorize;
 use regex::Regex;
@@ -9,18 +9,21 @@ use std::{ops::Range, str::FromStr, sync::LazyLock};
 pub trait MarkdownQueryExt<'a>: Sized {
     fn markdown(
         markdown: &str,
+        schema: &Schema,
         matcher: impl Fn(&Db, &dyn FactValue, &str) -> bool + Send + Sync + 'a,
-    ) -> Option<Self>;
+    ) -> Result<Self, ParseError>;
 }
 
 impl<'a> MarkdownQueryExt<'a> for Query<'a, Vec<(Span, String)>> {
     fn markdown(
         markdown: &str,
+        schema: &Schema,
         matcher: impl Fn(&Db, &dyn FactValue, &str) -> bool + Send + Sync + 'a,
-    ) -> Option<Self> {
-        let file = File::from_str(markdown).ok()?;
+    ) -> Result<Self, ParseError> {
+        let file = File::from_str(markdown)?;
+        file.check(markdown, schema)?;
 
-        Some(Query::new(move |db, initial| {
+        Ok(Query::new(move |db, initial| {
             let mut result = Vec::new();
             for values in query(&file.terms, initial, db, &matcher) {
                 let mut body = file.body.clone();
@@ -59,6 +62,10 @@ struct File {
     terms: Vec<Term>,
     body: String,
     links: Vec<Link>,
+
+    // Where the terms and body start in the file
+    line: usize,
+    body_offset: usize,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -68,6 +75,41 @@ struct Link {
     code: bool,
 }
 
+impl File {
+    fn check(&self, markdown: &str, schema: &Schema) -> Result<(), ParseError> {
+        let types = schema
+            .check(&self.terms, &[])
+            .map_err(|(index, message)| ParseError::new(self.line + index, 1, message))?;
+
+        if types.get("span") != Some(&Some(ValueType::of::<Span>())) {
+            return Err(ParseError::new(
+                self.line,
+                1,
+                "feedback needs a `span` variable bound to a span",
+            ));
+        }
+
+        for link in &self.links {
+            // Text like `[name]` inside code is left as is
+            let in_code = self.body[..link.range.start].matches('`').count() % 2 == 1;
+
+            if !in_code && !types.contains_key(&link.name) {
+                let offset = self.body_offset + link.range.start;
+                let line = markdown[..offset].matches('\n').count() + 1;
+                let line_start = markdown[..offset].rfind('\n').map_or(0, |index| index + 1);
+
+                return Err(ParseError::new(
+                    line,
+                    column_of(&markdown[line_start..], &markdown[offset..]),
+                    format!("`{}` isn't bound by any term", link.name),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 static FILE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r#"^\s*---\n(?<frontmatter>(?s).*)\n---\n(?<body>(?s).*)$"#).unwrap()
 });
@@ -76,20 +118,29 @@ static LINK_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r#"\[(?<code>`)?(?<name>[A-Za-z_]+)`?\]"#).unwrap());
 
 impl FromStr for File {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let file = FILE_REGEX
             .captures(s)
-            .ok_or_else(|| anyhow::format_err!("missing frontmatter"))?;
+            .ok_or_else(|| ParseError::new(1, 1, "missing frontmatter"))?;
 
-        let frontmatter = file.name("frontmatter").unwrap().as_str();
-        let body = file.name("body").unwrap().as_str().trim().to_string();
+        let frontmatter = file.name("frontmatter").unwrap();
+        let line = s[..frontmatter.start()].matches('\n').count() + 1;
+
+        let body = file.name("body").unwrap();
+        let body_offset = body.end() - body.as_str().trim_start().len();
+        let body = body.as_str().trim().to_string();
 
         let terms = frontmatter
+            .as_str()
             .lines()
-            .map(|line| line.parse())
-            .collect::<anyhow::Result<Vec<Term>>>()?;
+            .enumerate()
+            .map(|(index, term)| {
+                term.parse()
+                    .map_err(|error: ParseError| error.at(line + index, 1))
+            })
+            .collect::<Result<Vec<Term>, _>>()?;
 
         let links = LINK_REGEX
             .captures_iter(&body)
@@ -100,6 +151,12 @@ impl FromStr for File {
             })
             .collect::<Vec<_>>();
 
-        Ok(File { terms, body, links })
+        Ok(File {
+            terms,
+            body,
+            links,
+            line,
+            body_offset,
+        })
     }
 }
@@ -1,10 +1,22 @@
+mod compare;
+mod error;
 mod markdown;
+mod plan;
+mod rules;
+mod schema;
 mod yaml;
 
+pub use compare::*;
+pub use error::*;
 pub use markdown::*;
+pub use rules::*;
+pub use schema::*;
 pub use yaml::*;
 
-use crate::{Db, FactValue, NodeId};
+use crate::{
+    Db, FactValue, NodeId, Span, Spans,
+    query::{error::column_of, plan::Plan},
+};
 use regex::Regex;
 use std::{borrow::Cow, collections::HashMap, rc::Rc, str::FromStr, sync::LazyLock};
 
@@ -23,7 +35,56 @@ impl<'a, T> Query<'a, T> {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Term {
+pub enum Term {
+    /// `node.fact(arg)`, or `!node.fact` to check that there's no such fact.
+    Fact(FactTerm),
+
+    /// `a | b`, matching any of the terms.
+    Or(Vec<Term>),
+
+    /// `a != b`, `a < 3`, `a in b`, checked once the variables are bound.
+    Compare(Operand, Comparison, Operand),
+
+    /// `n = count(x: terms)` or `n = spans(x: terms)`, combining the
+    /// different values `x` has in the results of `terms`. Variables shared
+    /// with the rest of the query are bound before aggregating.
+    Aggregate {
+        result: String,
+        aggregate: Aggregate,
+        variable: String,
+        terms: Vec<Term>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    /// `count`, the number of values.
+    Count,
+
+    /// `spans`, the values listed together as [`Spans`], sorted by position.
+    Spans,
+}
+
+impl Aggregate {
+    fn value(self, values: &[&Rc<dyn FactValue>]) -> Rc<dyn FactValue> {
+        match self {
+            Aggregate::Count => Rc::new(values.len()),
+            Aggregate::Spans => {
+                let mut spans = values
+                    .iter()
+                    .filter_map(|value| value.downcast_ref::<Span>().cloned())
+                    .collect::<Vec<_>>();
+
+                spans.sort_by(|a, b| (&a.path, a.range.start).cmp(&(&b.path, b.range.start)));
+
+                Rc::new(Spans(spans))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FactTerm {
     pub not: bool,
     pub node: String,
     pub fact: String,
@@ -42,92 +103,313 @@ pub fn query(
     db: &Db,
     matcher: impl Fn(&Db, &dyn FactValue, &str) -> bool,
 ) -> impl Iterator<Item = QueryValues> {
+    let plan = Plan::new(terms, initial.keys().map(String::as_str), db);
+
     let mut result = Vec::new();
-    query_inner(db, &matcher, terms, &initial, &mut result);
+    query_inner(db, &matcher, &plan, &plan.steps, &initial, &mut result);
+    result.into_iter()
+}
+
+/// Like [`query`], but hidden nodes are matched like any other node.
+pub(crate) fn query_hidden(
+    terms: &[Term],
+    initial: QueryValues,
+    db: &Db,
+    matcher: impl Fn(&Db, &dyn FactValue, &str) -> bool,
+) -> impl Iterator<Item = QueryValues> {
+    let mut plan = Plan::new(terms, initial.keys().map(String::as_str), db);
+    plan.visible.clear();
+
+    let mut result = Vec::new();
+    query_inner(db, &matcher, &plan, &plan.steps, &initial, &mut result);
     result.into_iter()
 }
 
 fn query_inner(
     db: &Db,
     matcher: &dyn Fn(&Db, &dyn FactValue, &str) -> bool,
-    terms: &[Term],
+    plan: &Plan<'_>,
+    terms: &[&Term],
     values: &QueryValues,
     result: &mut Vec<QueryValues>,
 ) {
-    match terms.split_first() {
-        Some((next, terms)) => {
-            let facts: Vec<_> = match values
-                .get(&next.node)
-                .and_then(|node| node.downcast_ref::<NodeId>().copied())
-                .filter(|&node| !db.is_hidden(node))
-            {
-                Some(node) => db
-                    .iter_by(node, &next.fact)
-                    .map(|fact| (Cow::Borrowed(values), fact))
-                    .collect(),
-                None => db
-                    .all(&next.fact)
-                    .filter(|&(node, _)| !db.is_hidden(node))
-                    .map(|(node, fact)| {
-                        let mut values = values.clone();
-                        values.insert(next.node.clone(), Rc::new(node));
-                        (Cow::Owned(values), fact)
-                    })
-                    .collect(),
+    let Some((next, terms)) = terms.split_first() else {
+        result.push(values.clone());
+        return;
+    };
+
+    match next {
+        Term::Fact(next) => query_fact(db, matcher, plan, next, terms, values, result),
+        Term::Or(alternatives) => {
+            for alternative in alternatives {
+                let terms = [alternative].into_iter().chain(terms.iter().copied());
+                query_inner(
+                    db,
+                    matcher,
+                    plan,
+                    &terms.collect::<Vec<_>>(),
+                    values,
+                    result,
+                );
+            }
+        }
+        Term::Compare(left, comparison, right) => {
+            let (Some(left), Some(right)) = (left.value(values), right.value(values)) else {
+                return;
             };
 
-            if next.not {
-                if facts.is_empty() {
-                    query_inner(db, matcher, terms, values, result);
-                } else {
-                    return;
+            if comparison.test(db, left.as_ref(), right.as_ref()) {
+                query_inner(db, matcher, plan, terms, values, result);
+            }
+        }
+        Term::Aggregate {
+            result: name,
+            aggregate,
+            variable,
+            terms: counted,
+        } => {
+            let counted_plan = Plan::new(counted, values.keys().map(String::as_str), db);
+
+            let mut found = Vec::new();
+            query_inner(
+                db,
+                matcher,
+                &counted_plan,
+                &counted_plan.steps,
+                values,
+                &mut found,
+            );
+
+            let mut distinct = Vec::<&Rc<dyn FactValue>>::new();
+            for value in found.iter().filter_map(|values| values.get(variable)) {
+                if !distinct
+                    .iter()
+                    .any(|other| other.as_ref() == value.as_ref())
+                {
+                    distinct.push(value);
                 }
             }
 
-            for (mut values, fact) in facts {
-                if let Some(arg) = &next.arg {
-                    match arg {
-                        Arg::Variable(variable) => match values.get(variable) {
-                            Some(other) => {
-                                if other.as_ref() != fact.value() {
-                                    continue;
-                                }
-                            }
-                            None => {
-                                values.to_mut().insert(variable.clone(), fact.clone_value());
-                            }
-                        },
-                        Arg::Value(pattern) => {
-                            if !matcher(db, fact.value(), pattern) {
-                                continue;
-                            }
+            let aggregated = aggregate.value(&distinct);
+
+            match values.get(name) {
+                Some(other) if other.as_ref() != aggregated.as_ref() => {}
+                Some(_) => query_inner(db, matcher, plan, terms, values, result),
+                None => {
+                    let mut values = values.clone();
+                    values.insert(name.clone(), aggregated);
+                    query_inner(db, matcher, plan, terms, &values, result);
+                }
+            }
+        }
+    }
+}
+
+fn query_fact(
+    db: &Db,
+    matcher: &dyn Fn(&Db, &dyn FactValue, &str) -> bool,
+    plan: &Plan<'_>,
+    next: &FactTerm,
+    terms: &[&Term],
+    values: &QueryValues,
+    result: &mut Vec<QueryValues>,
+) {
+    let is_visible =
+        |node: NodeId| !plan.visible.contains(next.node.as_str()) || !db.is_hidden(node);
+
+    let facts: Vec<_> = match values
+        .get(&next.node)
+        .and_then(|node| node.downcast_ref::<NodeId>().copied())
+    {
+        Some(node) if !is_visible(node) => Vec::new(),
+        Some(node) => db
+            .iter_by(node, &next.fact)
+            .map(|fact| (Cow::Borrowed(values), fact))
+            .collect(),
+        None => {
+            // Look up the node by the fact's value if it's already known
+            let found = match &next.arg {
+                Some(Arg::Variable(variable)) if !next.not => values
+                    .get(variable)
+                    .and_then(|value| db.find(&next.fact, value.as_ref())),
+                _ => None,
+            };
+
+            let candidates: Box<dyn Iterator<Item = _>> = match found {
+                Some(found) => Box::new(found),
+                None => Box::new(db.all(&next.fact)),
+            };
+
+            candidates
+                .filter(|&(node, _)| is_visible(node))
+                .map(|(node, fact)| {
+                    let mut values = values.clone();
+                    values.insert(next.node.clone(), Rc::new(node));
+                    (Cow::Owned(values), fact)
+                })
+                .collect()
+        }
+    };
+
+    if next.not {
+        if facts.is_empty() {
+            query_inner(db, matcher, plan, terms, values, result);
+        }
+
+        return;
+    }
+
+    for (mut values, fact) in facts {
+        if let Some(arg) = &next.arg {
+            match arg {
+                Arg::Variable(variable) => match values.get(variable) {
+                    Some(other) => {
+                        if other.as_ref() != fact.value() {
+                            continue;
                         }
                     }
+                    None => {
+                        // Facts on hidden nodes are never matched, so don't
+                        // bind a hidden node to a variable that needs facts
+                        if plan.visible.contains(variable.as_str())
+                            && fact
+                                .value()
+                                .downcast_ref::<NodeId>()
+                                .is_some_and(|&node| db.is_hidden(node))
+                        {
+                            continue;
+                        }
+
+                        values.to_mut().insert(variable.clone(), fact.clone_value());
+                    }
+                },
+                Arg::Value(pattern) => {
+                    if !matcher(db, fact.value(), pattern) {
+                        continue;
+                    }
                 }
+            }
+        }
+
+        query_inner(db, matcher, plan, terms, &values, result);
+    }
+}
 
-                query_inner(db, matcher, terms, &values, result);
+/// Split `s` at each `separator` outside of parentheses and backticks.
+pub(crate) fn split_terms(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut code = false;
+    let mut start = 0;
+    for (index, c) in s.char_indices() {
+        match c {
+            '`' => code = !code,
+            '(' if !code => depth += 1,
+            ')' if !code => depth = depth.saturating_sub(1),
+            c if c == separator && !code && depth == 0 => {
+                parts.push(&s[start..index]);
+                start = index + c.len_utf8();
             }
+            _ => {}
         }
-        None => result.push(values.clone()),
     }
+
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Parse each part of `s`, keeping track of where the part is in `s`.
+pub(crate) fn parse_terms(s: &str, parts: Vec<&str>) -> Result<Vec<Term>, ParseError> {
+    parts
+        .into_iter()
+        .map(|part| {
+            part.parse()
+                .map_err(|error: ParseError| error.at(1, column_of(s, part)))
+        })
+        .collect()
 }
 
 impl FromStr for Term {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        static TERM_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        static FACT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
             Regex::new(
                 r#"^(?<not>!)?(?<node>[A-Za-z_]+)\.(?<fact>[A-Za-z_]+)(\((?<value>`[^`]*`|[A-Za-z_]+)\))?$"#,
             )
             .unwrap()
         });
 
-        let captures = TERM_REGEX
-            .captures(s)
-            .ok_or_else(|| anyhow::format_err!("invalid term: {s}"))?;
+        static COMPARE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(
+                r#"^(?<left>[A-Za-z_]+|\d+)(\s*(?<op>!=|<=|>=|=|<|>)\s*|\s+(?<inside>in)\s+)(?<right>[A-Za-z_]+|\d+)$"#,
+            )
+            .unwrap()
+        });
+
+        static AGGREGATE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(
+                r#"^(?<result>[A-Za-z_]+)\s*=\s*(?<aggregate>count|spans)\(\s*(?<variable>[A-Za-z_]+)\s*:(?<terms>.+)\)$"#,
+            )
+            .unwrap()
+        });
+
+        let original = s;
+        let s = s.trim();
+
+        let alternatives = split_terms(s, '|');
+        if alternatives.len() > 1 {
+            return Ok(Term::Or(parse_terms(original, alternatives)?));
+        }
+
+        if let Some(captures) = AGGREGATE_REGEX.captures(s) {
+            return Ok(Term::Aggregate {
+                result: captures.name("result").unwrap().as_str().to_string(),
+                aggregate: match captures.name("aggregate").unwrap().as_str() {
+                    "count" => Aggregate::Count,
+                    _ => Aggregate::Spans,
+                },
+                variable: captures.name("variable").unwrap().as_str().to_string(),
+                terms: parse_terms(
+                    original,
+                    split_terms(captures.name("terms").unwrap().as_str(), ','),
+                )?,
+            });
+        }
+
+        if let Some(captures) = COMPARE_REGEX.captures(s) {
+            let operand = |name| {
+                let s = captures.name(name).unwrap().as_str();
+                match s.parse() {
+                    Ok(number) => Operand::Number(number),
+                    Err(_) => Operand::Variable(s.to_string()),
+                }
+            };
+
+            let comparison = match captures.name("op").map(|c| c.as_str()) {
+                Some("=") => Comparison::Equal,
+                Some("!=") => Comparison::NotEqual,
+                Some("<") => Comparison::Less,
+                Some("<=") => Comparison::LessOrEqual,
+                Some(">") => Comparison::Greater,
+                Some(">=") => Comparison::GreaterOrEqual,
+                _ => Comparison::Inside,
+            };
+
+            return Ok(Term::Compare(operand("left"), comparison, operand("right")));
+        }
+
+        let captures = FACT_REGEX.captures(s).ok_or_else(|| {
+            let message = if s.is_empty() {
+                String::from("expected a term")
+            } else {
+                format!("invalid term `{s}`")
+            };
+
+            ParseError::new(1, column_of(original, s), message)
+        })?;
 
-        Ok(Term {
+        Ok(Term::Fact(FactTerm {
             not: captures.name("not").is_some(),
             node: captures.name("node").unwrap().as_str().to_string(),
             fact: captures.name("fact").unwrap().as_str().to_string(),
@@ -138,6 +420,440 @@ impl FromStr for Term {
                     Arg::Variable(c.as_str().to_string())
                 }
             }),
-        })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Fact, Source, Span};
+    use markdown::MarkdownQueryExt;
+
+    fn db() -> Db {
+        let mut db = Db::new();
+
+        let nodes = (0..12).map(|_| db.node()).collect::<Vec<_>>();
+        for (index, &node) in nodes.iter().enumerate() {
+            db.fact(node, Fact::new("source", Source(format!("n{}", index % 5))));
+
+            // Each node's span contains the spans of the nodes after it
+            db.fact(
+                node,
+                Fact::new(
+                    "span",
+                    Span {
+                        range: index..(24 - index),
+                        ..Span::root("test")
+                    },
+                ),
+            );
+
+            if index % 3 == 0 {
+                db.fact(node, Fact::hidden());
+            }
+
+            if index % 2 == 0 {
+                db.fact(node, Fact::new("even", ()));
+            }
+
+            db.fact(node, Fact::new("parent", nodes[index / 2]));
+
+            if index % 4 == 1 {
+                db.fact(node, Fact::new("parent", nodes[(index + 5) % 12]));
+            }
+        }
+
+        db
+    }
+
+    fn run(db: &Db, plan: &Plan<'_>, initial: &QueryValues) -> Vec<Vec<(String, String)>> {
+        let mut result = Vec::new();
+        query_inner(
+            db,
+            &|_, _, _| false,
+            plan,
+            &plan.steps,
+            initial,
+            &mut result,
+        );
+
+        let mut result = result
+            .into_iter()
+            .map(|values| {
+                let mut values = values
+                    .into_iter()
+                    .map(|(name, value)| (name, format!("{value:?}")))
+                    .collect::<Vec<_>>();
+
+                values.sort();
+                values
+            })
+            .collect::<Vec<_>>();
+
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn test_plan_matches_order() {
+        let db = db();
+
+        let queries = [
+            "a.parent(b)\nb.parent(c)\nc.source(s)",
+            "c.source(s)\nb.parent(c)\na.parent(b)",
+            "a.source(s)\nb.source(s)\nb.parent(a)",
+            "a.parent(b)\n!b.even\nb.source(s)",
+            "!a.even\na.parent(b)",
+            "a.parent(b)\nb.even\n!c.hidden\nc.parent(b)",
+            "x.source(s)\nx.parent(x)",
+            "a.parent(input)\na.source(s)",
+            "input.parent(a)\na.parent(b)\nb.parent(input)",
+            "a.parent(b) | b.parent(a)\na.even",
+            "x.parent(input) | input.parent(x)\nx != input",
+            "a.parent(b)\na != b\nb.source(s)",
+            "a.parent(b)\nb.even\nb in a | a in b",
+            "a.source(s)\nn = count(b: b.parent(a))\nn > 1",
+            "a.even\nn = count(s: b.parent(a), b.source(s) | b.even)\nn >= 1",
+        ];
+
+        for query in queries {
+            let terms = query
+                .lines()
+                .map(|line| line.parse())
+                .collect::<Result<Vec<Term>, _>>()
+                .unwrap();
+
+            for input in [None, Some(NodeId(1)), Some(NodeId(3))] {
+                let initial = input
+                    .map(|input| (String::from("input"), Rc::new(input) as Rc<dyn FactValue>))
+                    .into_iter()
+                    .collect::<QueryValues>();
+
+                let keys = || initial.keys().map(String::as_str);
+
+                assert_eq!(
+                    run(&db, &Plan::new(&terms, keys(), &db), &initial),
+                    run(&db, &Plan::in_order(&terms), &initial),
+                    "{query}",
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_terms() {
+        let fact = |not, node: &str, fact: &str, arg: Option<&str>| {
+            Term::Fact(FactTerm {
+                not,
+                node: node.to_string(),
+                fact: fact.to_string(),
+                arg: arg.map(|arg| Arg::Variable(arg.to_string())),
+            })
+        };
+
+        let variable = |name: &str| Operand::Variable(name.to_string());
+
+        let cases = [
+            ("!a.even", fact(true, "a", "even", None)),
+            (
+                "a.parent(b) | b.parent(a)",
+                Term::Or(vec![
+                    fact(false, "a", "parent", Some("b")),
+                    fact(false, "b", "parent", Some("a")),
+                ]),
+            ),
+            (
+                "a != b",
+                Term::Compare(variable("a"), Comparison::NotEqual, variable("b")),
+            ),
+            (
+                "a in b",
+                Term::Compare(variable("a"), Comparison::Inside, variable("b")),
+            ),
+            (
+                "n>=3",
+                Term::Compare(
+                    variable("n"),
+                    Comparison::GreaterOrEqual,
+                    Operand::Number(3),
+                ),
+            ),
+            (
+                "n = count(t: c.input(x) | c.output(x), x.type(t))",
+                Term::Aggregate {
+                    result: String::from("n"),
+                    aggregate: Aggregate::Count,
+                    variable: String::from("t"),
+                    terms: vec![
+                        Term::Or(vec![
+                            fact(false, "c", "input", Some("x")),
+                            fact(false, "c", "output", Some("x")),
+                        ]),
+                        fact(false, "x", "type", Some("t")),
+                    ],
+                },
+            ),
+            (
+                "s = spans(x: a.parent(b), b.span(x))",
+                Term::Aggregate {
+                    result: String::from("s"),
+                    aggregate: Aggregate::Spans,
+                    variable: String::from("x"),
+                    terms: vec![
+                        fact(false, "a", "parent", Some("b")),
+                        fact(false, "b", "span", Some("x")),
+                    ],
+                },
+            ),
+        ];
+
+        for (s, term) in cases {
+            assert_eq!(s.parse::<Term>().unwrap(), term, "{s}");
+        }
+
+        assert_eq!(
+            "a.ty(`Maybe (a | b)`)".parse::<Term>().unwrap(),
+            Term::Fact(FactTerm {
+                not: false,
+                node: String::from("a"),
+                fact: String::from("ty"),
+                arg: Some(Arg::Value(String::from("Maybe (a | b)"))),
+            })
+        );
+
+        assert!("a = count(b)".parse::<Term>().is_err());
+    }
+
+    #[test]
+    fn test_count_children() {
+        let db = db();
+
+        let terms = ["a.source(s)", "n = count(b: b.parent(a))", "n > 1"]
+            .map(|term| term.parse::<Term>().unwrap());
+
+        let mut found = query(&terms, QueryValues::new(), &db, |_, _, _| false)
+            .map(|values| {
+                (
+                    *values["a"].downcast_ref::<NodeId>().unwrap(),
+                    *values["n"].downcast_ref::<usize>().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        found.sort();
+
+        // Hidden children aren't counted
+        let expected = db
+            .nodes()
+            .filter(|&node| !db.is_hidden(node))
+            .map(|node| {
+                let children = db
+                    .all("parent")
+                    .filter(|(_, fact)| fact.value().downcast_ref::<NodeId>() == Some(&node))
+                    .map(|(child, _)| child)
+                    .filter(|&child| !db.is_hidden(child))
+                    .collect::<std::collections::BTreeSet<_>>();
+
+                (node, children.len())
+            })
+            .filter(|&(_, count)| count > 1)
+            .collect::<Vec<_>>();
+
+        assert_eq!(found, expected);
+        assert!(!found.is_empty());
+    }
+
+    #[test]
+    fn test_spans_of_children() {
+        let db = db();
+
+        let terms = ["a.source(s)", "n = spans(x: b.parent(a), b.span(x))"]
+            .map(|term| term.parse::<Term>().unwrap());
+
+        let found = query(&terms, QueryValues::new(), &db, |_, _, _| false)
+            .map(|values| {
+                let node = *values["a"].downcast_ref::<NodeId>().unwrap();
+                let spans = values["n"].downcast_ref::<Spans>().unwrap().clone();
+                (node, spans)
+            })
+            .collect::<Vec<_>>();
+
+        // Each node lists the spans of its visible children in order
+        for (node, Spans(spans)) in &found {
+            let mut expected = db
+                .all("parent")
+                .filter(|(_, fact)| fact.value().downcast_ref::<NodeId>() == Some(node))
+                .map(|(child, _)| child)
+                .filter(|&child| !db.is_hidden(child))
+                .filter_map(|child| db.get::<Span>(child, "span").cloned())
+                .collect::<Vec<_>>();
+
+            expected.sort_by_key(|span| span.range.start);
+            expected.dedup();
+
+            assert_eq!(spans, &expected);
+        }
+
+        assert!(found.iter().any(|(_, Spans(spans))| spans.len() > 1));
+    }
+
+    #[test]
+    fn test_hidden_nodes_are_never_matched() {
+        let db = db();
+
+        let terms = ["a.parent(b)", "b.source(s)"].map(|term| term.parse::<Term>().unwrap());
+
+        let parents = query(&terms, QueryValues::new(), &db, |_, _, _| false)
+            .map(|values| *values["b"].downcast_ref::<NodeId>().unwrap())
+            .collect::<Vec<_>>();
+
+        // `b` is bound through `a`'s facts, but hidden parents still don't
+        // match `b.source(s)`
+        assert!(!parents.is_empty());
+        assert!(parents.iter().all(|&node| !db.is_hidden(node)));
+        assert!(
+            db.all("parent")
+                .filter_map(|(_, fact)| fact.value().downcast_ref::<NodeId>())
+                .any(|&node| db.is_hidden(node))
+        );
+
+        // Nor do hidden nodes passed in as parameters
+        let terms = ["input.source(s)".parse::<Term>().unwrap()];
+        let hidden = db.nodes().find(|&node| db.is_hidden(node)).unwrap();
+        let initial =
+            QueryValues::from([(String::from("input"), Rc::new(hidden) as Rc<dyn FactValue>)]);
+
+        assert_eq!(query(&terms, initial, &db, |_, _, _| false).count(), 0);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let position = |error: ParseError| (error.line, error.column, error.message);
+
+        assert_eq!(
+            position("a.parent(b) | b.parent(a".parse::<Term>().unwrap_err()),
+            (1, 15, String::from("invalid term `b.parent(a`")),
+        );
+
+        let mut schema = Schema::new();
+        schema.insert::<NodeId>(&["parent"]);
+        schema.insert::<Span>(&["span"]);
+        schema.insert::<Source>(&["source"]);
+
+        let markdown = |s: &str| {
+            Query::markdown(s, &schema, |_, _, _| false)
+                .err()
+                .map(position)
+        };
+
+        assert_eq!(
+            markdown("---\na.parent(b)\nb.parnet(c)\n---\nmessage"),
+            Some((
+                3,
+                1,
+                String::from("unknown fact `parnet`; did you mean `parent`?")
+            )),
+        );
+
+        assert_eq!(
+            markdown("---\na.parent(b)\nb.span(b)\n---\nmessage"),
+            Some((
+                3,
+                1,
+                String::from("`b` is a `NodeId` in an earlier term but a `Span` here")
+            )),
+        );
+
+        assert_eq!(
+            markdown("---\na.span(span)\na.source(s)\n---\n\n[`s`] and [t], but not `[t]`"),
+            Some((6, 11, String::from("`t` isn't bound by any term"))),
+        );
+
+        assert_eq!(
+            markdown("---\na.span(span)\nn = count(b: b.parent(a))\nn in a\n---\nmessage"),
+            Some((
+                4,
+                1,
+                String::from("`n` is a `usize`, which can't be compared with `in`")
+            )),
+        );
+
+        assert_eq!(markdown("---\na.span(span)\n---\nmessage"), None);
+
+        let yaml = |s: &str| {
+            YamlQuery::yaml(s, &schema, &|_, _, _| false)
+                .err()
+                .map(position)
+        };
+
+        assert_eq!(
+            yaml(
+                "definition:\n  parameters:\n    input: span\n  outputs:\n    output: span\n  terms: |\n    node.span(input)\n    node.parent(x)\n    x.span(output) y\n"
+            ),
+            Some((9, 5, String::from("invalid term `x.span(output) y`"))),
+        );
+
+        assert_eq!(
+            yaml(
+                "definition:\n  parameters:\n    input: span\n  outputs:\n    output: span\n  terms: |\n    node.span(input)\n    node.source(output)\n"
+            ),
+            Some((5, 5, String::from("`output` is a `Source`, not a `Span`"))),
+        );
+
+        assert_eq!(
+            yaml(
+                "definition:\n  parameters:\n    input: spam\n  outputs:\n    output: span\n  terms: |\n    node.span(input)\n"
+            ),
+            Some((
+                3,
+                12,
+                String::from("expected one of `unit`, `node`, `span`, `text`, `type`, `number`")
+            )),
+        );
+
+        assert_eq!(
+            yaml(
+                "definition:\n  outputs:\n    output: span\n  terms:\n    - |\n      node.span(input)\n"
+            ),
+            Some((3, 5, String::from("`output` isn't bound by any term"))),
+        );
+
+        let rules = |s: &str| {
+            s.parse::<Rules>()
+                .and_then(|rules| rules.register(&mut schema.clone()))
+                .err()
+                .map(position)
+        };
+
+        assert_eq!(
+            rules(
+                "# ancestors\n\nancestor(x, y) :- x.parent(y)\nancestor(x, z) :- x.parent(y), y.ancestor(z"
+            ),
+            Some((4, 32, String::from("invalid term `y.ancestor(z`"))),
+        );
+
+        assert_eq!(
+            rules("ancestor(x, y) :- x.parent(y)\nnamed(x, s) :- x.ancestor(y), y.sauce(s)"),
+            Some((
+                2,
+                1,
+                String::from("unknown fact `sauce`; did you mean `source`?")
+            )),
+        );
+
+        assert_eq!(
+            rules("ancestor(x, y) :- x.parent(y)\nancestor(x, y) :- x.span(y)"),
+            Some((
+                2,
+                1,
+                String::from("`ancestor` is a `NodeId` in another rule but a `Span` here")
+            )),
+        );
+
+        assert_eq!(
+            rules("ancestor(x, z) :- x.parent(y), y.ancestor(z)\nancestor(x, y) :- x.parent(y)"),
+            None,
+        );
     }
 }
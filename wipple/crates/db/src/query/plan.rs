@@ -0,0 +1,210 @@
+use crate::{
+    Db,
+    query::{Arg, Term},
+};
+use std::collections::HashSet;
+
+/// The order to match a query's terms in, chosen so each term narrows down
+/// the results as much as possible before the next one runs.
+///
+/// Terms are written as if they're matched from top to bottom, so the plan
+/// keeps the meaning they have in that order: a negated term whose node isn't
+/// bound yet checks that no node has the fact at all. A variable used as a
+/// term's node never refers to a hidden node, however it's bound.
+#[derive(Debug, Clone)]
+pub(crate) struct Plan<'a> {
+    pub steps: Vec<&'a Term>,
+    pub visible: HashSet<&'a str>,
+}
+
+impl<'a> Plan<'a> {
+    /// Match the terms in the order they're written.
+    pub fn in_order(terms: &'a [Term]) -> Self {
+        let mut plan = Plan {
+            steps: terms.iter().collect(),
+            visible: HashSet::new(),
+        };
+
+        for term in terms {
+            subjects(term, &mut plan.visible);
+        }
+
+        plan
+    }
+
+    /// Reorder the terms based on which variables are bound and how many
+    /// facts each term could match.
+    pub fn new(terms: &'a [Term], initial: impl IntoIterator<Item = &'a str>, db: &Db) -> Self {
+        let initial = initial.into_iter().collect::<HashSet<_>>();
+        let in_order = Plan::in_order(terms);
+
+        // Negated terms checking every node don't depend on anything else
+        let mut bound = initial.clone();
+        let (global, mut remaining): (Vec<_>, Vec<_>) = terms.iter().partition(|term| {
+            let global =
+                matches!(term, Term::Fact(term) if term.not && !bound.contains(term.node.as_str()));
+
+            bound.extend(binds(term));
+            global
+        });
+
+        let mut steps = global;
+        let mut bound = initial;
+        while !remaining.is_empty() {
+            // Terms that only filter the results (or count them) run as soon
+            // as possible
+            while let Some(index) = remaining
+                .iter()
+                .position(|&term| is_filter(term) && is_ready(term, terms, &bound))
+            {
+                let term = remaining.remove(index);
+                bound.extend(binds(term));
+                steps.push(term);
+            }
+
+            let Some(index) = (0..remaining.len())
+                .filter(|&index| !is_filter(remaining[index]))
+                .min_by_key(|&index| cost(remaining[index], &bound, db))
+            else {
+                steps.append(&mut remaining);
+                break;
+            };
+
+            let term = remaining.remove(index);
+            bound.extend(binds(term));
+            steps.push(term);
+        }
+
+        Plan {
+            steps,
+            visible: in_order.visible,
+        }
+    }
+}
+
+/// Record the variables that `term` matches facts on.
+fn subjects<'a>(term: &'a Term, visible: &mut HashSet<&'a str>) {
+    match term {
+        Term::Fact(term) => {
+            if !term.not {
+                visible.insert(&term.node);
+            }
+        }
+        Term::Or(alternatives) => {
+            for alternative in alternatives {
+                subjects(alternative, visible);
+            }
+        }
+        Term::Compare(..) | Term::Aggregate { .. } => {}
+    }
+}
+
+/// The variables that are always bound after matching `term`.
+fn binds(term: &Term) -> Vec<&str> {
+    match term {
+        Term::Fact(term) if term.not => Vec::new(),
+        Term::Fact(term) => {
+            let mut variables = vec![term.node.as_str()];
+            if let Some(Arg::Variable(variable)) = &term.arg {
+                variables.push(variable);
+            }
+
+            variables
+        }
+        Term::Or(alternatives) => {
+            let mut alternatives = alternatives.iter().map(binds);
+            let first = alternatives.next().unwrap_or_default();
+            alternatives.fold(first, |mut variables, other| {
+                variables.retain(|variable| other.contains(variable));
+                variables
+            })
+        }
+        Term::Compare(..) => Vec::new(),
+        Term::Aggregate { result, .. } => vec![result],
+    }
+}
+
+/// The variables that appear anywhere in `term`.
+fn variables(term: &Term) -> Vec<&str> {
+    match term {
+        Term::Fact(term) => {
+            let mut variables = vec![term.node.as_str()];
+            if let Some(Arg::Variable(variable)) = &term.arg {
+                variables.push(variable);
+            }
+
+            variables
+        }
+        Term::Or(terms) => terms.iter().flat_map(variables).collect(),
+        Term::Compare(left, _, right) => [left, right]
+            .into_iter()
+            .filter_map(|operand| operand.variable())
+            .collect(),
+        Term::Aggregate {
+            result,
+            variable,
+            terms,
+            ..
+        } => [result.as_str(), variable.as_str()]
+            .into_iter()
+            .chain(terms.iter().flat_map(variables))
+            .collect(),
+    }
+}
+
+fn is_filter(term: &Term) -> bool {
+    match term {
+        Term::Fact(term) => term.not,
+        Term::Or(alternatives) => alternatives.iter().all(is_filter),
+        Term::Compare(..) | Term::Aggregate { .. } => true,
+    }
+}
+
+/// Whether everything `term` depends on is bound. An aggregate only depends on
+/// the variables it shares with the rest of the query.
+fn is_ready(term: &Term, terms: &[Term], bound: &HashSet<&str>) -> bool {
+    match term {
+        Term::Fact(term) => !term.not || bound.contains(term.node.as_str()),
+        Term::Or(_) | Term::Compare(..) => variables(term)
+            .into_iter()
+            .all(|variable| bound.contains(variable)),
+        Term::Aggregate {
+            terms: aggregated, ..
+        } => {
+            let shared = terms
+                .iter()
+                .filter(|&other| !std::ptr::eq(other, term))
+                .flat_map(variables)
+                .collect::<HashSet<_>>();
+
+            aggregated
+                .iter()
+                .flat_map(variables)
+                .filter(|variable| shared.contains(variable))
+                .all(|variable| bound.contains(variable))
+        }
+    }
+}
+
+fn cost(term: &Term, bound: &HashSet<&str>, db: &Db) -> usize {
+    match term {
+        Term::Fact(term) => {
+            if bound.contains(term.node.as_str()) {
+                0
+            } else if matches!(
+                &term.arg,
+                Some(Arg::Variable(variable)) if bound.contains(variable.as_str())
+            ) {
+                // Probably found in the value index
+                1
+            } else {
+                2 + db.count(&term.fact)
+            }
+        }
+        Term::Or(alternatives) => alternatives
+            .iter()
+            .map(|alternative| cost(alternative, bound, db))
+            .fold(0, usize::saturating_add),
+        Term::Compare(..) | Term::Aggregate { .. } => 0,
+    }
+}
@@ -0,0 +1,419 @@
+use crate::{
+    Db, Fact, FactValue, NodeId,
+    query::{
+        Arg, FactTerm, ParseError, QueryValues, Schema, Term, ValueType, parse_terms, query_hidden,
+        split_terms,
+    },
+};
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    str::FromStr,
+    sync::LazyLock,
+};
+
+/// A fact derived from other facts, like `inside(x, y) :- x.parent(y)`.
+/// Rules with the same name are alternatives, and a rule can refer to itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub node: String,
+    pub arg: Option<String>,
+    pub body: Vec<Term>,
+
+    /// Where the rule was written, for reporting errors.
+    pub line: usize,
+}
+
+/// The rules available to queries, one per line. Lines starting with `#` are
+/// comments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rules(pub Vec<Rule>);
+
+impl FromStr for Rule {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        static RULE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(
+                r#"^\s*(?<name>[A-Za-z_]+)\(\s*(?<node>[A-Za-z_]+)\s*(,\s*(?<arg>[A-Za-z_]+)\s*)?\)\s*:-(?<body>.+)$"#,
+            )
+            .unwrap()
+        });
+
+        let captures = RULE_REGEX.captures(s).ok_or_else(|| {
+            ParseError::new(1, 1, "expected a rule like `name(node, value) :- terms`")
+        })?;
+
+        Ok(Rule {
+            name: captures.name("name").unwrap().as_str().to_string(),
+            node: captures.name("node").unwrap().as_str().to_string(),
+            arg: captures.name("arg").map(|c| c.as_str().to_string()),
+            body: parse_terms(s, split_terms(captures.name("body").unwrap().as_str(), ','))?,
+            line: 1,
+        })
+    }
+}
+
+impl FromStr for Rules {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rules = s
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                let line = line.trim();
+                !line.is_empty() && !line.starts_with('#')
+            })
+            .map(|(index, line)| {
+                let rule = line
+                    .parse::<Rule>()
+                    .map_err(|error| error.at(index + 1, 1))?;
+
+                Ok(Rule {
+                    line: index + 1,
+                    ..rule
+                })
+            })
+            .collect::<Result<Vec<_>, ParseError>>()?;
+
+        let names = rules
+            .iter()
+            .map(|rule| rule.name.as_str())
+            .collect::<HashSet<_>>();
+
+        // Negating or counting a derived fact could change whether the fact
+        // itself is derived
+        for rule in &rules {
+            for term in &rule.body {
+                check_derived(term, &names, false)
+                    .map_err(|message| ParseError::new(rule.line, 1, message))?;
+            }
+        }
+
+        // Each combination of alternatives becomes its own rule, so facts
+        // derived in one round are always matched in the next
+        let rules = rules
+            .iter()
+            .flat_map(|rule| {
+                expand(&rule.body).into_iter().map(move |body| Rule {
+                    body,
+                    ..rule.clone()
+                })
+            })
+            .collect();
+
+        Ok(Rules(rules))
+    }
+}
+
+impl Rules {
+    /// Add the facts derived by these rules to `schema`, checking each rule's
+    /// body against the facts already there.
+    pub fn register(&self, schema: &mut Schema) -> Result<(), ParseError> {
+        // A rule can only be checked once the facts it refers to are known, so
+        // keep going until every rule has been checked
+        let mut remaining = self.0.iter().collect::<Vec<_>>();
+        while !remaining.is_empty() {
+            let (ready, rest): (Vec<&Rule>, Vec<&Rule>) = remaining.iter().partition(|rule| {
+                rule.body
+                    .iter()
+                    .all(|term| facts(term).all(|fact| !schema.types(fact).is_empty()))
+            });
+
+            // Report the unknown fact in the first rule that can't be checked
+            let ready = if ready.is_empty() {
+                vec![rest[0]]
+            } else {
+                ready
+            };
+
+            for rule in &ready {
+                let error = |message: String| ParseError::new(rule.line, 1, message);
+
+                let types = schema
+                    .check(&rule.body, &[])
+                    .map_err(|(_, message)| error(message))?;
+
+                if types.get(&rule.node) != Some(&Some(ValueType::of::<NodeId>())) {
+                    return Err(error(format!("`{}` isn't bound to a node", rule.node)));
+                }
+
+                let ty = match &rule.arg {
+                    Some(arg) => types
+                        .get(arg)
+                        .ok_or_else(|| error(format!("`{arg}` isn't bound by any term")))?
+                        .ok_or_else(|| error(format!("`{arg}` could have more than one type")))?,
+                    None => ValueType::of::<()>(),
+                };
+
+                match schema.types(&rule.name) {
+                    [] => schema.insert_type(&rule.name, ty),
+                    [existing] if *existing == ty => {}
+                    [existing, ..] => {
+                        return Err(error(format!(
+                            "`{}` is a `{existing}` in another rule but a `{ty}` here",
+                            rule.name
+                        )));
+                    }
+                }
+            }
+
+            remaining.retain(|rule| !ready.contains(rule));
+        }
+
+        Ok(())
+    }
+}
+
+/// The names of the facts `term` refers to.
+fn facts(term: &Term) -> Box<dyn Iterator<Item = &str> + '_> {
+    match term {
+        Term::Fact(term) => Box::new(std::iter::once(term.fact.as_str())),
+        Term::Or(terms) | Term::Aggregate { terms, .. } => Box::new(terms.iter().flat_map(facts)),
+        Term::Compare(..) => Box::new(std::iter::empty()),
+    }
+}
+
+impl Db {
+    /// Add the facts derived by `rules`, repeating until there are no more.
+    /// Each round only looks for facts that depend on the previous round's
+    /// facts.
+    pub fn derive(&mut self, rules: &Rules, matcher: impl Fn(&Db, &dyn FactValue, &str) -> bool) {
+        let names = rules
+            .0
+            .iter()
+            .map(|rule| rule.name.as_str())
+            .collect::<HashSet<_>>();
+
+        self.derived
+            .extend(names.iter().map(|&name| Rc::from(name)));
+
+        let mut new = Vec::new();
+        for rule in &rules.0 {
+            for values in query_hidden(&rule.body, QueryValues::new(), self, &matcher) {
+                new.extend(rule.fact(&values));
+            }
+        }
+
+        loop {
+            let mut delta = Db::new();
+            for (node, fact) in new.drain(..) {
+                if !self
+                    .iter_by(node, &fact.name)
+                    .any(|other| other.value() == fact.value())
+                {
+                    delta.fact(node, fact.clone());
+                    self.fact(node, fact);
+                }
+            }
+
+            if delta.nodes().next().is_none() {
+                break;
+            }
+
+            for rule in &rules.0 {
+                for (index, term) in rule.body.iter().enumerate() {
+                    let Term::Fact(term) = term else {
+                        continue;
+                    };
+
+                    if term.not || !names.contains(term.fact.as_str()) {
+                        continue;
+                    }
+
+                    let mut rest = rule.body.clone();
+                    rest.remove(index);
+
+                    for (node, fact) in delta.all(&term.fact) {
+                        let Some(initial) = term.bind(node, fact, self, &matcher) else {
+                            continue;
+                        };
+
+                        for values in query_hidden(&rest, initial, self, &matcher) {
+                            new.extend(rule.fact(&values));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether facts with this name were derived from rules rather than
+    /// added directly.
+    pub fn is_derived(&self, name: &str) -> bool {
+        self.derived.contains(name)
+    }
+}
+
+impl Rule {
+    fn fact(&self, values: &QueryValues) -> Option<(NodeId, Fact)> {
+        let node = values.get(&self.node)?.downcast_ref::<NodeId>().copied()?;
+
+        let value = match &self.arg {
+            Some(arg) => values.get(arg)?.clone(),
+            None => Rc::new(()),
+        };
+
+        Some((
+            node,
+            Fact {
+                name: Rc::from(self.name.as_str()),
+                value,
+            },
+        ))
+    }
+}
+
+/// The bodies matching each combination of alternatives in `body`.
+fn expand(body: &[Term]) -> Vec<Vec<Term>> {
+    body.iter().fold(vec![Vec::new()], |bodies, term| {
+        let alternatives = match term {
+            Term::Or(alternatives) => alternatives
+                .iter()
+                .flat_map(|alternative| expand(std::slice::from_ref(alternative)))
+                .collect(),
+            _ => vec![vec![term.clone()]],
+        };
+
+        bodies
+            .iter()
+            .flat_map(|body| {
+                alternatives
+                    .iter()
+                    .map(move |alternative| [body.as_slice(), alternative].concat())
+            })
+            .collect()
+    })
+}
+
+fn check_derived(term: &Term, names: &HashSet<&str>, aggregated: bool) -> Result<(), String> {
+    match term {
+        Term::Fact(term) if names.contains(term.fact.as_str()) => {
+            if term.not {
+                return Err(format!("can't negate derived fact `{}`", term.fact));
+            } else if aggregated {
+                return Err(format!("can't aggregate derived fact `{}`", term.fact));
+            }
+
+            Ok(())
+        }
+        Term::Fact(_) | Term::Compare(..) => Ok(()),
+        Term::Or(terms) => terms
+            .iter()
+            .try_for_each(|term| check_derived(term, names, aggregated)),
+        Term::Aggregate { terms, .. } => terms
+            .iter()
+            .try_for_each(|term| check_derived(term, names, true)),
+    }
+}
+
+impl FactTerm {
+    /// The values bound by matching `fact` on `node` with this term.
+    fn bind(
+        &self,
+        node: NodeId,
+        fact: &Fact,
+        db: &Db,
+        matcher: &dyn Fn(&Db, &dyn FactValue, &str) -> bool,
+    ) -> Option<QueryValues> {
+        let mut values = HashMap::from([(self.node.clone(), Rc::new(node) as Rc<dyn FactValue>)]);
+
+        match &self.arg {
+            Some(Arg::Variable(variable)) => match values.get(variable) {
+                Some(other) if other.as_ref() != fact.value() => return None,
+                Some(_) => {}
+                None => {
+                    values.insert(variable.clone(), fact.clone_value());
+                }
+            },
+            Some(Arg::Value(pattern)) if !matcher(db, fact.value(), pattern) => return None,
+            Some(Arg::Value(_)) | None => {}
+        }
+
+        Some(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_derive_transitive_closure() {
+        let mut db = Db::new();
+
+        let nodes = (0..20).map(|_| db.node()).collect::<Vec<_>>();
+        let mut edges = Vec::new();
+        for (index, &node) in nodes.iter().enumerate() {
+            for parent in [index / 2, (index * 7 + 3) % 20] {
+                if parent != index {
+                    db.fact(node, Fact::new("parent", nodes[parent]));
+                    edges.push((node, nodes[parent]));
+                }
+            }
+
+            if index % 3 == 0 {
+                db.fact(node, Fact::hidden());
+            }
+        }
+
+        let rules: Rules = "
+            # Each node's parents, their parents, and so on
+            ancestor(x, y) :- x.parent(y)
+            ancestor(x, z) :- x.parent(y), y.ancestor(z)
+            root(x) :- x.ancestor(x)
+        "
+        .parse()
+        .unwrap();
+
+        db.derive(&rules, |_, _, _| false);
+
+        let mut expected = BTreeSet::new();
+        for &(node, _) in &edges {
+            let mut stack = vec![node];
+            while let Some(next) = stack.pop() {
+                for &(_, parent) in edges.iter().filter(|&&(child, _)| child == next) {
+                    if expected.insert((node, parent)) {
+                        stack.push(parent);
+                    }
+                }
+            }
+        }
+
+        let found = db
+            .all("ancestor")
+            .map(|(node, fact)| (node, *fact.value().downcast_ref::<NodeId>().unwrap()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(found.len(), expected.len(), "duplicate facts");
+        assert_eq!(found.into_iter().collect::<BTreeSet<_>>(), expected);
+
+        let roots = db
+            .all("root")
+            .map(|(node, _)| node)
+            .collect::<BTreeSet<_>>();
+        let cycles = expected
+            .iter()
+            .filter(|(node, parent)| node == parent)
+            .map(|&(node, _)| node)
+            .collect::<BTreeSet<_>>();
+
+        assert_eq!(roots, cycles);
+        assert!(db.is_derived("ancestor") && !db.is_derived("parent"));
+    }
+
+    #[test]
+    fn test_rules_reject_negated_derived_facts() {
+        let rules = "
+            ancestor(x, y) :- x.parent(y)
+            orphan(x) :- x.source(s), !x.ancestor(y)
+        "
+        .parse::<Rules>();
+
+        assert!(rules.is_err());
+    }
+}
@@ -0,0 +1,388 @@
+use crate::{
+    Db, Fact, FactValue, NodeId, Source, Span, Spans, TraitOutput,
+    query::{Aggregate, Arg, Comparison, Operand, Term},
+};
+use regex::Regex;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    sync::LazyLock,
+};
+use visualizer::{Constraint, Ty};
+
+/// The type of a fact's value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueType {
+    id: TypeId,
+    name: &'static str,
+}
+
+impl ValueType {
+    pub fn of<T: FactValue>() -> Self {
+        ValueType {
+            id: TypeId::of::<T>(),
+            name: std::any::type_name::<T>(),
+        }
+    }
+
+    /// The names types are written with in query files.
+    pub const NAMES: &[&str] = &["unit", "node", "span", "text", "type", "number"];
+
+    /// The type written with this name in a query file, like `span`.
+    pub fn named(name: &str) -> Option<Self> {
+        Some(match name {
+            "unit" => ValueType::of::<()>(),
+            "node" => ValueType::of::<NodeId>(),
+            "span" => ValueType::of::<Span>(),
+            "text" => ValueType::of::<Source>(),
+            "type" => ValueType::of::<Ty<Db>>(),
+            "number" => ValueType::of::<usize>(),
+            _ => return None,
+        })
+    }
+
+    /// The name the type is written with in query files, if it has one.
+    pub fn query_name(&self) -> Option<&'static str> {
+        ValueType::NAMES
+            .iter()
+            .copied()
+            .find(|&name| ValueType::named(name) == Some(*self))
+    }
+
+    pub fn is_type_of(&self, value: &dyn FactValue) -> bool {
+        (value as &dyn Any).type_id() == self.id
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        static PATH_REGEX: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r#"[A-Za-z_0-9]+::"#).unwrap());
+
+        match self.name {
+            "()" => write!(f, "unit"),
+            name => write!(f, "{}", PATH_REGEX.replace_all(name, "")),
+        }
+    }
+}
+
+/// The facts a database can contain and the types of their values, so
+/// queries can be checked when they're loaded rather than silently matching
+/// nothing. A fact can have values of more than one type, like `number`,
+/// which marks number nodes and also links them to the `Number` type.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    facts: HashMap<String, Vec<ValueType>>,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Schema::new()
+    }
+}
+
+impl Schema {
+    /// A schema containing the facts added by [`Db`] itself.
+    pub fn new() -> Self {
+        let mut schema = Schema {
+            facts: HashMap::new(),
+        };
+
+        schema.insert::<()>(&[
+            "hidden",
+            "instantiated",
+            "generalized",
+            "incompleteType",
+            "solverStepLimit",
+        ]);
+
+        schema.insert::<NodeId>(&[
+            "resolvedTrait",
+            "resolvedInstance",
+            "usedDefaultInstance",
+            "customErrorInstance",
+            "missingSuperInstance",
+            "unresolvedTrait",
+            "ambiguousTrait",
+            "ambiguousInstance",
+            "overlappingInstance",
+            "instanceRecursionLimit",
+        ]);
+
+        schema.insert::<Source>(&["customError"]);
+        schema.insert::<Ty<Db>>(&["type"]);
+        schema.insert::<TraitOutput>(&["traitOutput"]);
+        schema.insert::<Constraint<Db>>(&["unsolvedConstraint"]);
+
+        schema
+    }
+
+    /// Add facts whose values have type `T`.
+    pub fn insert<T: FactValue>(&mut self, names: &[&str]) {
+        for &name in names {
+            self.insert_type(name, ValueType::of::<T>());
+        }
+    }
+
+    pub fn insert_type(&mut self, name: &str, ty: ValueType) {
+        let types = self.facts.entry(name.to_string()).or_default();
+        if !types.contains(&ty) {
+            types.push(ty);
+        }
+    }
+
+    /// The types of values the fact can have, which is empty if the fact
+    /// isn't in the schema.
+    pub fn types(&self, name: &str) -> &[ValueType] {
+        self.facts.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Every fact in the schema and the types of its values, sorted by name.
+    pub fn facts(&self) -> Vec<(&str, &[ValueType])> {
+        let mut facts = self
+            .facts
+            .iter()
+            .map(|(name, types)| (name.as_str(), types.as_slice()))
+            .collect::<Vec<_>>();
+
+        facts.sort_by_key(|&(name, _)| name);
+        facts
+    }
+
+    /// The facts in `db` that aren't in the schema, or whose values have a
+    /// different type.
+    pub fn unknown_facts<'a>(&self, db: &'a Db) -> Vec<(NodeId, &'a Fact)> {
+        db.nodes()
+            .flat_map(|node| db.iter(node).map(move |fact| (node, fact)))
+            .filter(|(_, fact)| {
+                !self
+                    .types(fact.name())
+                    .iter()
+                    .any(|ty| ty.is_type_of(fact.value()))
+            })
+            .collect()
+    }
+
+    /// Check that the terms only use facts in the schema, and that each
+    /// variable always refers to the same type of value. Returns the type of
+    /// each variable (or `None` if it could have more than one type), or the
+    /// index of the term with a problem.
+    pub(crate) fn check(
+        &self,
+        terms: &[Term],
+        initial: &[(&str, ValueType)],
+    ) -> Result<HashMap<String, Option<ValueType>>, (usize, String)> {
+        let mut checker = Checker {
+            schema: self,
+            types: initial
+                .iter()
+                .map(|&(name, ty)| (name.to_string(), Some(ty)))
+                .collect(),
+        };
+
+        for (index, term) in terms.iter().enumerate() {
+            checker.term(term).map_err(|message| (index, message))?;
+        }
+
+        // Comparisons can refer to variables bound by any other term
+        for (index, term) in terms.iter().enumerate() {
+            checker
+                .comparisons(term)
+                .map_err(|message| (index, message))?;
+        }
+
+        Ok(checker.types)
+    }
+
+    fn suggest(&self, name: &str) -> String {
+        let closest = self
+            .facts
+            .keys()
+            .map(|other| (distance(name, other), other))
+            .filter(|&(distance, _)| distance <= 3)
+            .min();
+
+        match closest {
+            Some((_, other)) => format!("unknown fact `{name}`; did you mean `{other}`?"),
+            None => format!("unknown fact `{name}`"),
+        }
+    }
+}
+
+struct Checker<'a> {
+    schema: &'a Schema,
+    types: HashMap<String, Option<ValueType>>,
+}
+
+impl Checker<'_> {
+    fn term(&mut self, term: &Term) -> Result<(), String> {
+        match term {
+            Term::Fact(term) => {
+                let ty = match self.schema.types(&term.fact) {
+                    [] => return Err(self.schema.suggest(&term.fact)),
+                    [ty] => Some(*ty),
+                    _ => None,
+                };
+
+                self.variable(&term.node, Some(ValueType::of::<NodeId>()))?;
+
+                // Negated terms ignore their value
+                if !term.not
+                    && let Some(Arg::Variable(variable)) = &term.arg
+                {
+                    self.variable(variable, ty)?;
+                }
+
+                Ok(())
+            }
+            Term::Or(alternatives) => alternatives
+                .iter()
+                .try_for_each(|alternative| self.term(alternative)),
+            Term::Compare(..) => Ok(()),
+            Term::Aggregate {
+                result,
+                aggregate,
+                variable,
+                terms,
+            } => {
+                let result_ty = match aggregate {
+                    Aggregate::Count => ValueType::of::<usize>(),
+                    Aggregate::Spans => ValueType::of::<Spans>(),
+                };
+
+                self.variable(result, Some(result_ty))?;
+                terms.iter().try_for_each(|term| self.term(term))?;
+
+                match (aggregate, self.types.get(variable)) {
+                    (_, None) => Err(format!("`{variable}` isn't bound by the aggregated terms")),
+                    (Aggregate::Spans, Some(Some(ty))) if *ty != ValueType::of::<Span>() => Err(
+                        format!("`{variable}` is a `{ty}`, which can't be listed with `spans`"),
+                    ),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    fn comparisons(&self, term: &Term) -> Result<(), String> {
+        match term {
+            Term::Fact(_) => Ok(()),
+            Term::Or(terms) | Term::Aggregate { terms, .. } => {
+                terms.iter().try_for_each(|term| self.comparisons(term))
+            }
+            Term::Compare(left, comparison, right) => {
+                // Values that could have more than one type are checked when
+                // the query runs
+                let (Some(left_ty), Some(right_ty)) = (self.operand(left)?, self.operand(right)?)
+                else {
+                    return Ok(());
+                };
+
+                let number = ValueType::of::<usize>();
+                let allowed = |operand: &Operand, ty: ValueType, allowed: &[ValueType]| {
+                    if allowed.contains(&ty) {
+                        Ok(())
+                    } else {
+                        Err(format!(
+                            "`{}` is a `{ty}`, which can't be compared with `{}`",
+                            display_operand(operand),
+                            display_comparison(*comparison),
+                        ))
+                    }
+                };
+
+                match comparison {
+                    Comparison::Equal | Comparison::NotEqual => {
+                        if left_ty == right_ty {
+                            Ok(())
+                        } else {
+                            Err(format!(
+                                "`{}` is a `{left_ty}` but `{}` is a `{right_ty}`",
+                                display_operand(left),
+                                display_operand(right),
+                            ))
+                        }
+                    }
+                    Comparison::Less
+                    | Comparison::LessOrEqual
+                    | Comparison::Greater
+                    | Comparison::GreaterOrEqual => {
+                        allowed(left, left_ty, &[number])?;
+                        allowed(right, right_ty, &[number])
+                    }
+                    Comparison::Inside => {
+                        let spans = [ValueType::of::<Span>(), ValueType::of::<NodeId>()];
+                        allowed(left, left_ty, &spans)?;
+                        allowed(right, right_ty, &spans)
+                    }
+                }
+            }
+        }
+    }
+
+    fn variable(&mut self, name: &str, ty: Option<ValueType>) -> Result<(), String> {
+        match (self.types.get(name), ty) {
+            (Some(&Some(existing)), Some(ty)) if existing != ty => Err(format!(
+                "`{name}` is a `{existing}` in an earlier term but a `{ty}` here"
+            )),
+            (Some(None), Some(ty)) | (None, Some(ty)) => {
+                self.types.insert(name.to_string(), Some(ty));
+                Ok(())
+            }
+            (None, None) => {
+                self.types.insert(name.to_string(), None);
+                Ok(())
+            }
+            (Some(_), _) => Ok(()),
+        }
+    }
+
+    fn operand(&self, operand: &Operand) -> Result<Option<ValueType>, String> {
+        match operand {
+            Operand::Variable(variable) => self
+                .types
+                .get(variable)
+                .copied()
+                .ok_or_else(|| format!("`{variable}` isn't bound by any term")),
+            Operand::Number(_) => Ok(Some(ValueType::of::<usize>())),
+        }
+    }
+}
+
+fn display_operand(operand: &Operand) -> String {
+    match operand {
+        Operand::Variable(variable) => variable.clone(),
+        Operand::Number(number) => number.to_string(),
+    }
+}
+
+fn display_comparison(comparison: Comparison) -> &'static str {
+    match comparison {
+        Comparison::Equal => "=",
+        Comparison::NotEqual => "!=",
+        Comparison::Less => "<",
+        Comparison::LessOrEqual => "<=",
+        Comparison::Greater => ">",
+        Comparison::GreaterOrEqual => ">=",
+        Comparison::Inside => "in",
+    }
+}
+
+/// The number of single-character edits to turn `a` into `b`.
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
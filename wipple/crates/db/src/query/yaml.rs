@@ -1,80 +1,297 @@
 use crate::{
-    Db, FactValue, Query,
-    query::{QueryValues, Term, query},
+    Db, FactValue,
+    query::{ParseError, Query, QueryValues, Schema, Term, ValueType, query},
 };
-use saphyr::{LoadableYamlNode, Yaml};
-use std::{collections::HashMap, str::FromStr};
+use saphyr::{LoadableYamlNode, MarkedYaml};
+use std::{collections::HashMap, rc::Rc, str::FromStr};
+
+/// A query declared in a YAML file, along with the types of the parameters it
+/// takes and of the outputs it returns. Running the query returns one row for
+/// each distinct combination of output values, in the order the outputs are
+/// declared. Parameters that aren't provided match any value.
+pub struct YamlQuery<'a> {
+    pub parameters: Vec<(String, ValueType)>,
+    pub outputs: Vec<(String, ValueType)>,
+    pub query: Query<'a, Vec<Vec<Rc<dyn FactValue>>>>,
+}
 
 pub trait YamlQueryExt<'a>: Sized {
     fn yaml(
         yaml: &str,
+        schema: &Schema,
         matcher: &'a (dyn Fn(&Db, &dyn FactValue, &str) -> bool + Send + Sync),
-    ) -> Option<HashMap<String, Self>>;
+    ) -> Result<HashMap<String, Self>, ParseError>;
 }
 
-impl<'a> YamlQueryExt<'a> for Query<'a, Vec<QueryValues>> {
+impl<'a> YamlQueryExt<'a> for YamlQuery<'a> {
     fn yaml(
         yaml: &str,
+        schema: &Schema,
         matcher: &'a (dyn Fn(&Db, &dyn FactValue, &str) -> bool + Send + Sync),
-    ) -> Option<HashMap<String, Self>> {
-        let file = File::from_str(yaml).ok()?;
-
-        Some(
-            file.queries
-                .into_iter()
-                .map(move |(action, options)| {
-                    let query = Query::new(move |db, initial| {
-                        let mut result = Vec::new();
-                        for terms in &options {
-                            result.extend(query(terms, initial.clone(), db, matcher));
+    ) -> Result<HashMap<String, Self>, ParseError> {
+        let file = File::from_str(yaml)?;
+        file.check(schema)?;
+
+        Ok(file
+            .queries
+            .into_iter()
+            .map(move |(action, definition)| {
+                let outputs = definition
+                    .outputs
+                    .iter()
+                    .map(|output| output.name.clone())
+                    .collect::<Vec<_>>();
+
+                let options = definition.options;
+
+                let query = Query::new(move |db, initial: QueryValues| {
+                    let mut rows = Vec::new();
+                    for option in &options {
+                        for values in query(&option.terms, initial.clone(), db, matcher) {
+                            let Some(row) = outputs
+                                .iter()
+                                .map(|output| values.get(output).cloned())
+                                .collect::<Option<Vec<_>>>()
+                            else {
+                                continue;
+                            };
+
+                            if !rows.contains(&row) {
+                                rows.push(row);
+                            }
                         }
+                    }
 
-                        result
-                    });
+                    rows
+                });
 
-                    (action, query)
-                })
-                .collect(),
-        )
+                let declarations = |declarations: Vec<Declaration>| {
+                    declarations
+                        .into_iter()
+                        .map(|declaration| (declaration.name, declaration.ty))
+                        .collect()
+                };
+
+                (
+                    action,
+                    YamlQuery {
+                        parameters: declarations(definition.parameters),
+                        outputs: declarations(definition.outputs),
+                        query,
+                    },
+                )
+            })
+            .collect())
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 struct File {
-    queries: HashMap<String, Vec<Vec<Term>>>,
+    queries: HashMap<String, QueryDefinition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct QueryDefinition {
+    parameters: Vec<Declaration>,
+    outputs: Vec<Declaration>,
+    options: Vec<QueryOption>,
+}
+
+/// A parameter or output, and where it's declared.
+#[derive(Debug, Clone, PartialEq)]
+struct Declaration {
+    name: String,
+    ty: ValueType,
+    line: usize,
+    column: usize,
+}
+
+/// One of the term lists a query tries, and where its first term is.
+#[derive(Debug, Clone, PartialEq)]
+struct QueryOption {
+    terms: Vec<Term>,
+    line: usize,
+    column: usize,
+}
+
+impl File {
+    fn check(&self, schema: &Schema) -> Result<(), ParseError> {
+        for definition in self.queries.values() {
+            let parameters = definition
+                .parameters
+                .iter()
+                .map(|parameter| (parameter.name.as_str(), parameter.ty))
+                .collect::<Vec<_>>();
+
+            for option in &definition.options {
+                let types =
+                    schema
+                        .check(&option.terms, &parameters)
+                        .map_err(|(index, message)| {
+                            ParseError::new(option.line + index, option.column, message)
+                        })?;
+
+                for output in &definition.outputs {
+                    let message = match types.get(&output.name) {
+                        Some(Some(ty)) if *ty == output.ty => continue,
+                        Some(Some(ty)) => {
+                            format!("`{}` is a `{}`, not a `{}`", output.name, ty, output.ty)
+                        }
+                        Some(None) => format!("`{}` could have more than one type", output.name),
+                        None => format!("`{}` isn't bound by any term", output.name),
+                    };
+
+                    return Err(ParseError::new(output.line, output.column, message));
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl FromStr for File {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(File {
-            queries: Yaml::load_from_str(s)?[0]
+        let error = |node: &MarkedYaml<'_>, message: &str| {
+            ParseError::new(node.span.start.line(), node.span.start.col() + 1, message)
+        };
+
+        let documents = MarkedYaml::load_from_str(s).map_err(|error| {
+            let marker = error.marker();
+            ParseError::new(marker.line(), marker.col() + 1, error.info())
+        })?;
+
+        let Some(document) = documents.first() else {
+            return Ok(File {
+                queries: HashMap::new(),
+            });
+        };
+
+        let parse_option = |item: &MarkedYaml<'_>| {
+            let terms = item
+                .data
+                .as_str()
+                .ok_or_else(|| error(item, "expected terms to be a string"))?;
+
+            let line = item.span.start.line();
+            let column = s
+                .lines()
+                .nth(line - 1)
+                .map_or(1, |source| source.len() - source.trim_start().len() + 1);
+
+            Ok(QueryOption {
+                terms: terms
+                    .lines()
+                    .enumerate()
+                    .map(|(index, term)| {
+                        term.parse()
+                            .map_err(|error: ParseError| error.at(line + index, column))
+                    })
+                    .collect::<Result<_, ParseError>>()?,
+                line,
+                column,
+            })
+        };
+
+        let parse_declarations = |value: Option<&MarkedYaml<'_>>| {
+            let Some(value) = value else {
+                return Ok(Vec::new());
+            };
+
+            value
+                .data
                 .as_mapping()
-                .ok_or_else(|| anyhow::format_err!("expected a mapping"))?
-                .into_iter()
+                .ok_or_else(|| error(value, "expected a mapping from names to types"))?
+                .iter()
                 .map(|(key, value)| {
-                    Ok((
-                        key.as_str()
-                            .ok_or_else(|| anyhow::format_err!("expected key to be a string"))?
-                            .to_string(),
-                        value
-                            .as_sequence()
-                            .ok_or_else(|| anyhow::format_err!("expected value to be a sequence"))?
-                            .iter()
-                            .map(|item| {
-                                item.as_str()
-                                    .ok_or_else(|| {
-                                        anyhow::format_err!("expected sequence item to be a string")
-                                    })?
-                                    .lines()
-                                    .map(|line| line.parse())
-                                    .collect::<anyhow::Result<_>>()
-                            })
-                            .collect::<anyhow::Result<_>>()?,
-                    ))
+                    let name = key
+                        .data
+                        .as_str()
+                        .ok_or_else(|| error(key, "expected name to be a string"))?;
+
+                    let ty = value
+                        .data
+                        .as_str()
+                        .and_then(ValueType::named)
+                        .ok_or_else(|| {
+                            error(
+                                value,
+                                &format!(
+                                    "expected one of {}",
+                                    ValueType::NAMES
+                                        .iter()
+                                        .map(|name| format!("`{name}`"))
+                                        .collect::<Vec<_>>()
+                                        .join(", ")
+                                ),
+                            )
+                        })?;
+
+                    Ok(Declaration {
+                        name: name.to_string(),
+                        ty,
+                        line: key.span.start.line(),
+                        column: key.span.start.col() + 1,
+                    })
                 })
-                .collect::<anyhow::Result<_>>()?,
-        })
+                .collect::<Result<Vec<_>, ParseError>>()
+        };
+
+        let queries = document
+            .data
+            .as_mapping()
+            .ok_or_else(|| error(document, "expected a mapping"))?
+            .iter()
+            .map(|(key, value)| {
+                let name = key
+                    .data
+                    .as_str()
+                    .ok_or_else(|| error(key, "expected key to be a string"))?;
+
+                let mapping = value
+                    .data
+                    .as_mapping()
+                    .ok_or_else(|| error(value, "expected value to be a mapping"))?;
+
+                let mut fields = HashMap::new();
+                for (key, value) in mapping.iter() {
+                    match key.data.as_str() {
+                        Some(field @ ("parameters" | "outputs" | "terms")) => {
+                            fields.insert(field, value);
+                        }
+                        _ => {
+                            return Err(error(key, "expected `parameters`, `outputs` or `terms`"));
+                        }
+                    }
+                }
+
+                let terms = fields
+                    .get("terms")
+                    .ok_or_else(|| error(key, "expected `terms`"))?;
+
+                let options = match terms.data.as_sequence() {
+                    Some(items) => items.iter().map(parse_option).collect::<Result<_, _>>()?,
+                    None => vec![parse_option(terms)?],
+                };
+
+                let outputs = parse_declarations(fields.get("outputs").copied())?;
+                if outputs.is_empty() {
+                    return Err(error(key, "expected `outputs`"));
+                }
+
+                Ok((
+                    name.to_string(),
+                    QueryDefinition {
+                        parameters: parse_declarations(fields.get("parameters").copied())?,
+                        outputs,
+                        options,
+                    },
+                ))
+            })
+            .collect::<Result<_, ParseError>>()?;
+
+        Ok(File { queries })
     }
 }
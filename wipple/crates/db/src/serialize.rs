@@ -0,0 +1,341 @@
+use crate::{Db, Fact, FactValue, NodeId, Source, Span, TraitOutput, ValueType};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::{
+    io::{Read, Write},
+    rc::Rc,
+};
+use visualizer::{Substitutions, Ty, TyGroups};
+
+type Encode = fn(&dyn FactValue) -> Option<serde_json::Value>;
+type Decode = fn(serde_json::Value) -> serde_json::Result<Rc<dyn FactValue>>;
+
+/// The types of fact values that can be saved, each identified by a name in
+/// the saved file.
+#[derive(Clone)]
+pub struct Registry {
+    types: Vec<RegisteredType>,
+}
+
+#[derive(Clone)]
+struct RegisteredType {
+    name: &'static str,
+    ty: ValueType,
+    encode: Encode,
+    decode: Decode,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::new()
+    }
+}
+
+impl Registry {
+    /// A registry containing the values used by [`Db`] itself.
+    pub fn new() -> Self {
+        let mut registry = Registry { types: Vec::new() };
+        registry.register::<()>("unit");
+        registry.register::<NodeId>("node");
+        registry.register::<Span>("span");
+        registry.register::<Source>("text");
+        registry.register::<Ty<Db>>("type");
+        registry.register::<usize>("number");
+        registry.register::<Substitutions<Db>>("substitutions");
+        registry.register::<TraitOutput>("traitOutput");
+        registry
+    }
+
+    /// Save values of type `T` under `name`.
+    pub fn register<T: FactValue + Serialize + DeserializeOwned>(&mut self, name: &'static str) {
+        fn encode<T: FactValue + Serialize>(value: &dyn FactValue) -> Option<serde_json::Value> {
+            serde_json::to_value(value.downcast_ref::<T>()?).ok()
+        }
+
+        fn decode<T: FactValue + DeserializeOwned>(
+            value: serde_json::Value,
+        ) -> serde_json::Result<Rc<dyn FactValue>> {
+            Ok(Rc::new(serde_json::from_value::<T>(value)?))
+        }
+
+        self.types.push(RegisteredType {
+            name,
+            ty: ValueType::of::<T>(),
+            encode: encode::<T>,
+            decode: decode::<T>,
+        });
+    }
+
+    fn encode(&self, value: &dyn FactValue) -> Option<(&'static str, serde_json::Value)> {
+        let registered = self.types.iter().find(|ty| ty.ty.is_type_of(value))?;
+        Some((registered.name, (registered.encode)(value)?))
+    }
+
+    fn decode(
+        &self,
+        name: &str,
+        value: serde_json::Value,
+    ) -> serde_json::Result<Rc<dyn FactValue>> {
+        let registered = self
+            .types
+            .iter()
+            .find(|ty| ty.name == name)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown value type `{name}`")))?;
+
+        (registered.decode)(value)
+    }
+}
+
+/// A value whose type isn't in the [`Registry`], saved as the text it's
+/// displayed with so a loaded database is written the same way.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Unregistered {
+    pub display: Option<String>,
+    pub code: bool,
+}
+
+impl FactValue for Unregistered {
+    fn display(&self, _db: &Db) -> Option<String> {
+        self.display.clone()
+    }
+
+    fn is_code(&self) -> bool {
+        self.code
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SavedDb {
+    next_id: u32,
+    derived: Vec<String>,
+    nodes: Vec<SavedNode>,
+    ty_groups: Vec<SavedTyGroup>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedNode {
+    id: NodeId,
+    facts: Vec<SavedFact>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedTyGroup {
+    nodes: Vec<NodeId>,
+    types: Vec<Ty<Db>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedFact {
+    name: String,
+
+    /// `None` for an [`Unregistered`] value.
+    #[serde(rename = "type")]
+    ty: Option<String>,
+
+    value: serde_json::Value,
+}
+
+impl Db {
+    /// Write the database as JSON, along with the type groups produced by the
+    /// solver. Values whose types aren't in `registry` are saved as
+    /// [`Unregistered`] values.
+    pub fn save(
+        &self,
+        registry: &Registry,
+        ty_groups: &TyGroups<Db>,
+        w: impl Write,
+    ) -> serde_json::Result<()> {
+        let mut derived = self
+            .derived
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+
+        derived.sort();
+
+        let nodes = self
+            .nodes()
+            .map(|node| {
+                Ok(SavedNode {
+                    id: node,
+                    facts: self
+                        .iter(node)
+                        .map(|fact| {
+                            let (ty, value) = match registry.encode(fact.value()) {
+                                Some((ty, value)) => (Some(ty.to_string()), value),
+                                None => (
+                                    None,
+                                    serde_json::to_value(Unregistered {
+                                        display: fact.value().display(self),
+                                        code: fact.value().is_code(),
+                                    })?,
+                                ),
+                            };
+
+                            Ok(SavedFact {
+                                name: fact.name().to_string(),
+                                ty,
+                                value,
+                            })
+                        })
+                        .collect::<serde_json::Result<_>>()?,
+                })
+            })
+            .collect::<serde_json::Result<Vec<_>>>()?;
+
+        let ty_groups = ty_groups
+            .groups()
+            .map(|(index, tys)| SavedTyGroup {
+                nodes: ty_groups.nodes_in_group(index).collect(),
+                types: tys.to_vec(),
+            })
+            .collect();
+
+        serde_json::to_writer_pretty(
+            w,
+            &SavedDb {
+                next_id: self.next_id,
+                derived,
+                nodes,
+                ty_groups,
+            },
+        )
+    }
+
+    /// Read a database and its type groups written by [`Db::save`].
+    pub fn load(registry: &Registry, r: impl Read) -> serde_json::Result<(Db, TyGroups<Db>)> {
+        let saved = serde_json::from_reader::<_, SavedDb>(r)?;
+
+        let mut db = Db::new();
+        db.next_id = saved.next_id;
+        db.derived = saved.derived.into_iter().map(Rc::from).collect();
+
+        for node in saved.nodes {
+            for fact in node.facts {
+                let value = match fact.ty {
+                    Some(ty) => registry.decode(&ty, fact.value)?,
+                    None => Rc::new(serde_json::from_value::<Unregistered>(fact.value)?),
+                };
+
+                db.fact(
+                    node.id,
+                    Fact {
+                        name: Rc::from(fact.name),
+                        value,
+                    },
+                );
+            }
+        }
+
+        let mut ty_groups = TyGroups::default();
+        for group in saved.ty_groups {
+            let mut tys = group.types.into_iter();
+
+            let Some(ty) = tys.next() else {
+                return Err(serde::de::Error::custom("type group has no types"));
+            };
+
+            let index = ty_groups.insert_group(ty);
+            ty_groups.tys_at_mut(index).extend(tys);
+
+            for node in group.nodes {
+                ty_groups.assign_node_to_index(node, index);
+            }
+        }
+
+        Ok((db, ty_groups))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Filter, query::query};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn test_save_and_load() {
+        #[derive(Debug, PartialEq, Eq)]
+        struct Opaque;
+
+        impl FactValue for Opaque {
+            fn display(&self, _db: &Db) -> Option<String> {
+                Some(String::from("Opaque(..)"))
+            }
+        }
+
+        let mut db = Db::new();
+        let number = db.node();
+        let value = db.node();
+
+        let span = Span {
+            path: "test".into(),
+            range: 0..1,
+            start_line_col: (1, 1),
+            end_line_col: (1, 2),
+        };
+
+        db.fact(number, Fact::new("source", Source(String::from("Number"))));
+        db.fact(number, Fact::new("typeDefinition", ()));
+        db.fact(value, Fact::new("source", Source(String::from("1"))));
+        db.fact(value, Fact::new("span", span.clone()));
+        db.fact(value, Fact::new("number", number));
+        db.fact(value, Fact::new("opaque", Opaque));
+        db.fact(
+            value,
+            Fact::new(
+                "type",
+                Ty::<Db>::Named {
+                    name: number,
+                    parameters: BTreeMap::new(),
+                },
+            ),
+        );
+        db.fact(
+            value,
+            Fact::new(
+                "substitutions",
+                Substitutions::<Db>(BTreeMap::from([(number, Ty::unit())])),
+            ),
+        );
+
+        let mut ty_groups = TyGroups::default();
+        let index = ty_groups.insert_group(Ty::Named {
+            name: number,
+            parameters: BTreeMap::new(),
+        });
+        ty_groups.assign_node_to_index(value, index);
+
+        let registry = Registry::new();
+
+        let mut saved = Vec::new();
+        db.save(&registry, &ty_groups, &mut saved).unwrap();
+
+        let (loaded, loaded_ty_groups) = Db::load(&registry, saved.as_slice()).unwrap();
+
+        let write = |db: &Db| {
+            let mut output = Vec::new();
+            db.write(&[] as &[Filter<'_>], "  ", &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(write(&loaded), write(&db));
+
+        let mut saved_again = Vec::new();
+        loaded
+            .save(&registry, &loaded_ty_groups, &mut saved_again)
+            .unwrap();
+        assert_eq!(saved_again, saved);
+
+        let terms = vec![
+            "node.number(n)".parse().unwrap(),
+            "node.span(s)".parse().unwrap(),
+        ];
+        let results = query(&terms, HashMap::new(), &loaded, |_, _, _| false).collect::<Vec<_>>();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["s"].downcast_ref::<Span>(), Some(&span));
+        assert_eq!(results[0]["n"].downcast_ref::<NodeId>(), Some(&number));
+
+        assert!(loaded.get::<Unregistered>(value, "opaque").is_some());
+    }
+}
@@ -0,0 +1,363 @@
+use crate::{Db, FactValue, NodeId, Schema, Source, Span, TraitOutput, ValueType};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+use visualizer::{Substitutions, Ty};
+
+/// A relation to export for Soufflé, with a row for each fact. The first
+/// column is always the node the fact is on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relation {
+    pub name: String,
+    pub columns: Vec<(&'static str, &'static str)>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// How values of each type are flattened into columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    Unit,
+    Node,
+    Span,
+    Text,
+    Type,
+    Number,
+    Substitutions,
+    TraitOutput,
+
+    /// Any other value, written as the text it's displayed with.
+    Other,
+}
+
+const TYPE_COLUMNS: [(&str, &str); 4] = [
+    ("kind", "symbol"),
+    ("head", "number"),
+    ("encoding", "symbol"),
+    ("display", "symbol"),
+];
+
+impl Layout {
+    fn of(ty: ValueType) -> Self {
+        if ty == ValueType::of::<()>() {
+            Layout::Unit
+        } else if ty == ValueType::of::<NodeId>() {
+            Layout::Node
+        } else if ty == ValueType::of::<Span>() {
+            Layout::Span
+        } else if ty == ValueType::of::<Source>() {
+            Layout::Text
+        } else if ty == ValueType::of::<Ty<Db>>() {
+            Layout::Type
+        } else if ty == ValueType::of::<usize>() {
+            Layout::Number
+        } else if ty == ValueType::of::<Substitutions<Db>>() {
+            Layout::Substitutions
+        } else if ty == ValueType::of::<TraitOutput>() {
+            Layout::TraitOutput
+        } else {
+            Layout::Other
+        }
+    }
+
+    fn columns(self) -> Vec<(&'static str, &'static str)> {
+        match self {
+            Layout::Unit => Vec::new(),
+            Layout::Node | Layout::Number => vec![("value", "number")],
+            Layout::Span => vec![
+                ("path", "symbol"),
+                ("start", "number"),
+                ("end", "number"),
+                ("startLine", "number"),
+                ("startColumn", "number"),
+                ("endLine", "number"),
+                ("endColumn", "number"),
+            ],
+            Layout::Text | Layout::Other => vec![("value", "symbol")],
+            Layout::Type => TYPE_COLUMNS.to_vec(),
+            Layout::Substitutions | Layout::TraitOutput => [("parameter", "number")]
+                .into_iter()
+                .chain(TYPE_COLUMNS)
+                .collect(),
+        }
+    }
+
+    /// The rows for a value, not including the node. Most values have one
+    /// row, but substitutions have one for each parameter.
+    fn rows(self, value: &dyn FactValue, db: &Db) -> Vec<Vec<String>> {
+        match self {
+            Layout::Unit => vec![Vec::new()],
+            Layout::Node => vec![vec![value.downcast_ref::<NodeId>().unwrap().0.to_string()]],
+            Layout::Span => {
+                let span = value.downcast_ref::<Span>().unwrap();
+
+                vec![vec![
+                    escape(&span.path),
+                    span.range.start.to_string(),
+                    span.range.end.to_string(),
+                    span.start_line_col.0.to_string(),
+                    span.start_line_col.1.to_string(),
+                    span.end_line_col.0.to_string(),
+                    span.end_line_col.1.to_string(),
+                ]]
+            }
+            Layout::Text => vec![vec![escape(&value.downcast_ref::<Source>().unwrap().0)]],
+            Layout::Type => vec![ty_columns(value.downcast_ref::<Ty<Db>>().unwrap(), db)],
+            Layout::Number => vec![vec![value.downcast_ref::<usize>().unwrap().to_string()]],
+            Layout::Substitutions => value
+                .downcast_ref::<Substitutions<Db>>()
+                .unwrap()
+                .0
+                .iter()
+                .map(|(parameter, ty)| {
+                    let mut row = vec![parameter.0.to_string()];
+                    row.extend(ty_columns(ty, db));
+                    row
+                })
+                .collect(),
+            Layout::TraitOutput => {
+                let output = value.downcast_ref::<TraitOutput>().unwrap();
+
+                let mut row = vec![output.parameter.0.to_string()];
+                row.extend(ty_columns(&output.ty, db));
+                vec![row]
+            }
+            Layout::Other => vec![vec![escape(&value.display(db).unwrap_or_default())]],
+        }
+    }
+}
+
+fn ty_columns(ty: &Ty<Db>, db: &Db) -> Vec<String> {
+    let (kind, head) = match ty {
+        Ty::Unknown(node) => ("unknown", Some(node)),
+        Ty::Of(node) => ("of", Some(node)),
+        Ty::Parameter(node) => ("parameter", Some(node)),
+        Ty::Named { name, .. } => ("named", Some(name)),
+        Ty::Function { .. } => ("function", None),
+        Ty::Tuple { .. } => ("tuple", None),
+    };
+
+    vec![
+        kind.to_string(),
+        head.map_or(-1, |node| node.0 as i64).to_string(),
+        escape(&encode_ty(ty)),
+        escape(&ty.display(db).unwrap_or_default()),
+    ]
+}
+
+/// A stable encoding of a type that refers to nodes by their IDs, like
+/// `function(named(1), of(5) -> tuple())`.
+fn encode_ty(ty: &Ty<Db>) -> String {
+    let list =
+        |tys: &mut dyn Iterator<Item = &Ty<Db>>| tys.map(encode_ty).collect::<Vec<_>>().join(", ");
+
+    match ty {
+        Ty::Unknown(node) => format!("unknown({})", node.0),
+        Ty::Of(node) => format!("of({})", node.0),
+        Ty::Parameter(node) => format!("parameter({})", node.0),
+        Ty::Named { name, parameters } => {
+            let mut s = format!("named({}", name.0);
+            for (parameter, ty) in parameters {
+                s.push_str(&format!(", {}={}", parameter.0, encode_ty(ty)));
+            }
+
+            s.push(')');
+            s
+        }
+        Ty::Function { inputs, output } => format!(
+            "function({}{}-> {})",
+            list(&mut inputs.iter()),
+            if inputs.is_empty() { "" } else { " " },
+            encode_ty(output)
+        ),
+        Ty::Tuple { elements } => format!("tuple({})", list(&mut elements.iter())),
+    }
+}
+
+/// Escape characters that would otherwise end a column or a row.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+impl Relation {
+    /// The relation's `.decl` and `.input` directives.
+    pub fn write_declaration(&self, mut w: impl Write) -> io::Result<()> {
+        let columns = [("node", "number")]
+            .iter()
+            .chain(&self.columns)
+            .map(|(name, ty)| format!("{name}: {ty}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        writeln!(w, ".decl {}({columns})", self.name)?;
+        writeln!(w, ".input {}", self.name)
+    }
+
+    /// The relation's rows, with columns separated by tabs.
+    pub fn write_facts(&self, mut w: impl Write) -> io::Result<()> {
+        for row in &self.rows {
+            writeln!(w, "{}", row.join("\t"))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Db {
+    /// A relation for each fact in `schema`, sorted by name. Facts whose
+    /// values can have more than one type get a relation for each type, like
+    /// `number_unit` and `number_node`. Facts that aren't in the schema
+    /// aren't exported.
+    pub fn souffle_relations(&self, schema: &Schema) -> Vec<Relation> {
+        let mut relations = Vec::new();
+        for (fact, types) in schema.facts() {
+            for &ty in types {
+                let name = if types.len() > 1 {
+                    let suffix = ty
+                        .query_name()
+                        .map_or_else(|| ty.to_string().to_lowercase(), String::from);
+
+                    format!("{fact}_{suffix}")
+                } else {
+                    fact.to_string()
+                };
+
+                let layout = Layout::of(ty);
+
+                let rows = self
+                    .all(fact)
+                    .filter(|(_, value)| ty.is_type_of(value.value()))
+                    .flat_map(|(node, value)| {
+                        layout
+                            .rows(value.value(), self)
+                            .into_iter()
+                            .map(move |row| {
+                                let mut columns = vec![node.0.to_string()];
+                                columns.extend(row);
+                                columns
+                            })
+                    })
+                    .collect();
+
+                relations.push(Relation {
+                    name,
+                    columns: layout.columns(),
+                    rows,
+                });
+            }
+        }
+
+        relations
+    }
+
+    /// Write a `.facts` file for each relation in
+    /// [`souffle_relations`](Db::souffle_relations) to `dir`, along with a
+    /// `wipple.dl` file declaring them.
+    pub fn export_souffle(&self, schema: &Schema, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+
+        let mut declarations = io::BufWriter::new(fs::File::create(dir.join("wipple.dl"))?);
+
+        for relation in self.souffle_relations(schema) {
+            relation.write_declaration(&mut declarations)?;
+
+            let path = dir.join(format!("{}.facts", relation.name));
+            relation.write_facts(io::BufWriter::new(fs::File::create(path)?))?;
+        }
+
+        declarations.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Fact;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_souffle_relations() {
+        let mut db = Db::new();
+        let number = db.node();
+        let value = db.node();
+
+        db.fact(number, Fact::new("source", Source(String::from("Number"))));
+        db.fact(value, Fact::new("source", Source(String::from("1\t2\n"))));
+        db.fact(value, Fact::new("number", ()));
+        db.fact(value, Fact::new("number", number));
+        db.fact(
+            value,
+            Fact::new(
+                "span",
+                Span {
+                    path: "test".into(),
+                    range: 0..1,
+                    start_line_col: (1, 1),
+                    end_line_col: (1, 2),
+                },
+            ),
+        );
+        db.fact(
+            value,
+            Fact::new(
+                "type",
+                Ty::<Db>::Function {
+                    inputs: vec![Ty::Of(value)],
+                    output: Box::new(Ty::Named {
+                        name: number,
+                        parameters: BTreeMap::new(),
+                    }),
+                },
+            ),
+        );
+
+        let mut schema = Schema::new();
+        schema.insert::<Source>(&["source"]);
+        schema.insert::<Span>(&["span"]);
+        schema.insert::<()>(&["number"]);
+        schema.insert::<NodeId>(&["number"]);
+
+        let relations = db
+            .souffle_relations(&schema)
+            .into_iter()
+            .map(|relation| (relation.name.clone(), relation))
+            .collect::<BTreeMap<_, _>>();
+
+        let facts = |name: &str| {
+            let mut output = Vec::new();
+            relations[name].write_facts(&mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let declaration = |name: &str| {
+            let mut output = Vec::new();
+            relations[name].write_declaration(&mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(facts("source"), "0\tNumber\n1\t1\\t2\\n\n");
+        assert_eq!(facts("number_unit"), "1\n");
+        assert_eq!(facts("number_node"), "1\t0\n");
+        assert_eq!(facts("span"), "1\ttest\t0\t1\t1\t1\t1\t2\n");
+        assert_eq!(
+            facts("type"),
+            "1\tfunction\t-1\tfunction(of(1) -> named(0))\t_ -> Number\n"
+        );
+
+        assert_eq!(
+            declaration("number_node"),
+            ".decl number_node(node: number, value: number)\n.input number_node\n"
+        );
+
+        assert_eq!(
+            declaration("type"),
+            ".decl type(node: number, kind: symbol, head: number, encoding: symbol, display: symbol)\n.input type\n"
+        );
+
+        assert!(relations["hidden"].rows.is_empty());
+    }
+}
@@ -1,11 +1,13 @@
 use crate::{Db, FactValue};
+use serde::{Deserialize, Serialize};
 use std::{
     fmt::{Debug, Display},
     ops::Range,
     sync::Arc,
 };
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Span {
     pub path: Arc<str>,
     pub range: Range<usize>,
@@ -49,3 +51,20 @@ impl FactValue for Span {
         Some(self.to_string())
     }
 }
+
+/// Several spans reported together, collected by `spans(x: terms)` in a
+/// query.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Spans(pub Vec<Span>);
+
+impl FactValue for Spans {
+    fn display(&self, _db: &Db) -> Option<String> {
+        Some(
+            self.0
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    }
+}
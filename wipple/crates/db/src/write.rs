@@ -18,7 +18,10 @@ impl Db {
         let nodes = self.filtered_nodes(filter).collect::<Vec<_>>();
 
         for &node in &nodes {
-            let mut facts = self.iter(node).collect::<Vec<_>>();
+            let mut facts = self
+                .iter(node)
+                .filter(|fact| !self.is_derived(fact.name()))
+                .collect::<Vec<_>>();
 
             if facts.iter().copied().any(Fact::is_hidden) {
                 continue;
@@ -104,6 +107,7 @@ impl<'a> visualizer::WriteGraphContext<'a> for Ctx<'a> {
     fn related_nodes(&self, node: Self::Node) -> Vec<(Self::Node, String)> {
         self.0
             .iter(node)
+            .filter(|fact| !self.0.is_derived(fact.name()))
             .filter_map(|fact| {
                 Some((
                     *fact.value().downcast_ref::<NodeId>()?,
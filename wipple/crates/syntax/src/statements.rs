@@ -599,7 +599,7 @@ mod tests {
                         },
                         parameters: vec![Type::Parameterized(ParameterizedType {
                             range: Range::None,
-                            name: TypeName {
+                            name: ParameterizedTypeName {
                                 range: Range::None,
                                 value: String::from("Maybe")
                             },
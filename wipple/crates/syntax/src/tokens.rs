@@ -26,6 +26,7 @@ token!(TypeName, type_name);
 token!(VariantName, variant_name);
 token!(VariableName, variable_name);
 token!(TypeParameterName, type_parameter_name);
+token!(ParameterizedTypeName, parameterized_type_name);
 token!(AttributeName, attribute_name);
 
 pub fn span_into_string(span: pest::Span) -> String {
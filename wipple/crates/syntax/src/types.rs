@@ -1,6 +1,6 @@
 use crate::{
     Parse, Range, Rule, pest_enum,
-    tokens::{TypeName, TypeParameterName},
+    tokens::{ParameterizedTypeName, TypeName, TypeParameterName},
 };
 use pest_ast::FromPest;
 
@@ -82,7 +82,7 @@ pub struct NamedType {
 pub struct ParameterizedType {
     #[pest_ast(outer(with(Range::from)))]
     pub range: Range,
-    pub name: TypeName,
+    pub name: ParameterizedTypeName,
     pub parameters: Vec<ParameterizedTypeElement>,
 }
 
@@ -176,7 +176,7 @@ mod tests {
             Type::parse("Maybe Number").unwrap(),
             Type::Parameterized(ParameterizedType {
                 range: Range::None,
-                name: TypeName {
+                name: ParameterizedTypeName {
                     range: Range::None,
                     value: String::from("Maybe")
                 },
@@ -191,6 +191,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parameterized_type_parameter() {
+        assert_eq!(
+            Type::parse("value Number").unwrap(),
+            Type::Parameterized(ParameterizedType {
+                range: Range::None,
+                name: ParameterizedTypeName {
+                    range: Range::None,
+                    value: String::from("value")
+                },
+                parameters: vec![ParameterizedTypeElement(Type::Named(NamedType {
+                    range: Range::None,
+                    name: TypeName {
+                        range: Range::None,
+                        value: String::from("Number")
+                    },
+                }))],
+            })
+        );
+    }
+
     #[test]
     fn test_block_type() {
         assert_eq!(
@@ -262,7 +283,7 @@ mod tests {
                 inputs: FunctionTypeInputs(vec![
                     Type::Parameterized(ParameterizedType {
                         range: Range::None,
-                        name: TypeName {
+                        name: ParameterizedTypeName {
                             range: Range::None,
                             value: String::from("Maybe")
                         },
@@ -62,7 +62,7 @@ impl<'a> AttributeParser<'a> {
         let mut found = false;
         for attribute in self.attributes {
             if attribute.name.value == name {
-                let node = visitor.node(attribute.range, "attribute");
+                let node = attribute_node(visitor, attribute);
 
                 if attribute.value.is_some() {
                     visitor.fact(node, "extraAttributeValue", ());
@@ -98,7 +98,7 @@ impl<'a> AttributeParser<'a> {
         let mut result = None;
         for attribute in self.attributes {
             if attribute.name.value == name {
-                let node = visitor.node(attribute.range, "attribute");
+                let node = attribute_node(visitor, attribute);
 
                 if let Some(value) = &attribute.value {
                     if result.is_some() {
@@ -121,3 +121,12 @@ impl<'a> AttributeParser<'a> {
         result
     }
 }
+
+fn attribute_node(visitor: &mut Visitor<'_>, attribute: &Attribute) -> NodeId {
+    let node = visitor.node(attribute.range, "attribute");
+
+    // Attributes aren't expressions, so they have no type
+    visitor.fact(node, "untyped", ());
+
+    node
+}
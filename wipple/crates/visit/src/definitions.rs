@@ -15,6 +15,7 @@ pub enum Definition {
 #[derive(Clone)]
 pub struct VariableDefinition {
     pub node: NodeId,
+    pub generalize: bool, // each use instantiates the variable's type
 }
 
 #[derive(Clone)]
@@ -22,7 +23,8 @@ pub struct ConstantDefinition {
     pub node: NodeId,
     pub comments: Comments,
     pub attributes: ConstantAttributes,
-    pub value: std::result::Result<NodeId, NodeId>, // Ok(node) or Err(type signature)
+    pub ty: NodeId,            // the type signature
+    pub value: Option<NodeId>, // set by the first assignment
 }
 
 #[derive(Clone)]
@@ -68,6 +70,15 @@ impl Definition {
         }
     }
 
+    /// Constants, types and traits can only be defined once in each scope,
+    /// unlike variables, which shadow each other.
+    pub fn is_unique(&self) -> bool {
+        matches!(
+            self,
+            Definition::Constant(_) | Definition::Type(_) | Definition::Trait(_)
+        )
+    }
+
     pub fn comments(&self) -> Option<&Comments> {
         match self {
             Definition::Variable(_) => None,
@@ -0,0 +1,69 @@
+use crate::visitor::Visitor;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use wipple_db::{Db, FactValue, NodeId};
+
+/// What a type or trait needs to be used. Type parameters always stand for
+/// complete types, so a kind is determined by how many parameters the
+/// definition declares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Kind {
+    Type { parameters: usize },
+    Trait { parameters: usize },
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (parameters, name) = match *self {
+            Kind::Type { parameters } => (parameters, "type"),
+            Kind::Trait { parameters } => (parameters, "trait"),
+        };
+
+        if parameters > 0 {
+            write!(f, "{}=> ", "_ ".repeat(parameters))?;
+        }
+
+        write!(f, "{name}")
+    }
+}
+
+impl FactValue for Kind {
+    fn display(&self, _db: &Db) -> Option<String> {
+        Some(self.to_string())
+    }
+
+    fn is_code(&self) -> bool {
+        true
+    }
+}
+
+/// The kind of a type or trait's definition, and the kind it's used with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KindMismatch {
+    pub expected: Kind,
+    pub found: Kind,
+}
+
+impl FactValue for KindMismatch {
+    fn display(&self, _db: &Db) -> Option<String> {
+        Some(format!(
+            "expected `{}`, found `{}`",
+            self.expected, self.found
+        ))
+    }
+}
+
+impl Visitor<'_> {
+    /// Record a `kindMismatch` on `node` if the kinds differ, returning whether
+    /// they match.
+    pub fn check_kind(&mut self, node: NodeId, expected: Kind, found: Kind) -> bool {
+        if expected == found {
+            return true;
+        }
+
+        self.fact(node, "kindMismatch", KindMismatch { expected, found });
+
+        false
+    }
+}
@@ -1,11 +1,13 @@
 pub mod attributes;
 pub mod constraints;
 pub mod definitions;
+pub mod kinds;
 pub mod nodes;
+pub mod schema;
 pub mod visitor;
 
 use crate::visitor::{ProgramInfo, Visitor};
-use wipple_syntax::{SourceFile, Statement};
+use wipple_syntax::SourceFile;
 
 pub use crate::visitor::Ctx;
 
@@ -16,11 +18,7 @@ pub fn visit(file: &SourceFile, ctx: Ctx<'_>) -> ProgramInfo {
     visitor.hide(source_file);
 
     if let Some(statements) = &file.statements {
-        for statement in &statements.0 {
-            if !matches!(statement, Statement::Empty(_)) {
-                visitor.child(statement, source_file, "statementInSourceFile");
-            }
-        }
+        visitor.statements(&statements.0, source_file, "statementInSourceFile");
     }
 
     visitor.finish()
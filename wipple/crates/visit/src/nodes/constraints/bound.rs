@@ -1,5 +1,6 @@
 use crate::{
     definitions::Definition,
+    kinds::Kind,
     visitor::{Visit, Visitor},
 };
 use std::collections::BTreeMap;
@@ -33,9 +34,21 @@ impl Visit for BoundConstraint {
         let parameters = self
             .parameters
             .iter()
-            .map(|ty| visitor.child(ty, id, "parameterInBound"));
+            .map(|ty| visitor.child(ty, id, "parameterInBound"))
+            .collect::<Vec<_>>();
+
+        if !visitor.check_kind(
+            id,
+            Kind::Trait {
+                parameters: trait_parameters.len(),
+            },
+            Kind::Trait {
+                parameters: parameters.len(),
+            },
+        ) {
+            return;
+        }
 
-        // TODO: Ensure `parameters` has the right length
         let substitutions = trait_parameters
             .into_iter()
             .zip(parameters)
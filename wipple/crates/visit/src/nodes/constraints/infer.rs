@@ -1,7 +1,9 @@
-use crate::visitor::{Visit, Visitor};
+use crate::{
+    definitions::Definition,
+    visitor::{Visit, Visitor},
+};
 use wipple_db::NodeId;
 use wipple_syntax::{InferConstraint, Range};
-// TODO
 
 impl Visit for InferConstraint {
     fn name(&self) -> &'static str {
@@ -13,6 +15,20 @@ impl Visit for InferConstraint {
     }
 
     fn visit(&self, id: NodeId, visitor: &mut Visitor<'_>) {
-        todo!()
+        let Some(parameter) =
+            visitor.resolve_name(&self.parameter.value, id, |definition| match definition {
+                Definition::TypeParameter(definition) => {
+                    Some((definition.node, "parameterInInferConstraint"))
+                }
+                _ => None,
+            })
+        else {
+            visitor.fact(id, "unresolvedParameterInInferConstraint", ());
+            return;
+        };
+
+        // The parameter is determined by the instance rather than used to
+        // select one
+        visitor.fact(parameter, "inferred", ());
     }
 }
@@ -3,7 +3,7 @@ use crate::{
     visitor::{Visit, Visitor},
 };
 use wipple_db::NodeId;
-use wipple_syntax::{BlockExpression, Range, Statement};
+use wipple_syntax::{BlockExpression, Range};
 
 impl Visit for BlockExpression {
     fn name(&self) -> &'static str {
@@ -17,13 +17,7 @@ impl Visit for BlockExpression {
     fn visit(&self, id: NodeId, visitor: &mut Visitor<'_>) {
         visitor.push_scope(id);
 
-        let statements = self
-            .statements
-            .0
-            .iter()
-            .filter(|statement| !matches!(statement, Statement::Empty(_)))
-            .map(|statement| visitor.child(statement, id, "blockStatement"))
-            .collect::<Vec<_>>();
+        let statements = visitor.statements(&self.statements.0, id, "blockStatement");
 
         visitor.pop_scope();
 
@@ -2,7 +2,7 @@ use crate::{
     definitions::Definition,
     visitor::{Visit, Visitor},
 };
-use visualizer::{Constraint, Instantiation, Substitutions};
+use visualizer::{Bound, Constraint, Instantiation, Substitutions};
 use wipple_db::NodeId;
 use wipple_syntax::{Range, TraitExpression};
 
@@ -24,12 +24,13 @@ impl Visit for TraitExpression {
             });
 
         if let Some(definition) = definition {
-            visitor.constraint(Constraint::Instantiation(Instantiation {
+            // The bound instantiates the trait's type at `id` once resolved
+            visitor.constraint(Constraint::Bound(Bound(Instantiation {
                 source: id,
                 node: id,
                 definition,
                 substitutions: Substitutions::replace_all(),
-            }));
+            })));
         } else {
             visitor.fact(id, "unresolvedTraitName", ());
         }
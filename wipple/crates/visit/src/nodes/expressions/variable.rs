@@ -17,22 +17,35 @@ impl Visit for VariableExpression {
 
     fn visit(&self, id: NodeId, visitor: &mut Visitor<'_>) {
         let constraint =
-            visitor.resolve_name(&self.variable.value, id, |definition| match definition {
-                Definition::Variable(definition) => Some((
-                    Constraint::Ty(id, Ty::Of(definition.node)),
-                    "resolvedVariableName",
-                )),
-                Definition::Constant(definition) => Some((
-                    Constraint::Instantiation(Instantiation {
-                        source: id,
-                        node: id,
-                        definition: definition.node,
-                        substitutions: Substitutions::replace_all(),
-                    }),
-                    "resolvedConstantName",
-                )),
-                _ => None,
-            });
+            visitor.resolve_name(
+                &self.variable.value,
+                id,
+                move |definition| match definition {
+                    Definition::Variable(definition) if definition.generalize => Some((
+                        Constraint::Instantiation(Instantiation {
+                            source: id,
+                            node: id,
+                            definition: definition.node,
+                            substitutions: Substitutions::replace_all(),
+                        }),
+                        "resolvedVariableName",
+                    )),
+                    Definition::Variable(definition) => Some((
+                        Constraint::Ty(id, Ty::Of(definition.node)),
+                        "resolvedVariableName",
+                    )),
+                    Definition::Constant(definition) => Some((
+                        Constraint::Instantiation(Instantiation {
+                            source: id,
+                            node: id,
+                            definition: definition.node,
+                            substitutions: Substitutions::replace_all(),
+                        }),
+                        "resolvedConstantName",
+                    )),
+                    _ => None,
+                },
+            );
 
         if let Some(constraint) = constraint {
             visitor.constraint(constraint);
@@ -17,13 +17,17 @@ impl Visit for SetPattern {
 
     fn visit(&self, id: NodeId, visitor: &mut Visitor<'_>) {
         let constraint =
-            visitor.resolve_name(&self.variable.value, id, |definition| match definition {
-                Definition::Variable(definition) => Some((
-                    Constraint::Ty(id, Ty::Of(definition.node)),
-                    "resolvedVariableName",
-                )),
-                _ => None,
-            });
+            visitor.resolve_name(
+                &self.variable.value,
+                id,
+                move |definition| match definition {
+                    Definition::Variable(definition) => Some((
+                        Constraint::Ty(id, Ty::Of(definition.node)),
+                        "resolvedVariableName",
+                    )),
+                    _ => None,
+                },
+            );
 
         if let Some(constraint) = constraint {
             visitor.constraint(constraint);
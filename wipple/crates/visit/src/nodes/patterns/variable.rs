@@ -17,7 +17,10 @@ impl Visit for VariablePattern {
     fn visit(&self, id: NodeId, visitor: &mut Visitor<'_>) {
         visitor.define_name(
             &self.variable.value,
-            Definition::Variable(VariableDefinition { node: id }),
+            Definition::Variable(VariableDefinition {
+                node: id,
+                generalize: false,
+            }),
         );
     }
 }
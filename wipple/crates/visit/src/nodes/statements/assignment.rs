@@ -20,24 +20,34 @@ impl Visit for AssignmentStatement {
 
         // Try assigning to an existing constant if possible
         if let Pattern::Variable(pattern) = &self.pattern {
-            if let Some((definition, constraint)) =
+            if let Some((definition, previous, constraint)) =
                 visitor.peek_name(&pattern.variable.value, |definition| match definition {
                     Definition::Constant(definition) => {
-                        let ty = match definition.value {
-                            Ok(_) => todo!(),
-                            Err(ty) => ty,
-                        };
-
-                        definition.value = Err(value);
+                        let previous = definition.value;
+                        definition.value.get_or_insert(value);
 
                         // Ensure the value is assignable to the constant's
-                        // type
-                        Some((definition.node, Constraint::Ty(value, Ty::Of(ty))))
+                        // type, even if it's already been assigned
+                        Some((
+                            definition.node,
+                            previous,
+                            Constraint::Ty(value, Ty::Of(definition.ty)),
+                        ))
                     }
                     _ => None,
                 })
             {
                 visitor.fact(id, "assignmentToConstant", definition);
+
+                // Assignments are hidden, so the assignment is reported on a
+                // separate node for the constant's name
+                if let Some(previous) = previous {
+                    let name = visitor.node(self.pattern.range(), "constantName");
+                    visitor.relation(name, id, "assignmentPattern");
+                    visitor.fact(name, "untyped", ());
+                    visitor.fact(name, "constantAlreadyAssigned", previous);
+                }
+
                 visitor.constraint(constraint);
                 return;
             }
@@ -46,6 +56,21 @@ impl Visit for AssignmentStatement {
         let pattern = visitor.child(&self.pattern, id, "assignmentPattern");
 
         visitor.constraint(Constraint::Ty(value, Ty::Of(pattern)));
+
+        // Variables assigned directly may be used at different types, like
+        // constants
+        if let Pattern::Variable(variable) = &self.pattern {
+            visitor.peek_name(&variable.variable.value, |definition| match definition {
+                Definition::Variable(definition) if definition.node == pattern => {
+                    definition.generalize = true;
+                    Some(())
+                }
+                _ => None,
+            });
+
+            let environment = visitor.shared_variables();
+            visitor.constraint(Constraint::Generalize(pattern, environment));
+        }
         visitor.fact(id, "assignmentToPattern", ());
     }
 
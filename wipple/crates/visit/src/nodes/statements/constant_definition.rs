@@ -16,16 +16,53 @@ impl Visit for ConstantDefinitionStatement {
         self.name.range
     }
 
+    fn declare(&self, id: NodeId, visitor: &mut Visitor<'_>) {
+        let attributes =
+            ConstantAttributes::parse(visitor, &mut AttributeParser::new(id, &self.attributes));
+
+        // The type is visited along with the rest of the constant, but
+        // assignments may refer to it before then
+        let ty = visitor.node(
+            self.constraints.r#type.range(),
+            self.constraints.r#type.name(),
+        );
+
+        let previous = visitor.define_name(
+            &self.name.value,
+            Definition::Constant(ConstantDefinition {
+                node: id,
+                comments: self.comments.clone(),
+                attributes,
+                ty,
+                value: None,
+            }),
+        );
+
+        // Constant definitions are hidden, so the duplicate is reported on a
+        // separate node for its name
+        if let Some(previous) = previous {
+            let name = visitor.node(self.name.range, "constantName");
+            visitor.relation(name, id, "nameInConstantDefinition");
+            visitor.fact(name, "untyped", ());
+
+            let span = visitor.span(previous.source());
+            visitor.fact(name, "duplicateConstant", span);
+        }
+    }
+
     fn visit(&self, id: NodeId, visitor: &mut Visitor<'_>) {
-        visitor.with_definition(id, |visitor| {
-            let attributes =
-                ConstantAttributes::parse(visitor, &mut AttributeParser::new(id, &self.attributes));
+        let Definition::Constant(ConstantDefinition { ty, .. }) =
+            visitor.declared_definition(&self.name.value, id)
+        else {
+            unreachable!();
+        };
 
+        visitor.with_definition(id, |visitor| {
             visitor.push_scope(id);
 
             visitor.current_definition().implicit_type_parameters = true;
 
-            let ty = visitor.child(&self.constraints.r#type, id, "typeInConstantDefinition");
+            visitor.visit_child(&self.constraints.r#type, ty, id, "typeInConstantDefinition");
 
             visitor
                 .current_definition()
@@ -39,16 +76,6 @@ impl Visit for ConstantDefinitionStatement {
 
             visitor.pop_scope();
 
-            visitor.define_name(
-                &self.name.value,
-                Definition::Constant(ConstantDefinition {
-                    node: id,
-                    comments: self.comments.clone(),
-                    attributes,
-                    value: Err(ty),
-                }),
-            );
-
             visitor.constraint(Constraint::Ty(id, Ty::Of(ty)));
         });
     }
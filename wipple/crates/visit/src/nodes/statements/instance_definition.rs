@@ -1,11 +1,12 @@
 use crate::{
     attributes::{AttributeParser, InstanceAttributes},
     definitions::{Definition, InstanceDefinition},
+    kinds::Kind,
     visitor::{Visit, Visitor},
 };
 use std::collections::BTreeMap;
 use visualizer::{Constraint, Instantiation, Substitutions, Ty};
-use wipple_db::NodeId;
+use wipple_db::{NodeId, Source};
 use wipple_syntax::{Constraints, InstanceDefinitionStatement, Range};
 
 impl Visit for InstanceDefinitionStatement {
@@ -39,8 +40,44 @@ impl Visit for InstanceDefinitionStatement {
                 return;
             };
 
+            // Without a type for each of the trait's parameters, the instance
+            // can't be matched against bounds, so it isn't defined at all
+            if !visitor.check_kind(
+                id,
+                Kind::Trait {
+                    parameters: trait_parameters.len(),
+                },
+                Kind::Trait {
+                    parameters: self.constraints.bound.parameters.len(),
+                },
+            ) {
+                visitor.fact(id, "untyped", ());
+                visitor.pop_scope();
+                return;
+            }
+
             visitor.fact(trait_node, "instance", id);
 
+            if attributes.default {
+                visitor.fact(id, "defaultInstance", ());
+            }
+
+            if attributes.error {
+                visitor.fact(id, "errorInstance", ());
+            }
+
+            if !self.comments.0.is_empty() {
+                let comments = self
+                    .comments
+                    .0
+                    .iter()
+                    .map(|comment| comment.value.trim())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+
+                visitor.fact(id, "comments", Source(comments));
+            }
+
             visitor.current_definition().implicit_type_parameters = true;
 
             let parameters = self
@@ -48,9 +85,9 @@ impl Visit for InstanceDefinitionStatement {
                 .bound
                 .parameters
                 .iter()
-                .map(|ty| visitor.child(ty, id, "parameterInInstanceDefinition"));
+                .map(|ty| visitor.child(ty, id, "parameterInInstanceDefinition"))
+                .collect::<Vec<_>>();
 
-            // TODO: Ensure `parameters` has the right length
             let substitutions = Substitutions::from(
                 trait_parameters
                     .into_iter()
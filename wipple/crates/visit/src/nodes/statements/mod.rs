@@ -5,3 +5,33 @@ mod expression;
 mod instance_definition;
 mod trait_definition;
 mod type_definition;
+
+use crate::visitor::Visitor;
+use wipple_db::NodeId;
+use wipple_syntax::TypeParameters;
+
+/// Create the nodes for a type or trait's parameters while declaring it, so
+/// their kind is known wherever the type or trait is used.
+fn declare_type_parameters(
+    visitor: &mut Visitor<'_>,
+    id: NodeId,
+    parameters: Option<&TypeParameters>,
+    relation: &'static str,
+) -> Vec<NodeId> {
+    parameters
+        .map(|parameters| parameters.0.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .map(|parameter| {
+            let node = visitor.node(parameter.range, "parameterName");
+            visitor.relation(node, id, relation);
+            visitor.hide(node);
+
+            // Type parameters are defined along with their type or trait, so
+            // they have no type of their own
+            visitor.fact(node, "untyped", ());
+
+            node
+        })
+        .collect()
+}
@@ -1,9 +1,11 @@
 use crate::{
     attributes::{AttributeParser, TraitAttributes},
     definitions::{Definition, TraitDefinition, TypeParameterDefinition},
+    kinds::Kind,
+    nodes::statements::declare_type_parameters,
     visitor::{Visit, Visitor},
 };
-use visualizer::{Bound, Constraint, Instantiation, Substitutions, Ty};
+use visualizer::{Constraint, Ty};
 use wipple_db::NodeId;
 use wipple_syntax::{Constraints, Range, TraitDefinitionStatement};
 
@@ -16,38 +18,59 @@ impl Visit for TraitDefinitionStatement {
         self.name.range
     }
 
+    fn declare(&self, id: NodeId, visitor: &mut Visitor<'_>) {
+        let attributes =
+            TraitAttributes::parse(visitor, &mut AttributeParser::new(id, &self.attributes));
+
+        let parameters = declare_type_parameters(
+            visitor,
+            id,
+            self.parameters.as_ref(),
+            "parameterInTraitDefinition",
+        );
+
+        visitor.define_name(
+            &self.name.value,
+            Definition::Trait(TraitDefinition {
+                node: id,
+                comments: self.comments.clone(),
+                attributes,
+                parameters,
+            }),
+        );
+    }
+
     fn visit(&self, id: NodeId, visitor: &mut Visitor<'_>) {
-        visitor.with_definition(id, |visitor| {
-            let attributes =
-                TraitAttributes::parse(visitor, &mut AttributeParser::new(id, &self.attributes));
+        let Definition::Trait(definition) = visitor.declared_definition(&self.name.value, id)
+        else {
+            unreachable!();
+        };
 
+        visitor.with_definition(id, |visitor| {
             visitor.push_scope(id);
 
-            let parameters = self
+            let names = self
                 .parameters
                 .as_ref()
                 .map(|parameters| parameters.0.as_slice())
-                .unwrap_or_default()
-                .iter()
-                .map(|parameter| {
-                    let node = visitor.child(
-                        &(parameter.range, "parameterName"),
-                        id,
-                        "parameterInTraitDefinition",
-                    );
-
-                    visitor.hide(node);
+                .unwrap_or_default();
 
-                    visitor.define_name(
-                        &parameter.value,
-                        Definition::TypeParameter(TypeParameterDefinition { node }),
-                    );
+            for (parameter, &node) in names.iter().zip(&definition.parameters) {
+                visitor.define_name(
+                    &parameter.value,
+                    Definition::TypeParameter(TypeParameterDefinition { node }),
+                );
 
-                    visitor.constraint(Constraint::Ty(node, Ty::Parameter(node)));
+                visitor.constraint(Constraint::Ty(node, Ty::Parameter(node)));
+            }
 
-                    node
-                })
-                .collect::<Vec<_>>();
+            visitor.fact(
+                id,
+                "kind",
+                Kind::Trait {
+                    parameters: definition.parameters.len(),
+                },
+            );
 
             let ty = visitor.child(&self.constraints.r#type, id, "typeInTraitDefinition");
 
@@ -55,15 +78,6 @@ impl Visit for TraitDefinitionStatement {
                 .current_definition()
                 .lazy_constraint(move |node| Constraint::Ty(node, Ty::Of(id)));
 
-            visitor.current_definition().lazy_constraint(move |node| {
-                Constraint::Bound(Bound(Instantiation {
-                    source: node,
-                    node,
-                    definition: id,
-                    substitutions: Substitutions::replace_all(),
-                }))
-            });
-
             if let Some(Constraints(constraints)) = &self.constraints.constraints {
                 for constraint in constraints {
                     visitor.child(constraint, id, "constraintInTraitDefinition");
@@ -72,16 +86,6 @@ impl Visit for TraitDefinitionStatement {
 
             visitor.pop_scope();
 
-            visitor.define_name(
-                &self.name.value,
-                Definition::Trait(TraitDefinition {
-                    node: id,
-                    comments: self.comments.clone(),
-                    attributes,
-                    parameters,
-                }),
-            );
-
             visitor.constraint(Constraint::Ty(id, Ty::Of(ty)));
         });
     }
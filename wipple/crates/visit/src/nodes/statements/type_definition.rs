@@ -1,6 +1,8 @@
 use crate::{
     attributes::{AttributeParser, TypeAttributes},
     definitions::{Definition, TypeDefinition, TypeParameterDefinition},
+    kinds::Kind,
+    nodes::statements::declare_type_parameters,
     visitor::{Visit, Visitor},
 };
 use std::collections::BTreeMap;
@@ -17,36 +19,56 @@ impl Visit for TypeDefinitionStatement {
         self.name.range
     }
 
+    fn declare(&self, id: NodeId, visitor: &mut Visitor<'_>) {
+        let attributes =
+            TypeAttributes::parse(visitor, &mut AttributeParser::new(id, &self.attributes));
+
+        let parameters = declare_type_parameters(
+            visitor,
+            id,
+            self.parameters.as_ref(),
+            "parameterInTypeDefinition",
+        );
+
+        visitor.define_name(
+            &self.name.value,
+            Definition::Type(TypeDefinition {
+                node: id,
+                comments: self.comments.clone(),
+                attributes,
+                parameters,
+            }),
+        );
+    }
+
     fn visit(&self, id: NodeId, visitor: &mut Visitor<'_>) {
-        visitor.with_definition(id, |visitor| {
-            let attributes =
-                TypeAttributes::parse(visitor, &mut AttributeParser::new(id, &self.attributes));
+        let Definition::Type(definition) = visitor.declared_definition(&self.name.value, id) else {
+            unreachable!();
+        };
 
+        visitor.with_definition(id, |visitor| {
             visitor.push_scope(id);
 
-            let parameters = self
+            let names = self
                 .parameters
                 .as_ref()
                 .map(|parameters| parameters.0.as_slice())
-                .unwrap_or_default()
-                .iter()
-                .map(|parameter| {
-                    let node = visitor.child(
-                        &(parameter.range, "parameterName"),
-                        id,
-                        "parameterInTypeDefinition",
-                    );
-
-                    visitor.hide(node);
+                .unwrap_or_default();
 
-                    visitor.define_name(
-                        &parameter.value,
-                        Definition::TypeParameter(TypeParameterDefinition { node }),
-                    );
+            for (parameter, &node) in names.iter().zip(&definition.parameters) {
+                visitor.define_name(
+                    &parameter.value,
+                    Definition::TypeParameter(TypeParameterDefinition { node }),
+                );
+            }
 
-                    node
-                })
-                .collect::<Vec<_>>();
+            visitor.fact(
+                id,
+                "kind",
+                Kind::Type {
+                    parameters: definition.parameters.len(),
+                },
+            );
 
             visitor.current_definition().lazy_constraint(move |node| {
                 Constraint::Ty(
@@ -61,16 +83,6 @@ impl Visit for TypeDefinitionStatement {
             // Types don't have additional constraints
 
             visitor.pop_scope();
-
-            visitor.define_name(
-                &self.name.value,
-                Definition::Type(TypeDefinition {
-                    node: id,
-                    comments: self.comments.clone(),
-                    attributes,
-                    parameters,
-                }),
-            );
         })
     }
 }
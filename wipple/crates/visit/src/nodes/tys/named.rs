@@ -1,5 +1,6 @@
 use crate::{
     definitions::Definition,
+    kinds::Kind,
     visitor::{Visit, Visitor},
 };
 use std::collections::BTreeMap;
@@ -17,9 +18,12 @@ impl Visit for NamedType {
     }
 
     fn visit(&self, id: NodeId, visitor: &mut Visitor<'_>) {
-        let Some(type_node) =
+        let Some((type_node, type_parameters)) =
             visitor.resolve_name(&self.name.value, id, |definition| match definition {
-                Definition::Type(definition) => Some((definition.node, "resolvedNamedType")),
+                Definition::Type(definition) => Some((
+                    (definition.node, definition.parameters.len()),
+                    "resolvedNamedType",
+                )),
                 _ => None,
             })
         else {
@@ -27,7 +31,15 @@ impl Visit for NamedType {
             return;
         };
 
-        // TODO: Ensure `definition.parameters` is empty
+        if !visitor.check_kind(
+            id,
+            Kind::Type {
+                parameters: type_parameters,
+            },
+            Kind::Type { parameters: 0 },
+        ) {
+            return;
+        }
 
         visitor.constraint(Constraint::Ty(
             id,
@@ -1,5 +1,6 @@
 use crate::{
     definitions::Definition,
+    kinds::Kind,
     visitor::{Visit, Visitor},
 };
 use std::collections::BTreeMap;
@@ -17,12 +18,17 @@ impl Visit for ParameterizedType {
     }
 
     fn visit(&self, id: NodeId, visitor: &mut Visitor<'_>) {
+        // A type parameter stands for a complete type, so it resolves here
+        // only to report that it can't take parameters
         let Some((type_node, type_parameters)) =
             visitor.resolve_name(&self.name.value, id, |definition| match definition {
                 Definition::Type(definition) => Some((
                     (definition.node, definition.parameters.clone()),
                     "resolvedParameterizedType",
                 )),
+                Definition::TypeParameter(definition) => {
+                    Some(((definition.node, Vec::new()), "resolvedParameterizedType"))
+                }
                 _ => None,
             })
         else {
@@ -38,7 +44,17 @@ impl Visit for ParameterizedType {
             })
             .collect::<Vec<_>>();
 
-        // TODO: Ensure `parameters` has the right length
+        if !visitor.check_kind(
+            id,
+            Kind::Type {
+                parameters: type_parameters.len(),
+            },
+            Kind::Type {
+                parameters: parameters.len(),
+            },
+        ) {
+            return;
+        }
 
         visitor.constraint(Constraint::Ty(
             id,
@@ -0,0 +1,165 @@
+use crate::kinds::{Kind, KindMismatch};
+use visualizer::Substitutions;
+use wipple_db::{Db, LazyConstraints, NodeId, Registry, Schema, Source, Span};
+
+/// Each node's relation to the node it was written in. `rules.txt` derives
+/// `parent` from these.
+pub const CHILD_RELATIONS: &[&str] = &[
+    "annotatedPattern",
+    "annotatedValue",
+    "assignmentPattern",
+    "assignmentValue",
+    "blockStatement",
+    "constraintInConstantDefinition",
+    "constraintInInstanceDefinition",
+    "constraintInTraitDefinition",
+    "expressionInExpressionStatement",
+    "functionInApply",
+    "functionInFunctionCall",
+    "functionInput",
+    "functionOutput",
+    "functionTypeInput",
+    "functionTypeOutput",
+    "inputInApply",
+    "inputInFunctionCall",
+    "nameInConstantDefinition",
+    "numberInUnitCall",
+    "parameterInBound",
+    "parameterInInstanceDefinition",
+    "parameterInParameterizedType",
+    "parameterInTraitDefinition",
+    "parameterInTypeDefinition",
+    "statementInSourceFile",
+    "tuplePatternElement",
+    "typeInAnnotatedPattern",
+    "typeInAnnotatedValue",
+    "typeInConstantDefinition",
+    "typeInTraitDefinition",
+    "unitInUnitCall",
+    "valueInInstanceDefinition",
+];
+
+/// Every fact the visitor can add, along with the facts added while solving.
+pub fn schema() -> Schema {
+    let mut schema = Schema::new();
+
+    schema.insert::<Span>(&["span", "duplicateConstant"]);
+    schema.insert::<Source>(&["source", "comments"]);
+    schema.insert::<LazyConstraints>(&["constraints"]);
+    schema.insert::<Substitutions<Db>>(&["substitutions"]);
+    schema.insert::<Kind>(&["kind"]);
+    schema.insert::<KindMismatch>(&["kindMismatch"]);
+
+    // The kind of syntax each node was created from
+    schema.insert::<()>(&[
+        "annotate",
+        "annotatePattern",
+        "as",
+        "assignment",
+        "attribute",
+        "binary",
+        "block",
+        "blockType",
+        "boundConstraint",
+        "collection",
+        "constantDefinition",
+        "constantName",
+        "defaultConstraint",
+        "destructurePattern",
+        "do",
+        "emptyStatement",
+        "expressionStatement",
+        "formattedText",
+        "function",
+        "functionCall",
+        "functionType",
+        "inferConstraint",
+        "instanceDefinition",
+        "intrinsic",
+        "is",
+        "namedType",
+        "number",
+        "numberPattern",
+        "orPattern",
+        "parameterName",
+        "parameterType",
+        "parameterizedType",
+        "placeholder",
+        "placeholderType",
+        "setPattern",
+        "sourceFile",
+        "structure",
+        "text",
+        "textPattern",
+        "trait",
+        "traitDefinition",
+        "tuple",
+        "tuplePattern",
+        "tupleType",
+        "typeDefinition",
+        "unit",
+        "unitName",
+        "unitPattern",
+        "unitType",
+        "variable",
+        "variablePattern",
+        "variantPattern",
+        "when",
+        "wildcardPattern",
+    ]);
+
+    schema.insert::<NodeId>(CHILD_RELATIONS);
+
+    // Names resolved to their definitions, and other links between nodes
+    schema.insert::<NodeId>(&[
+        "assignmentToConstant",
+        "constantAlreadyAssigned",
+        "definedLater",
+        "duplicateDefinition",
+        "instance",
+        "number",
+        "parameterInInferConstraint",
+        "parameterType",
+        "resolvedConstantName",
+        "resolvedNamedType",
+        "resolvedParameterizedType",
+        "resolvedTraitInBound",
+        "resolvedTraitName",
+        "resolvedVariableName",
+        "text",
+        "traitInInstanceDefinition",
+    ]);
+
+    // Problems found while visiting, and other markers
+    schema.insert::<()>(&[
+        "assignmentToPattern",
+        "defaultInstance",
+        "duplicateAttribute",
+        "errorInstance",
+        "extraAttributeValue",
+        "inferred",
+        "mismatchedAttributeValue",
+        "missingAttributeValue",
+        "missingNumberType",
+        "missingTextType",
+        "unresolvedNamedType",
+        "unresolvedParameterInInferConstraint",
+        "unresolvedParameterType",
+        "unresolvedParameterizedType",
+        "unresolvedTraitInBound",
+        "unresolvedTraitName",
+        "untyped",
+    ]);
+
+    schema
+}
+
+/// The values the visitor adds that can be saved with [`Db::save`], along
+/// with the values used by the database itself. Constraints aren't saved,
+/// since they're only needed while solving.
+pub fn registry() -> Registry {
+    let mut registry = Registry::new();
+    registry.register::<Kind>("kind");
+    registry.register::<KindMismatch>("kindMismatch");
+    registry
+}
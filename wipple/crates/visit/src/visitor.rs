@@ -2,6 +2,7 @@ use crate::definitions::{Definition, InstanceDefinition};
 use std::{
     collections::{BTreeMap, HashMap},
     mem,
+    rc::Rc,
     sync::Arc,
 };
 use visualizer::Constraint;
@@ -75,6 +76,10 @@ pub trait Visit {
 
     fn visit(&self, id: NodeId, visitor: &mut Visitor<'_>);
 
+    /// Define the names introduced by this node before any of the statements
+    /// around it are visited.
+    fn declare(&self, _id: NodeId, _visitor: &mut Visitor<'_>) {}
+
     fn hide(&self) -> bool {
         false
     }
@@ -95,7 +100,7 @@ impl Visit for (Range, &'static str) {
 pub struct ProgramInfo {
     pub definitions: BTreeMap<NodeId, Definition>,
     pub instances: BTreeMap<NodeId, Vec<NodeId>>,
-    pub constraints: Vec<Constraint<Db>>,
+    pub constraints: BTreeMap<NodeId, Vec<Constraint<Db>>>, // by owner
 }
 
 pub struct Ctx<'a> {
@@ -169,7 +174,7 @@ impl<'a> Visitor<'a> {
         ProgramInfo {
             definitions,
             instances: instance_ids,
-            constraints: self.constraints.into_values().flatten().collect(),
+            constraints: self.constraints,
         }
     }
 }
@@ -191,13 +196,33 @@ impl<'a> Visitor<'a> {
         self.ctx.db.fact(node, Fact::new(name, value));
     }
 
+    pub fn span(&self, node: NodeId) -> Span {
+        self.ctx
+            .db
+            .get::<Span>(node, "span")
+            .cloned()
+            .expect("node has no span")
+    }
+
     pub fn hide(&mut self, node: NodeId) {
         self.ctx.db.fact(node, Fact::hidden());
     }
 
     pub fn child(&mut self, node: &impl Visit, parent: NodeId, relation: &'static str) -> NodeId {
         let id = self.node(node.range(), node.name());
+        self.visit_child(node, id, parent, relation);
+        id
+    }
 
+    /// Like [`Visitor::child`], but using a node created earlier with
+    /// [`Visitor::node`].
+    pub fn visit_child(
+        &mut self,
+        node: &impl Visit,
+        id: NodeId,
+        parent: NodeId,
+        relation: &'static str,
+    ) {
         let previous_node = self.current_node.replace(id);
 
         self.relation(id, parent, relation);
@@ -216,8 +241,33 @@ impl<'a> Visitor<'a> {
         node.visit(id, self);
 
         self.current_node = previous_node;
+    }
 
-        id
+    /// Visit the statements in a file or block. Constants, types and traits
+    /// are declared first, so they can be used before they're defined.
+    pub fn statements<'s>(
+        &mut self,
+        statements: impl IntoIterator<Item = &'s syntax::Statement>,
+        parent: NodeId,
+        relation: &'static str,
+    ) -> Vec<NodeId> {
+        let statements = statements
+            .into_iter()
+            .filter(|statement| !matches!(statement, syntax::Statement::Empty(_)))
+            .map(|statement| (statement, self.node(statement.range(), statement.name())))
+            .collect::<Vec<_>>();
+
+        for &(statement, id) in &statements {
+            statement.declare(id, self);
+        }
+
+        statements
+            .into_iter()
+            .map(|(statement, id)| {
+                self.visit_child(statement, id, parent, relation);
+                id
+            })
+            .collect()
     }
 
     pub fn relation(&mut self, child: NodeId, parent: NodeId, relation: &'static str) {
@@ -242,32 +292,57 @@ impl<'a> Visitor<'a> {
 #[derive(Clone, Default)]
 struct Scope {
     definitions: HashMap<String, Vec<Definition>>,
+    unresolved: Vec<Unresolved>, // names that might be defined later
+}
+
+/// A use of a name that wasn't defined yet, along with the kinds of
+/// definition it would have resolved to.
+#[derive(Clone)]
+struct Unresolved {
+    name: String,
+    node: NodeId,
+    accepts: Rc<dyn Fn(&Definition) -> bool>,
 }
 
 impl Visitor<'_> {
     pub fn push_scope(&mut self, _definition: NodeId) {
-        self.scopes.push(Scope {
-            definitions: HashMap::new(),
-        });
+        self.scopes.push(Scope::default());
     }
 
     pub fn pop_scope(&mut self) {
-        self.scopes.pop();
+        let scope = self.scopes.pop().unwrap();
+
+        // A definition later in an enclosing scope would also have been
+        // visible here
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .unresolved
+            .extend(scope.unresolved);
     }
 
     pub fn resolve_name<T>(
         &mut self,
         name: &str,
         node: NodeId,
-        mut filter: impl FnMut(&Definition) -> Option<(T, &'static str)>,
+        filter: impl Fn(&Definition) -> Option<(T, &'static str)> + 'static,
     ) -> Option<T> {
-        let ((result, relation), definition) = self
+        let Some(((result, relation), definition)) = self
             .scopes
             .iter()
             .rev()
             .filter_map(|scope| scope.definitions.get(name))
             .flatten()
-            .find_map(|definition| Some((filter(definition)?, definition.source())))?;
+            .find_map(|definition| Some((filter(definition)?, definition.source())))
+        else {
+            self.scopes.last_mut().unwrap().unresolved.push(Unresolved {
+                name: name.to_string(),
+                node,
+                accepts: Rc::new(move |definition| filter(definition).is_some()),
+            });
+
+            return None;
+        };
 
         self.relation(node, definition, relation);
 
@@ -287,7 +362,53 @@ impl Visitor<'_> {
             .find_map(filter)
     }
 
-    pub fn define_name(&mut self, name: &str, definition: Definition) {
+    /// The variables in scope whose types are shared by every use, rather than
+    /// instantiated like constants.
+    pub fn shared_variables(&self) -> Vec<NodeId> {
+        self.scopes
+            .iter()
+            .flat_map(|scope| scope.definitions.values())
+            .flatten()
+            .filter_map(|definition| match definition {
+                Definition::Variable(definition) if !definition.generalize => Some(definition.node),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Define `name` in the current scope, returning the constant, type or
+    /// trait it duplicates, if any.
+    pub fn define_name(&mut self, name: &str, definition: Definition) -> Option<Definition> {
+        let scope = self.scopes.last_mut().unwrap();
+
+        let (defined_later, unresolved) = mem::take(&mut scope.unresolved)
+            .into_iter()
+            .partition::<Vec<_>, _>(|unresolved| {
+                unresolved.name == name && (unresolved.accepts)(&definition)
+            });
+
+        scope.unresolved = unresolved;
+
+        let previous = scope
+            .definitions
+            .get(name)
+            .into_iter()
+            .flatten()
+            .find(|previous| definition.is_unique() && previous.is_unique())
+            .cloned();
+
+        for Unresolved { node, .. } in defined_later {
+            self.fact(node, "definedLater", definition.source());
+        }
+
+        if let Some(previous) = &previous {
+            self.fact(
+                definition.source(),
+                "duplicateDefinition",
+                previous.source(),
+            );
+        }
+
         self.scopes
             .last_mut()
             .unwrap()
@@ -295,6 +416,16 @@ impl Visitor<'_> {
             .entry(name.to_string())
             .or_default()
             .push(definition);
+
+        previous
+    }
+
+    /// The definition declared for `node` before its statement was visited.
+    pub fn declared_definition(&mut self, name: &str, node: NodeId) -> Definition {
+        self.peek_name(name, |definition| {
+            (definition.source() == node).then(|| definition.clone())
+        })
+        .expect("definition wasn't declared")
     }
 
     pub fn define_instance(&mut self, definition: InstanceDefinition) {
@@ -1,23 +1,35 @@
-use crate::matcher::matcher;
+use crate::{matcher::matcher, rules::schema};
 use colored::Colorize;
-use std::io::{self, Write};
-use wipple_db::{Db, MarkdownQueryExt, Query};
+use std::{io::Write, sync::LazyLock};
+use wipple_db::{Db, MarkdownQueryExt, ParseError, Query, Span};
 
 #[derive(rust_embed::RustEmbed)]
 #[folder = "feedback"]
 struct Feedback;
 
-pub fn write_feedback(db: &Db, mut output: impl Write) -> io::Result<()> {
-    let queries = Feedback::iter()
+type FeedbackQuery = Query<'static, Vec<(Span, String)>>;
+
+static QUERIES: LazyLock<Result<Vec<FeedbackQuery>, ParseError>> = LazyLock::new(|| {
+    let schema = schema()?;
+
+    Feedback::iter()
         .filter(|path| path.ends_with(".md"))
         .map(|path| {
             let markdown = String::from_utf8(Feedback::get(&path).unwrap().data.to_vec()).unwrap();
 
-            Query::markdown(&markdown, matcher)
-                .unwrap_or_else(|| panic!("invalid feedback file: {path}"))
-        });
+            Query::markdown(&markdown, schema, matcher)
+                .map_err(|error| error.with_path(format!("feedback/{path}")))
+        })
+        .collect()
+});
+
+pub fn write_feedback(db: &Db, mut output: impl Write) -> anyhow::Result<()> {
+    let queries = QUERIES.as_ref().map_err(Clone::clone)?;
 
-    for (span, message) in queries.flat_map(|query| query.run(db, Default::default())) {
+    for (span, message) in queries
+        .iter()
+        .flat_map(|query| query.run(db, Default::default()))
+    {
         let message = textwrap::wrap(
             &message,
             textwrap::Options::new(80)
@@ -1,43 +1,143 @@
 pub mod feedback;
 pub mod matcher;
 pub mod queries;
+pub mod rules;
 pub mod span;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 
 pub use wipple_db as db;
 pub use wipple_syntax as syntax;
 pub use wipple_visit as visit;
 
-use crate::{queries::run_query, span::ParsedSpan};
+use crate::{
+    queries::{parse_arguments, run_query},
+    span::ParsedSpan,
+};
 use colored::Colorize;
 use db::{Db, Filter};
 use line_index::LineIndex;
-use std::io::Write;
+use std::io::{Read, Write};
 use syntax::{Parse, Range};
-use visualizer::Graph;
+use visualizer::{Graph, TyGroups};
 
 #[derive(Default)]
 pub struct Options<'a> {
     pub path: &'a str,
     pub source: &'a str,
     pub filter: Vec<Filter<'a>>,
-    pub queries: Vec<(String, ParsedSpan)>,
+    /// The queries to run, along with their arguments, written as
+    /// `(parameter, value)`.
+    pub queries: Vec<(String, Vec<(String, String)>)>,
+    pub solver_limits: visualizer::SolverLimits,
+
+    /// Where to save the database as JSON after solving, so it can be
+    /// reloaded with [`Db::load`].
+    pub save_db: Option<Box<dyn Write + 'a>>,
+
+    /// A database saved with `save_db` to use instead of solving `source`
+    /// again. `source` should still be the code the database was saved from,
+    /// so spans in the output refer to it.
+    pub load_db: Option<Box<dyn Read + 'a>>,
+
+    /// A directory to export the facts to as Soufflé relations.
+    pub souffle_dir: Option<&'a std::path::Path>,
+
+    /// A SQLite database to export the results to, with the tables in
+    /// [`sqlite::SCHEMA`].
+    #[cfg(feature = "sqlite")]
+    pub sqlite_path: Option<&'a std::path::Path>,
 }
 
 pub fn run(
-    options: Options<'_>,
+    mut options: Options<'_>,
     mut output: impl Write,
     graph: Option<impl FnOnce(Graph)>,
 ) -> anyhow::Result<()> {
-    let source_file = match syntax::SourceFile::parse(options.source) {
-        Ok(source_file) => source_file,
-        Err(error) => {
-            write!(output, "syntax error: {error}")?;
-            return Ok(());
-        }
+    let line_index = LineIndex::new(options.source);
+
+    let (db, ty_groups) = match options.load_db.take() {
+        Some(load_db) => Db::load(&visit::schema::registry(), load_db)?,
+        None => match syntax::SourceFile::parse(options.source) {
+            Ok(source_file) => solve(&source_file, &options, &line_index)?,
+            Err(error) => {
+                write!(output, "syntax error: {error}")?;
+                return Ok(());
+            }
+        },
     };
 
-    let line_index = LineIndex::new(options.source);
+    if let Some(save_db) = options.save_db {
+        db.save(&visit::schema::registry(), &ty_groups, save_db)?;
+    }
+
+    if let Some(dir) = options.souffle_dir {
+        db.export_souffle(rules::schema()?, dir)?;
+    }
+
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = options.sqlite_path {
+        let mut connection = rusqlite::Connection::open(path)?;
+        sqlite::export(&db, &ty_groups, options.path, &mut connection)?;
+    }
+
+    for (query, arguments) in options.queries {
+        let parameters = parse_arguments(&query, &arguments, &line_index)?;
+        let table = run_query(&query, &db, parameters)?;
+
+        writeln!(
+            output,
+            "{}\n",
+            format!("Result of query '{query}':").bold().underline()
+        )?;
+
+        if table.rows.is_empty() {
+            writeln!(output, "    no outputs")?;
+        } else {
+            for row in table.rows {
+                let values = row
+                    .iter()
+                    .map(|value| match value {
+                        queries::Value::Span(span) => {
+                            format!("{}: {}", span, options.source[span.range.clone()].blue())
+                        }
+                        value => value.display(&db).blue().to_string(),
+                    })
+                    .collect::<Vec<_>>();
+
+                if let [value] = values.as_slice() {
+                    writeln!(output, "    {value}")?;
+                } else {
+                    writeln!(output, "    -")?;
+                    for (column, value) in table.columns.iter().zip(values) {
+                        writeln!(output, "      {column}: {value}")?;
+                    }
+                }
+            }
+        }
+
+        writeln!(output)?;
+    }
 
+    feedback::write_feedback(&db, &mut output)?;
+
+    writeln!(output, "{}\n", "Facts:".bold().underline())?;
+
+    db.write(&options.filter, "  ", &mut output)?;
+
+    if let Some(graph) = graph {
+        graph(db.graph(&ty_groups, &options.filter));
+    }
+
+    Ok(())
+}
+
+/// Visit and solve `source_file`, then derive the facts from `rules.txt`.
+fn solve(
+    source_file: &syntax::SourceFile,
+    options: &Options<'_>,
+    line_index: &LineIndex,
+) -> anyhow::Result<(Db, TyGroups<Db>)> {
     let mut db = Db::new();
 
     let ctx = visit::Ctx {
@@ -51,7 +151,7 @@ pub fn run(
                 path: options.path.to_string(),
                 range: start..end,
             }
-            .to_span(&line_index)
+            .to_span(line_index)
             .expect("invalid span");
 
             let source = options.source[start..end].to_string();
@@ -61,50 +161,958 @@ pub fn run(
         show_definitions: true, // TODO: make this an option?
     };
 
-    let info = visit::visit(&source_file, ctx);
+    let info = visit::visit(source_file, ctx);
 
-    let mut solver = visualizer::Solver::new(&mut db);
-    solver.insert(info.constraints);
+    let mut solver = visualizer::Solver::new(&mut db).with_limits(options.solver_limits);
+    solver.insert_owned(info.constraints);
+    solver.check_super_instances(info.instances.values().flatten().copied());
+    solver.check_overlapping_instances(info.instances.keys().copied());
     let ty_groups = solver.finish();
 
-    for (query, span) in options.queries {
-        let span = span
-            .to_span(&line_index)
-            .ok_or_else(|| anyhow::format_err!("invalid span: {span}"))?;
+    rules::derive_facts(&mut db)?;
 
-        let outputs = run_query(&query, &db, span)?;
+    Ok((db, ty_groups))
+}
 
-        writeln!(
-            output,
-            "{}\n",
-            format!("Result of query '{query}':").bold().underline()
-        )?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::{FactValue, MarkdownQueryExt, NodeId, Span};
+    use std::collections::{BTreeMap, BTreeSet};
+    use visualizer::{Constraint, Solver, TyGroups};
 
-        if outputs.is_empty() {
-            writeln!(output, "    no outputs")?;
-        } else {
-            for output_span in outputs {
-                writeln!(
-                    output,
-                    "    {}: {}",
-                    output_span,
-                    options.source[output_span.range.clone()].blue()
-                )?;
+    const SOURCE: &str = r#"
+Number : type
+Text : type
+Unit : type
+Maybe : value => type
+Show : value => trait (value -> Unit)
+instance (Show Number) : _
+instance (Show Text) : _
+instance (Show (Maybe value)) where (Show value) : _
+show :: value -> Unit where (Show value)
+n :: Number
+t :: Text
+m :: Maybe Number
+id : x -> x
+a : id n
+b : id t
+show a
+show m
+show b
+c : 2
+show c
+"#;
+
+    fn visit(source: &str) -> (Db, BTreeMap<NodeId, Vec<Constraint<Db>>>) {
+        let source_file = syntax::SourceFile::parse(source).unwrap();
+
+        let mut db = Db::new();
+
+        let ctx = visit::Ctx {
+            db: &mut db,
+            get_span_source: Box::new(|range: Range| {
+                let Range::Some(start, end) = range else {
+                    panic!("node has no range");
+                };
+
+                (Span::root("test"), source[start..end].to_string())
+            }),
+            show_definitions: true,
+        };
+
+        let info = visit::visit(&source_file, ctx);
+
+        (db, info.constraints)
+    }
+
+    /// Visit and solve `source` the way [`run`] does, with spans in a file
+    /// named `test`.
+    fn solved(source: &str) -> (Db, TyGroups<Db>) {
+        solved_with(Options {
+            path: "test",
+            source,
+            ..Default::default()
+        })
+    }
+
+    fn solved_with(options: Options<'_>) -> (Db, TyGroups<Db>) {
+        let source_file = syntax::SourceFile::parse(options.source).unwrap();
+        solve(&source_file, &options, &LineIndex::new(options.source)).unwrap()
+    }
+
+    /// Each feedback message on one line, after its span.
+    fn feedback(db: &Db) -> Vec<String> {
+        colored::control::set_override(false);
+
+        let mut output = Vec::new();
+        feedback::write_feedback(db, &mut output).unwrap();
+
+        String::from_utf8(output)
+            .unwrap()
+            .split("Feedback on ")
+            .skip(1)
+            .map(|feedback| feedback.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect()
+    }
+
+    fn tys(db: &Db, ty_groups: &TyGroups<Db>, nodes: &[NodeId]) -> BTreeMap<NodeId, Vec<String>> {
+        nodes
+            .iter()
+            .filter_map(|&node| {
+                let index = ty_groups.index_of(node)?;
+
+                let mut tys = ty_groups
+                    .tys_at(index)
+                    .iter()
+                    .map(|ty| ty.display(db).unwrap())
+                    .collect::<Vec<_>>();
+
+                tys.sort();
+
+                Some((node, tys))
+            })
+            .collect()
+    }
+
+    /// Every fact in the database, sorted. Nodes created while solving are
+    /// numbered in the order they're made, so they're written as `copy`
+    /// instead of by ID, where `nodes` are the nodes created while visiting.
+    fn facts(db: &Db, nodes: &[NodeId]) -> Vec<String> {
+        let label = |text: &str| {
+            let mut result = String::new();
+            let mut rest = text;
+            while let Some(index) = rest.find("NodeId(") {
+                result.push_str(&rest[..index]);
+
+                let (id, after) = rest[index + "NodeId(".len()..].split_once(')').unwrap();
+                let node = NodeId(id.parse().unwrap());
+                if nodes.binary_search(&node).is_ok() {
+                    result.push_str(&format!("{node:?}"));
+                } else {
+                    result.push_str("copy");
+                }
+
+                rest = after;
+            }
+
+            result.push_str(rest);
+            result
+        };
+
+        let mut facts = db
+            .nodes()
+            .flat_map(|node| {
+                db.iter(node).map(move |fact| {
+                    let value = fact.value().display(db).unwrap_or_default();
+                    label(&format!("{node:?} {}({value})", fact.name()))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        facts.sort();
+        facts
+    }
+
+    #[test]
+    fn test_missing_super_instance() {
+        let source = r#"
+Number : type
+Text : type
+Boolean : type
+Maybe : value => type
+Equal : value => trait (value -> value -> Boolean)
+Order : value => trait (value -> value -> Number) where (Equal value)
+instance (Equal Number) : _
+instance (Equal Boolean) where (Equal Boolean) : _
+instance (Equal (Maybe Number)) : _
+instance (Equal (Maybe b)) : _
+instance (Order Number) : _
+instance (Order Text) : _
+instance (Order Boolean) : _
+instance (Order (Maybe Number)) : _
+"#;
+
+        let (db, _) = solved(source);
+
+        let source_of = |node| db.get::<db::Source>(node, "source").unwrap().0.trim();
+
+        let missing = db
+            .nodes()
+            .filter_map(|node| {
+                let super_trait = db.get::<NodeId>(node, "missingSuperInstance")?;
+                Some((source_of(node), source_of(*super_trait)))
+            })
+            .collect::<Vec<_>>();
+
+        // `Equal Boolean` requires itself, and `Equal (Maybe Number)` matches
+        // both `Maybe` instances
+        assert_eq!(
+            missing,
+            [
+                ("instance (Order Text)", "Equal"),
+                ("instance (Order Boolean)", "Equal"),
+                ("instance (Order (Maybe Number))", "Equal"),
+            ]
+        );
+
+        // The trait's bounds are only reported as missing super instances
+        let reported = [
+            "unresolvedTrait",
+            "ambiguousTrait",
+            "instanceRecursionLimit",
+        ]
+        .into_iter()
+        .flat_map(|fact| db.all(fact))
+        .collect::<Vec<_>>();
+
+        assert!(reported.is_empty(), "{reported:?}");
+    }
+
+    #[test]
+    fn test_overlap_check_removes_copies() {
+        let source = r#"
+Number : type
+Maybe : value => type
+Show : value => trait (value -> Number)
+instance (Show Number) : _
+instance (Show (Maybe Number)) : _
+instance (Show (Maybe value)) : _
+"#;
+
+        let solve = |check_overlaps: bool| {
+            let (mut db, constraints) = visit(source);
+
+            let traits = db
+                .all("traitInInstanceDefinition")
+                .filter_map(|(_, fact)| fact.value().downcast_ref::<NodeId>().copied())
+                .collect::<BTreeSet<_>>();
+
+            let mut solver = Solver::new(&mut db);
+            solver.insert_owned(constraints);
+            if check_overlaps {
+                solver.check_overlapping_instances(traits);
+            }
+            solver.finish();
+            drop(solver);
+
+            db
+        };
+
+        let checked = solve(true);
+        let unchecked = solve(false);
+
+        let overlapping = checked
+            .all("overlappingInstance")
+            .map(|(node, _)| checked.get::<db::Source>(node, "source").unwrap().0.trim())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            overlapping,
+            [
+                "instance (Show (Maybe Number))",
+                "instance (Show (Maybe value))"
+            ]
+        );
+
+        // The copies made to compare the instances are gone
+        assert_eq!(
+            checked.nodes().collect::<Vec<_>>(),
+            unchecked.nodes().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let (db, _) = solved_with(Options {
+            path: "test",
+            source: SOURCE,
+            solver_limits: visualizer::SolverLimits {
+                max_steps: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        assert_eq!(db.count("solverStepLimit"), 1);
+    }
+
+    #[test]
+    fn test_unsolved_constraint() {
+        let source = r#"
+Number : type
+Unit : type
+Maybe : value => type
+Show : value => trait (value -> Unit)
+instance (Show Number) : _
+instance (Show (Maybe value)) where (Show value) : _
+show :: value -> Unit where (Show value)
+m :: Maybe (Maybe Number)
+show m
+"#;
+
+        // Stopping before the nested bounds are resolved leaves the bound
+        // unsolved
+        let (db, _) = solved_with(Options {
+            path: "test",
+            source,
+            solver_limits: visualizer::SolverLimits {
+                max_steps: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        assert_eq!(db.count("unsolvedConstraint"), 1);
+        assert_eq!(
+            feedback(&db),
+            [
+                "test:10.1-10.5: Wipple stopped checking types while working on `show` because it was taking too long. Types after this point may be missing or incomplete.",
+                "test:10.1-10.5: Wipple couldn't finish checking the types of `show` because it got stuck on `Show`. Types involving `show` may be missing or incomplete.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remove_nodes_since_checkpoint() {
+        let (mut db, _) = visit(SOURCE);
+        let nodes = db.nodes().collect::<Vec<_>>();
+
+        let checkpoint = <Db as visualizer::Db>::checkpoint(&mut db);
+        let fresh = <Db as visualizer::Db>::fresh_node(&mut db);
+        db.fact(fresh, db::Fact::new("resolvedInstance", nodes[0]));
+
+        <Db as visualizer::Db>::remove_nodes_since(&mut db, checkpoint);
+
+        assert_eq!(db.nodes().collect::<Vec<_>>(), nodes);
+        assert_eq!(db.count("resolvedInstance"), 0);
+        assert_eq!(db.find("resolvedInstance", &nodes[0]).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_trait_output() {
+        let source = r#"
+Number : type
+List : element => type
+Element : collection element => trait (collection -> element) where (infer element)
+instance (Element (List e) e) : _
+first :: collection -> element where (Element collection element)
+xs :: List Number
+xs : _
+x : first xs
+"#;
+
+        let (db, _) = solved(source);
+
+        let outputs = db
+            .all("traitOutput")
+            .map(|(_, fact)| fact.value().downcast_ref::<db::TraitOutput>().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].display(&db).unwrap(), "element = Number");
+
+        // The output refers to the trait's parameter, not the instance's
+        let trait_id = db
+            .get::<NodeId>(outputs[0].parameter, "parameterInTraitDefinition")
+            .unwrap();
+
+        assert_eq!(
+            db.get::<db::Source>(*trait_id, "source").unwrap().0.trim(),
+            "Element"
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_instance() {
+        let source = r#"
+Number : type
+Text : type
+Show : value => trait (value -> Text)
+instance (Show Number) : _
+instance (Show Text) : _
+describe :: value -> Text where (Show value)
+describe : _
+x : describe
+"#;
+
+        let (db, _) = solved(source);
+
+        let source_of = |node| db.get::<db::Source>(node, "source").unwrap().0.trim();
+
+        // Each candidate is linked to the bound
+        let bounds = db.all("ambiguousTrait").collect::<Vec<_>>();
+        assert_eq!(bounds.len(), 1);
+
+        let ty = *bounds[0].1.value().downcast_ref::<NodeId>().unwrap();
+        let candidates = db
+            .iter_of::<NodeId>(ty, "ambiguousInstance")
+            .map(|&instance| source_of(instance))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            candidates,
+            ["instance (Show Number)", "instance (Show Text)"]
+        );
+
+        // The bound is reported once, listing both instances
+        let feedback = feedback(&db)
+            .into_iter()
+            .filter(|feedback| feedback.contains("more than one instance"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(feedback.len(), 1, "{feedback:?}");
+        assert!(
+            feedback[0].starts_with("test:9.5-9.13: `describe` can't be used here"),
+            "{feedback:?}"
+        );
+        assert!(
+            feedback[0].contains("the instances at test:5.1-5.24, test:6.1-6.22."),
+            "{feedback:?}"
+        );
+    }
+
+    #[test]
+    fn test_kind_mismatch() {
+        let source = r#"
+Number : type
+Maybe : value => type
+Show : value => trait (value -> Number)
+a :: Maybe
+b :: Maybe Number Number
+c :: Maybe Number
+d :: Number where (Show Number Number)
+e :: value -> (value Number)
+instance (Show) : _
+"#;
+
+        let (db, constraints) = visit(source);
+
+        let mut mismatches = db
+            .nodes()
+            .filter_map(|node| {
+                let mismatch = db.get::<visit::kinds::KindMismatch>(node, "kindMismatch")?;
+                let source = db.get::<db::Source>(node, "source").unwrap().0.trim();
+                Some((source, mismatch.display(&db).unwrap()))
+            })
+            .collect::<Vec<_>>();
+
+        mismatches.sort();
+
+        assert_eq!(
+            mismatches,
+            [
+                (
+                    "(Show Number Number)",
+                    String::from("expected `_ => trait`, found `_ _ => trait`"),
+                ),
+                ("Maybe", String::from("expected `_ => type`, found `type`")),
+                (
+                    "Maybe Number Number",
+                    String::from("expected `_ => type`, found `_ _ => type`"),
+                ),
+                (
+                    "instance (Show)",
+                    String::from("expected `_ => trait`, found `trait`"),
+                ),
+                (
+                    "value Number",
+                    String::from("expected `type`, found `_ => type`"),
+                ),
+            ]
+        );
+
+        // The type parameter is the head of the parameterized type
+        let parameter = db
+            .nodes()
+            .find(|&node| db.get::<db::Source>(node, "source").unwrap().0 == "value Number")
+            .and_then(|node| db.get::<NodeId>(node, "resolvedParameterizedType"))
+            .unwrap();
+
+        assert_eq!(
+            db.get::<db::Source>(*parameter, "source").unwrap().0,
+            "value"
+        );
+
+        // Mismatched types and instances don't reach the solver
+        let instance = db.all("instanceDefinition").next().unwrap().0;
+        assert!(!constraints.contains_key(&instance));
+        assert!(db.all("instance").next().is_none());
+    }
+
+    #[test]
+    fn test_hoisting() {
+        let source = r#"
+show :: (Maybe Number) -> Number where (Show Number)
+even :: Number -> Boolean
+even : n -> odd n
+odd :: Number -> Boolean
+odd : n -> even n
+a : b
+b : even
+c : {
+    d
+    d :: Number
+    d : 1
+}
+instance (Show Number) : _
+Show : value => trait (value -> Number)
+Number : type
+Boolean : type
+Maybe : value => type
+"#;
+
+        let (db, _) = visit(source);
+
+        let source_of = |node| db.get::<db::Source>(node, "source").unwrap().0.trim();
+
+        let mut unresolved = Vec::new();
+        let mut defined_later = Vec::new();
+        for node in db.nodes() {
+            if let Some(&definition) = db.get::<NodeId>(node, "definedLater") {
+                defined_later.push((source_of(node), source_of(definition)));
+            } else if db
+                .iter(node)
+                .any(|fact| fact.name().starts_with("unresolved"))
+                || (db.get::<()>(node, "variable").is_some()
+                    && db.get::<NodeId>(node, "resolvedVariableName").is_none()
+                    && db.get::<NodeId>(node, "resolvedConstantName").is_none())
+            {
+                unresolved.push(source_of(node));
             }
         }
 
-        writeln!(output)?;
+        assert!(unresolved.is_empty(), "unresolved: {unresolved:?}");
+        assert_eq!(defined_later, [("b", "b")]);
     }
 
-    feedback::write_feedback(&db, &mut output)?;
+    #[test]
+    fn test_defined_later_matches_kind() {
+        let source = r#"
+Number : type
+c : {
+    (1 :: d)
+    d
+    d : 1
+}
+"#;
 
-    writeln!(output, "{}\n", "Facts:".bold().underline())?;
+        let (db, _) = visit(source);
 
-    db.write(&options.filter, "  ", &mut output)?;
+        let source_of = |node| db.get::<db::Source>(node, "source").unwrap().0.trim();
 
-    if let Some(graph) = graph {
-        graph(db.graph(&ty_groups, &options.filter));
+        let mut defined_later = Vec::new();
+        let mut unresolved_parameters = Vec::new();
+        for node in db.nodes() {
+            if let Some(&definition) = db.get::<NodeId>(node, "definedLater") {
+                defined_later.push((
+                    db.get::<()>(node, "variable").is_some(),
+                    source_of(definition),
+                ));
+            }
+
+            if db.get::<()>(node, "unresolvedParameterType").is_some() {
+                unresolved_parameters.push(source_of(node));
+            }
+        }
+
+        // The variable can't be used as the type in the annotation
+        assert_eq!(defined_later, [(true, "d")]);
+        assert_eq!(unresolved_parameters, ["d"]);
     }
 
-    Ok(())
+    #[test]
+    fn test_duplicate_definitions() {
+        let source = r#"
+Number : type
+Number : type
+x :: Number
+x : 1
+x : 2
+x :: Number
+y : 1
+y : 2
+"#;
+
+        let (db, _) = solved(source);
+        let feedback = feedback(&db).join("\n");
+
+        assert!(
+            feedback.contains("`Number` is already defined"),
+            "{feedback}"
+        );
+        assert!(feedback.contains("`x` is already defined"), "{feedback}");
+        assert!(feedback.contains("`x` already has a value"), "{feedback}");
+        assert!(!feedback.contains("`y`"), "{feedback}");
+    }
+
+    #[test]
+    fn test_enclosing_functions() {
+        let source = r#"
+Number : type
+f : (x :: Number) -> (y :: Number) -> x
+g : 1
+"#;
+
+        let (db, _) = solved(source);
+
+        let source_of = |node| db.get::<db::Source>(node, "source").unwrap().0.trim();
+
+        let mut functions = BTreeMap::new();
+        for node in db.nodes() {
+            if db.get::<()>(node, "number").is_some()
+                || db.get::<NodeId>(node, "resolvedVariableName").is_some()
+            {
+                let mut enclosing = db
+                    .iter_of::<NodeId>(node, "insideFunction")
+                    .map(|&function| source_of(function))
+                    .collect::<Vec<_>>();
+
+                enclosing.sort();
+                functions.insert(source_of(node), enclosing);
+            }
+        }
+
+        assert_eq!(
+            functions,
+            BTreeMap::from([
+                ("1", vec![]),
+                (
+                    "x",
+                    vec!["(x :: Number) -> (y :: Number) -> x", "(y :: Number) -> x"]
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_generalize_shares_enclosing_inputs() {
+        let source = r#"
+Number : type
+Text : type
+f : x -> {
+    g : z -> x
+    n : 1
+    (g n :: Number)
+    (g n :: Text)
+}
+"#;
+
+        let (db, ty_groups) = solved(source);
+        let nodes = db.nodes().collect::<Vec<_>>();
+
+        let source_of = |node| db.get::<db::Source>(node, "source").unwrap().0.trim();
+
+        // `x` belongs to the enclosing function, so both calls to `g` return
+        // the same type
+        let uses = tys(&db, &ty_groups, &nodes)
+            .into_iter()
+            .filter(|&(node, _)| {
+                db.get::<NodeId>(node, "resolvedVariableName").is_some() && source_of(node) == "g"
+            })
+            .map(|(_, tys)| tys)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            uses,
+            [
+                vec![String::from("Number -> Number")],
+                vec![String::from("Number -> Text"), String::from("_ -> Number")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_types_in_feedback() {
+        let (db, _) = solved(SOURCE);
+
+        let query = db::Query::markdown(
+            r#"
+---
+definition.variablePattern
+definition.source(name)
+n = count(ty: function.resolvedVariableName(definition) | function.resolvedConstantName(definition), function.type(ty))
+n > 1
+definition.span(span)
+---
+
+[`name`] is called with [n] different types.
+"#,
+            rules::schema().unwrap(),
+            matcher::matcher,
+        )
+        .unwrap();
+
+        let messages = query
+            .run(&db, Default::default())
+            .into_iter()
+            .map(|(_, message)| message)
+            .collect::<Vec<_>>();
+
+        assert_eq!(messages.len(), 1, "{messages:?}");
+        assert!(messages[0].ends_with(" is called with 2 different types."));
+        assert!(messages[0].contains("id"));
+    }
+
+    #[test]
+    fn test_query_table() {
+        colored::control::set_override(false);
+
+        let source = "Number : type\nUnit : type\nShow : value => trait (value -> Unit)\ninstance (Show Number) : _\n(Show) 1\n";
+
+        let arguments = |arguments: &[(&str, &str)]| {
+            arguments
+                .iter()
+                .map(|&(parameter, value)| (parameter.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let options = Options {
+            path: "test",
+            source,
+            queries: vec![
+                (String::from("instances"), arguments(&[("trait", "Show")])),
+                (
+                    String::from("typeAt"),
+                    arguments(&[("input", "test:5.8-5.9")]),
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        run(options, &mut output, None::<fn(_)>).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(
+            output.contains(
+                "Result of query 'instances':\n\n    test:4.1-4.24: instance (Show Number) \n"
+            ),
+            "{output}"
+        );
+
+        assert!(
+            output.contains("Result of query 'typeAt':\n\n    Number\n"),
+            "{output}"
+        );
+
+        let options = Options {
+            path: "test",
+            source,
+            queries: vec![(String::from("typeAt"), arguments(&[("trait", "Show")]))],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            run(options, std::io::sink(), None::<fn(_)>)
+                .unwrap_err()
+                .to_string(),
+            "query 'typeAt' has no parameter 'trait'"
+        );
+    }
+
+    #[test]
+    fn test_save_and_load_db() {
+        let (db, ty_groups) = solved(SOURCE);
+
+        let registry = visit::schema::registry();
+
+        let mut saved = Vec::new();
+        db.save(&registry, &ty_groups, &mut saved).unwrap();
+        let (loaded, loaded_ty_groups) = Db::load(&registry, saved.as_slice()).unwrap();
+
+        let write = |db: &Db| {
+            let mut output = Vec::new();
+            db.write(&[], "  ", &mut output).unwrap();
+            feedback::write_feedback(db, &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(write(&loaded), write(&db));
+
+        let graph = |db: &Db, ty_groups| serde_json::to_value(db.graph(ty_groups, &[])).unwrap();
+        assert_eq!(graph(&loaded, &loaded_ty_groups), graph(&db, &ty_groups));
+
+        let instances = |db: &Db| {
+            run_query(
+                "instances",
+                db,
+                db::QueryValues::from([(
+                    String::from("trait"),
+                    std::rc::Rc::new(db::Source(String::from("Show"))) as _,
+                )]),
+            )
+            .unwrap()
+        };
+
+        assert!(!instances(&db).rows.is_empty());
+        assert_eq!(instances(&loaded), instances(&db));
+    }
+
+    #[test]
+    fn test_run_with_loaded_db() {
+        let run_with = |save_db: Option<&mut Vec<u8>>, load_db: Option<&[u8]>| {
+            let options = Options {
+                path: "test",
+                source: SOURCE,
+                queries: vec![(
+                    String::from("instances"),
+                    vec![(String::from("trait"), String::from("Show"))],
+                )],
+                save_db: save_db.map(|saved| Box::new(saved) as _),
+                load_db: load_db.map(|saved| Box::new(saved) as _),
+                ..Default::default()
+            };
+
+            let mut output = Vec::new();
+            let mut graph = None;
+            run(options, &mut output, Some(|g| graph = Some(g))).unwrap();
+
+            (
+                String::from_utf8(output).unwrap(),
+                serde_json::to_value(graph.unwrap()).unwrap(),
+            )
+        };
+
+        let mut saved = Vec::new();
+        let solved = run_with(Some(&mut saved), None);
+        let loaded = run_with(None, Some(&saved));
+
+        assert!(solved.0.contains("Result of query 'instances'"));
+        assert_eq!(loaded, solved);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_export() {
+        let (db, ty_groups) = solved(SOURCE);
+
+        let mut connection = rusqlite::Connection::open_in_memory().unwrap();
+
+        // Exporting again replaces the rows for the same file
+        for _ in 0..2 {
+            sqlite::export(&db, &ty_groups, "test", &mut connection).unwrap();
+        }
+
+        sqlite::export(&db, &ty_groups, "other", &mut connection).unwrap();
+
+        let count = |sql: &str| {
+            connection
+                .query_row(sql, [], |row| row.get::<_, usize>(0))
+                .unwrap()
+        };
+
+        assert_eq!(
+            count("SELECT COUNT(*) FROM nodes WHERE file = 'test'"),
+            db.nodes().count()
+        );
+
+        assert_eq!(
+            count("SELECT COUNT(*) FROM facts WHERE file = 'test'"),
+            db.nodes().map(|node| db.iter(node).count()).sum::<usize>()
+        );
+
+        assert_eq!(
+            count("SELECT COUNT(*) FROM type_group_nodes WHERE file = 'test'"),
+            ty_groups.nodes().count()
+        );
+
+        assert_eq!(
+            count(
+                "SELECT COUNT(*) FROM facts WHERE file = 'test' AND name = 'parent' AND derived = 1 AND related_node IS NOT NULL"
+            ),
+            db.all("parent").count()
+        );
+
+        assert_eq!(count("SELECT COUNT(DISTINCT file) FROM spans"), 2);
+
+        // The type of each number node, found through its type group
+        let number_types = connection
+            .prepare(
+                "SELECT DISTINCT type_group_types.type FROM facts
+                JOIN type_group_nodes USING (file, node)
+                JOIN type_group_types USING (file, type_group)
+                WHERE facts.file = 'test' AND facts.name = 'number' AND facts.value IS NULL",
+            )
+            .unwrap()
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(number_types, vec![String::from("Number")]);
+    }
+
+    #[test]
+    fn test_schema_covers_facts() {
+        // Loading checks the feedback and queries against the schema
+        feedback::write_feedback(&Db::new(), std::io::sink()).unwrap();
+        run_query("definition", &Db::new(), db::QueryValues::new()).unwrap();
+
+        let (db, _) = solved(SOURCE);
+
+        let unknown = rules::schema()
+            .unwrap()
+            .unknown_facts(&db)
+            .into_iter()
+            .map(|(_, fact)| format!("{}({:?})", fact.name(), fact.value()))
+            .collect::<Vec<_>>();
+
+        assert!(unknown.is_empty(), "{unknown:?}");
+    }
+
+    #[test]
+    fn test_parent_rules_match_child_relations() {
+        let mut parents = include_str!("../rules.txt")
+            .lines()
+            .filter_map(|line| line.strip_prefix("parent(x, y) :- x."))
+            .map(|line| &line[..line.find('(').unwrap()])
+            .collect::<Vec<_>>();
+
+        parents.sort();
+
+        let mut relations = visit::schema::CHILD_RELATIONS.to_vec();
+        relations.sort();
+
+        assert_eq!(parents, relations);
+    }
+
+    #[test]
+    fn test_retract_matches_solving_from_scratch() {
+        let (visited, constraints) = visit(SOURCE);
+        let nodes = visited.nodes().collect::<Vec<_>>();
+
+        let results = |solver: &Solver<'_, Db>| {
+            let ty_groups = solver.finish();
+            let db = solver.db();
+            (tys(&db, &ty_groups, &nodes), facts(&db, &nodes))
+        };
+
+        let solve = |constraints| {
+            let mut db = visited.clone();
+            let mut solver = Solver::new(&mut db);
+            solver.insert_owned(constraints);
+            results(&solver)
+        };
+
+        let full = solve(constraints.clone());
+
+        // Each owner is retracted and reinserted in turn, so results from
+        // earlier cycles must not be left behind
+        let mut db = visited.clone();
+        let mut solver = Solver::new(&mut db);
+        solver.insert_owned(constraints.clone());
+
+        for (&owner, owned) in &constraints {
+            let mut remaining = constraints.clone();
+            remaining.remove(&owner);
+            let from_scratch = solve(remaining);
+
+            solver.retract([owner]);
+            assert_eq!(results(&solver), from_scratch, "retracting {owner:?}");
+
+            solver.insert_owned([(owner, owned.clone())]);
+            assert_eq!(results(&solver), full, "reinserting {owner:?}");
+        }
+    }
 }
@@ -1,5 +1,6 @@
 use clap::Parser;
 use std::{fs, io, path::PathBuf};
+use visualizer::SolverLimits;
 use wipple::span::ParsedSpan;
 use wipple_db::Filter;
 
@@ -13,8 +14,45 @@ struct Args {
     #[clap(long)]
     query: Option<String>,
 
+    /// Shorthand for `--query-arg input=SPAN`.
     #[clap(long, requires = "query")]
     query_span: Option<ParsedSpan>,
+
+    /// An argument to the query, written as `parameter=value`.
+    #[clap(long, requires = "query", value_parser = parse_query_arg)]
+    query_arg: Vec<(String, String)>,
+
+    /// Save the database as JSON to this path after solving.
+    #[clap(long)]
+    save_db: Option<PathBuf>,
+
+    /// Load a database saved with `--save-db` instead of solving the program
+    /// at `path` again. `path` should be the program it was saved from.
+    #[clap(long)]
+    load_db: Option<PathBuf>,
+
+    /// Export the facts as Soufflé relations to this directory.
+    #[clap(long)]
+    souffle: Option<PathBuf>,
+
+    /// Export the results to this SQLite database.
+    #[cfg(feature = "sqlite")]
+    #[clap(long)]
+    sqlite: Option<PathBuf>,
+
+    #[clap(long)]
+    max_instance_depth: Option<u32>,
+
+    #[clap(long)]
+    max_solver_steps: Option<u32>,
+}
+
+fn parse_query_arg(s: &str) -> anyhow::Result<(String, String)> {
+    let (parameter, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow::format_err!("expected `parameter=value`"))?;
+
+    Ok((parameter.to_string(), value.to_string()))
 }
 
 fn main() -> anyhow::Result<()> {
@@ -26,11 +64,42 @@ fn main() -> anyhow::Result<()> {
         (!args.filter_lines.is_empty()).then_some(Filter::Lines(&args.filter_lines)),
     );
 
+    let mut solver_limits = SolverLimits::default();
+    if let Some(max_depth) = args.max_instance_depth {
+        solver_limits.max_depth = max_depth;
+    }
+    if let Some(max_steps) = args.max_solver_steps {
+        solver_limits.max_steps = max_steps;
+    }
+
+    let queries = Vec::from_iter(args.query.map(|query| {
+        let mut arguments = Vec::from_iter(
+            args.query_span
+                .map(|span| (String::from("input"), span.to_string())),
+        );
+
+        arguments.extend(args.query_arg);
+
+        (query, arguments)
+    }));
+
     let options = wipple::Options {
         path: &args.path.display().to_string(),
         source: &source,
         filter,
-        queries: Vec::from_iter(args.query.zip(args.query_span)),
+        queries,
+        solver_limits,
+        save_db: match &args.save_db {
+            Some(path) => Some(Box::new(io::BufWriter::new(fs::File::create(path)?))),
+            None => None,
+        },
+        load_db: match &args.load_db {
+            Some(path) => Some(Box::new(io::BufReader::new(fs::File::open(path)?))),
+            None => None,
+        },
+        souffle_dir: args.souffle.as_deref(),
+        #[cfg(feature = "sqlite")]
+        sqlite_path: args.sqlite.as_deref(),
     };
 
     wipple::run(options, io::stdout(), None::<fn(_)>)
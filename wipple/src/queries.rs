@@ -1,27 +1,143 @@
-use crate::matcher::matcher;
+use crate::{matcher::matcher, rules::schema, span::ParsedSpan};
+use line_index::LineIndex;
 use std::{collections::HashMap, rc::Rc, sync::LazyLock};
-use wipple_db::{Db, Query, QueryValues, Span, YamlQueryExt};
+use visualizer::Ty;
+use wipple_db::{
+    Db, FactValue, NodeId, ParseError, QueryValues, Source, Span, ValueType, YamlQuery,
+    YamlQueryExt,
+};
 
-static QUERIES: LazyLock<HashMap<String, Query<'static, Vec<QueryValues>>>> = LazyLock::new(|| {
-    Query::yaml(include_str!("../queries.yml"), &matcher).expect("failed to parse actions")
+type Queries = HashMap<String, YamlQuery<'static>>;
+
+static QUERIES: LazyLock<Result<Queries, ParseError>> = LazyLock::new(|| {
+    YamlQuery::yaml(include_str!("../queries.yml"), schema()?, &matcher)
+        .map_err(|error| error.with_path("queries.yml"))
 });
 
-pub fn run_query(name: &str, db: &Db, input: Span) -> anyhow::Result<Vec<Span>> {
-    let query = QUERIES
+/// A value returned by a query, with the type its output is declared with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Unit,
+    Node(NodeId),
+    Span(Span),
+    Text(String),
+    Type(Ty<Db>),
+    Number(usize),
+}
+
+impl Value {
+    fn new(value: &dyn FactValue) -> Option<Self> {
+        if value.is::<()>() {
+            Some(Value::Unit)
+        } else if let Some(&node) = value.downcast_ref::<NodeId>() {
+            Some(Value::Node(node))
+        } else if let Some(span) = value.downcast_ref::<Span>() {
+            Some(Value::Span(span.clone()))
+        } else if let Some(source) = value.downcast_ref::<Source>() {
+            Some(Value::Text(source.0.clone()))
+        } else if let Some(ty) = value.downcast_ref::<Ty<Db>>() {
+            Some(Value::Type(ty.clone()))
+        } else {
+            value.downcast_ref::<usize>().copied().map(Value::Number)
+        }
+    }
+
+    pub fn display(&self, db: &Db) -> String {
+        match self {
+            Value::Unit => String::from("()"),
+            Value::Node(node) => node.0.to_string(),
+            Value::Span(span) => span.to_string(),
+            Value::Text(text) => text.clone(),
+            Value::Type(ty) => ty.display(db).unwrap_or_default(),
+            Value::Number(number) => number.to_string(),
+        }
+    }
+}
+
+/// The rows returned by a query, with a value for each of its outputs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+fn get_query(name: &str) -> anyhow::Result<&'static YamlQuery<'static>> {
+    QUERIES
+        .as_ref()
+        .map_err(Clone::clone)?
         .get(name)
-        .ok_or_else(|| anyhow::anyhow!("no such query '{name}'"))?;
+        .ok_or_else(|| anyhow::anyhow!("no such query '{name}'"))
+}
 
-    let values = query.run(
-        db,
-        HashMap::from([(String::from("input"), Rc::new(input) as _)]),
-    );
+/// Parse the arguments to a query, written as `parameter=value`. Spans are
+/// written like `--query-span`, nodes as their number, and text as is.
+pub fn parse_arguments(
+    name: &str,
+    arguments: &[(String, String)],
+    line_index: &LineIndex,
+) -> anyhow::Result<QueryValues> {
+    let query = get_query(name)?;
 
-    Ok(values
+    arguments
+        .iter()
+        .map(|(parameter, argument)| {
+            let &(_, ty) = query
+                .parameters
+                .iter()
+                .find(|(name, _)| name == parameter)
+                .ok_or_else(|| anyhow::anyhow!("query '{name}' has no parameter '{parameter}'"))?;
+
+            let value: Rc<dyn FactValue> = if ty == ValueType::of::<Span>() {
+                let span = argument.parse::<ParsedSpan>()?;
+
+                Rc::new(
+                    span.to_span(line_index)
+                        .ok_or_else(|| anyhow::format_err!("invalid span: {span}"))?,
+                )
+            } else if ty == ValueType::of::<NodeId>() {
+                Rc::new(NodeId(
+                    argument
+                        .parse()
+                        .map_err(|_| anyhow::format_err!("invalid node: {argument}"))?,
+                ))
+            } else if ty == ValueType::of::<Source>() {
+                Rc::new(Source(argument.clone()))
+            } else if ty == ValueType::of::<usize>() {
+                Rc::new(
+                    argument
+                        .parse::<usize>()
+                        .map_err(|_| anyhow::format_err!("invalid number: {argument}"))?,
+                )
+            } else {
+                anyhow::bail!("can't pass a `{ty}` to parameter '{parameter}'");
+            };
+
+            Ok((parameter.clone(), value))
+        })
+        .collect()
+}
+
+pub fn run_query(name: &str, db: &Db, parameters: QueryValues) -> anyhow::Result<Table> {
+    let query = get_query(name)?;
+
+    let rows = query
+        .query
+        .run(db, parameters)
         .into_iter()
-        .filter_map(|mut values| {
-            values
-                .remove("output")
-                .and_then(|value| value.downcast_ref::<Span>().cloned())
+        .map(|row| {
+            row.iter()
+                .map(|value| Value::new(value.as_ref()))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| anyhow::anyhow!("query '{name}' returned an unsupported value"))
         })
-        .collect())
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(Table {
+        columns: query
+            .outputs
+            .iter()
+            .map(|(output, _)| output.clone())
+            .collect(),
+        rows,
+    })
 }
@@ -0,0 +1,31 @@
+use crate::matcher::matcher;
+use std::sync::LazyLock;
+use wipple_db::{Db, ParseError, Rules, Schema};
+
+static RULES: LazyLock<Result<(Rules, Schema), ParseError>> = LazyLock::new(|| {
+    let error = |error: ParseError| error.with_path("rules.txt");
+
+    let rules = include_str!("../rules.txt")
+        .parse::<Rules>()
+        .map_err(error)?;
+
+    let mut schema = crate::visit::schema::schema();
+    rules.register(&mut schema).map_err(error)?;
+
+    Ok((rules, schema))
+});
+
+/// The facts available to queries and feedback, including the ones derived
+/// from the rules in `rules.txt`.
+pub fn schema() -> Result<&'static Schema, ParseError> {
+    let (_, schema) = RULES.as_ref().map_err(Clone::clone)?;
+    Ok(schema)
+}
+
+/// Add the facts derived from the rules in `rules.txt`, so queries and
+/// feedback can refer to them like any other fact.
+pub fn derive_facts(db: &mut Db) -> Result<(), ParseError> {
+    let (rules, _) = RULES.as_ref().map_err(Clone::clone)?;
+    db.derive(rules, matcher);
+    Ok(())
+}
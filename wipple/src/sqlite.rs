@@ -0,0 +1,163 @@
+//! Export a solved program to SQLite, so results from many programs can be
+//! loaded into one database and analysed with SQL. Every table has a `file`
+//! column with the path of the program, and exporting a file again replaces
+//! its rows. See [`SCHEMA`] for the tables and their columns.
+
+use rusqlite::{Connection, params};
+use visualizer::TyGroups;
+use wipple_db::{Db, FactValue, NodeId, Source, Span};
+
+/// The tables written by [`export`].
+pub const SCHEMA: &str = r#"
+-- Every node with at least one fact
+CREATE TABLE IF NOT EXISTS nodes (
+    file TEXT NOT NULL,
+    id INTEGER NOT NULL,
+    -- The code the node was created from, if any
+    source TEXT,
+    -- 1 if the node was created while solving rather than from the code
+    hidden INTEGER NOT NULL,
+    PRIMARY KEY (file, id)
+);
+
+-- Every fact on every node, in the order they were added
+CREATE TABLE IF NOT EXISTS facts (
+    file TEXT NOT NULL,
+    node INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    -- The value as it's displayed in the output, or NULL for facts without
+    -- a value
+    value TEXT,
+    -- The node the fact refers to, if its value is a node
+    related_node INTEGER,
+    -- 1 if the fact was derived from `rules.txt`
+    derived INTEGER NOT NULL
+);
+
+-- Where each node is in the code, from its `span` fact
+CREATE TABLE IF NOT EXISTS spans (
+    file TEXT NOT NULL,
+    node INTEGER NOT NULL,
+    path TEXT NOT NULL,
+    -- The byte range, excluding `end_offset`
+    start_offset INTEGER NOT NULL,
+    end_offset INTEGER NOT NULL,
+    -- Lines and columns start at 1
+    start_line INTEGER NOT NULL,
+    start_column INTEGER NOT NULL,
+    end_line INTEGER NOT NULL,
+    end_column INTEGER NOT NULL
+);
+
+-- The type group each node belongs to; nodes in the same group were solved
+-- to have the same type
+CREATE TABLE IF NOT EXISTS type_group_nodes (
+    file TEXT NOT NULL,
+    type_group INTEGER NOT NULL,
+    node INTEGER NOT NULL
+);
+
+-- The types in each group, which has more than one if the group's nodes were
+-- used with conflicting types
+CREATE TABLE IF NOT EXISTS type_group_types (
+    file TEXT NOT NULL,
+    type_group INTEGER NOT NULL,
+    type TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS facts_by_name ON facts (file, name);
+CREATE INDEX IF NOT EXISTS facts_by_node ON facts (file, node);
+"#;
+
+const TABLES: &[&str] = &[
+    "nodes",
+    "facts",
+    "spans",
+    "type_group_nodes",
+    "type_group_types",
+];
+
+/// Write the nodes, facts, spans and type groups of the program at `file`,
+/// replacing any rows already exported for it.
+pub fn export(
+    db: &Db,
+    ty_groups: &TyGroups<Db>,
+    file: &str,
+    connection: &mut Connection,
+) -> rusqlite::Result<()> {
+    connection.execute_batch(SCHEMA)?;
+
+    let transaction = connection.transaction()?;
+
+    for table in TABLES {
+        transaction.execute(&format!("DELETE FROM {table} WHERE file = ?1"), [file])?;
+    }
+
+    {
+        let mut insert_node = transaction
+            .prepare("INSERT INTO nodes (file, id, source, hidden) VALUES (?1, ?2, ?3, ?4)")?;
+
+        let mut insert_fact = transaction.prepare(
+            "INSERT INTO facts (file, node, name, value, related_node, derived) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+
+        let mut insert_span = transaction.prepare(
+            "INSERT INTO spans (file, node, path, start_offset, end_offset, start_line, start_column, end_line, end_column) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )?;
+
+        for node in db.nodes() {
+            let source = db
+                .get::<Source>(node, "source")
+                .map(|source| source.0.as_str());
+
+            insert_node.execute(params![file, node.0, source, db.is_hidden(node)])?;
+
+            for fact in db.iter(node) {
+                let value = fact.value();
+
+                insert_fact.execute(params![
+                    file,
+                    node.0,
+                    fact.name(),
+                    value.display(db),
+                    value.downcast_ref::<NodeId>().map(|related| related.0),
+                    db.is_derived(fact.name()),
+                ])?;
+            }
+
+            for span in db.iter_of::<Span>(node, "span") {
+                insert_span.execute(params![
+                    file,
+                    node.0,
+                    span.path.as_ref(),
+                    span.range.start,
+                    span.range.end,
+                    span.start_line_col.0,
+                    span.start_line_col.1,
+                    span.end_line_col.0,
+                    span.end_line_col.1,
+                ])?;
+            }
+        }
+
+        let mut insert_group_node = transaction
+            .prepare("INSERT INTO type_group_nodes (file, type_group, node) VALUES (?1, ?2, ?3)")?;
+
+        let mut insert_group_type = transaction
+            .prepare("INSERT INTO type_group_types (file, type_group, type) VALUES (?1, ?2, ?3)")?;
+
+        for node in ty_groups.nodes() {
+            if let Some(index) = ty_groups.index_of(node) {
+                insert_group_node.execute(params![file, index, node.0])?;
+            }
+        }
+
+        for (index, tys) in ty_groups.groups() {
+            for ty in tys {
+                insert_group_type.execute(params![file, index, ty.display(db)])?;
+            }
+        }
+    }
+
+    transaction.commit()
+}